@@ -0,0 +1,278 @@
+//! Pre-flight checks run on a bot module before it is compiled and instantiated.
+
+use std::fmt;
+
+use serde::Serialize;
+use wasmparser::{Parser, Payload, TypeRef, Validator};
+
+/// Imports provided by the machine to every bot.
+pub const DEFAULT_ALLOWED_IMPORTS: &[(&str, &str)] = &[
+    ("env", "print_u32"),
+    ("env", "log"),
+    ("env", "log_f32"),
+    ("env", "log_level"),
+    ("env", "storage_read"),
+    ("env", "storage_write"),
+];
+
+/// What a bot module is allowed to contain.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// (module, name) of the functions a bot may import.
+    pub allowed_imports: Vec<(String, String)>,
+    pub max_functions: u32,
+    pub max_table_size: u64,
+    /// Pages (64 KiB) the memory may start with.
+    pub max_initial_memory_pages: u64,
+    /// Pages the memory may declare as its maximum.
+    pub max_memory_pages: u64,
+    /// Accept a memory that declares no maximum, for runtimes that cap it themselves.
+    pub allow_unbounded_memory: bool,
+    /// Total size of the data segments.
+    pub max_data_bytes: usize,
+    /// Total size of the custom sections (names, producers, debug info, ...).
+    pub max_custom_section_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            allowed_imports: DEFAULT_ALLOWED_IMPORTS
+                .iter()
+                .map(|(module, name)| (module.to_string(), name.to_string()))
+                .collect(),
+            max_functions: 10_000,
+            max_table_size: 10_000,
+            max_initial_memory_pages: 256,
+            max_memory_pages: 256,
+            allow_unbounded_memory: false,
+            max_data_bytes: 1_000_000,
+            max_custom_section_bytes: 256_000,
+        }
+    }
+}
+
+/// A rule the module breaks.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    Malformed { message: String },
+    ForbiddenImport { module: String, name: String },
+    StartFunction,
+    TooManyFunctions { count: u32, limit: u32 },
+    TableTooLarge { size: u64, limit: u64 },
+    InitialMemoryTooLarge { pages: u64, limit: u64 },
+    MaximumMemoryTooLarge { pages: u64, limit: u64 },
+    UnboundedMemory,
+    DataTooLarge { bytes: usize, limit: usize },
+    CustomSectionsTooLarge { bytes: usize, limit: usize },
+    MissingExport { name: String },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Malformed { message } => write!(f, "malformed module: {}", message),
+            Violation::ForbiddenImport { module, name } => {
+                write!(f, "import `{}.{}` is not allowed", module, name)
+            }
+            Violation::StartFunction => write!(f, "start functions are not allowed"),
+            Violation::TooManyFunctions { count, limit } => {
+                write!(f, "{} functions (limit {})", count, limit)
+            }
+            Violation::TableTooLarge { size, limit } => {
+                write!(f, "table of {} elements (limit {})", size, limit)
+            }
+            Violation::InitialMemoryTooLarge { pages, limit } => {
+                write!(f, "memory starts with {} pages (limit {})", pages, limit)
+            }
+            Violation::MaximumMemoryTooLarge { pages, limit } => {
+                write!(f, "memory can grow to {} pages (limit {})", pages, limit)
+            }
+            Violation::UnboundedMemory => write!(f, "memory without a maximum"),
+            Violation::DataTooLarge { bytes, limit } => {
+                write!(f, "{} bytes of data segments (limit {})", bytes, limit)
+            }
+            Violation::CustomSectionsTooLarge { bytes, limit } => {
+                write!(f, "{} bytes of custom sections (limit {})", bytes, limit)
+            }
+            Violation::MissingExport { name } => write!(f, "missing export `{}`", name),
+        }
+    }
+}
+
+/// What was found in the module, and what is wrong with it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Report {
+    pub imports: Vec<String>,
+    pub exports: Vec<String>,
+    pub functions: u32,
+    pub table_size: u64,
+    pub initial_memory_pages: u64,
+    pub has_memory: bool,
+    pub maximum_memory_pages: Option<u64>,
+    pub data_bytes: usize,
+    pub custom_section_bytes: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.violations.is_empty() {
+            return write!(f, "valid bot module");
+        }
+        write!(f, "invalid bot module:")?;
+        for violation in self.violations.iter() {
+            write!(f, "\n  - {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check a bot module (binary format) against the limits.
+pub fn validate(wasm: &[u8], limits: &Limits) -> Report {
+    let mut report = Report::default();
+
+    if let Err(err) = Validator::new().validate_all(wasm) {
+        report.violations.push(Violation::Malformed {
+            message: err.to_string(),
+        });
+        return report;
+    }
+
+    if let Err(err) = inspect(wasm, limits, &mut report) {
+        report.violations.push(Violation::Malformed {
+            message: err.to_string(),
+        });
+        return report;
+    }
+
+    if report.functions > limits.max_functions {
+        report.violations.push(Violation::TooManyFunctions {
+            count: report.functions,
+            limit: limits.max_functions,
+        });
+    }
+    if report.table_size > limits.max_table_size {
+        report.violations.push(Violation::TableTooLarge {
+            size: report.table_size,
+            limit: limits.max_table_size,
+        });
+    }
+    if report.initial_memory_pages > limits.max_initial_memory_pages {
+        report.violations.push(Violation::InitialMemoryTooLarge {
+            pages: report.initial_memory_pages,
+            limit: limits.max_initial_memory_pages,
+        });
+    }
+    match report.maximum_memory_pages {
+        Some(pages) if pages > limits.max_memory_pages => {
+            report.violations.push(Violation::MaximumMemoryTooLarge {
+                pages,
+                limit: limits.max_memory_pages,
+            });
+        }
+        None if report.has_memory && !limits.allow_unbounded_memory => {
+            report.violations.push(Violation::UnboundedMemory)
+        }
+        _ => {}
+    }
+    if report.data_bytes > limits.max_data_bytes {
+        report.violations.push(Violation::DataTooLarge {
+            bytes: report.data_bytes,
+            limit: limits.max_data_bytes,
+        });
+    }
+    if report.custom_section_bytes > limits.max_custom_section_bytes {
+        report.violations.push(Violation::CustomSectionsTooLarge {
+            bytes: report.custom_section_bytes,
+            limit: limits.max_custom_section_bytes,
+        });
+    }
+
+    let exports = |name: &str| report.exports.iter().any(|export| export == name);
+    let mut missing = Vec::new();
+    let with_context = exports("context_buffer") && exports("init_with_context");
+    if !exports("init") && !with_context {
+        missing.push("init");
+    }
+    let extended = exports("observation_buffer") && exports("compute_actions_ext");
+    if !exports("compute_actions") && !extended {
+        missing.push("compute_actions");
+    }
+    for name in missing {
+        report.violations.push(Violation::MissingExport {
+            name: name.to_string(),
+        });
+    }
+
+    report
+}
+
+fn inspect(wasm: &[u8], limits: &Limits, report: &mut Report) -> wasmparser::Result<()> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    let import = import?;
+                    report
+                        .imports
+                        .push(format!("{}.{}", import.module, import.name));
+                    let allowed = matches!(import.ty, TypeRef::Func(_))
+                        && limits
+                            .allowed_imports
+                            .iter()
+                            .any(|(module, name)| module == import.module && name == import.name);
+                    if !allowed {
+                        report.violations.push(Violation::ForbiddenImport {
+                            module: import.module.to_string(),
+                            name: import.name.to_string(),
+                        });
+                    }
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    report.exports.push(export?.name.to_string());
+                }
+            }
+            Payload::StartSection { .. } => {
+                report.violations.push(Violation::StartFunction);
+            }
+            Payload::FunctionSection(reader) => {
+                report.functions = reader.count();
+            }
+            Payload::TableSection(reader) => {
+                for table in reader {
+                    let table = table?;
+                    let size = table.ty.maximum.unwrap_or(table.ty.initial);
+                    report.table_size = report.table_size.max(size);
+                }
+            }
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    let memory = memory?;
+                    report.has_memory = true;
+                    report.initial_memory_pages = memory.initial;
+                    report.maximum_memory_pages = memory.maximum;
+                }
+            }
+            Payload::DataSection(reader) => {
+                for data in reader {
+                    report.data_bytes += data?.data.len();
+                }
+            }
+            Payload::CustomSection(reader) => {
+                report.custom_section_bytes += reader.name().len() + reader.data().len();
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
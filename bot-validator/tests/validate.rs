@@ -0,0 +1,133 @@
+use bot_validator::{validate, Limits, Report, Violation};
+
+fn check(wat: &str) -> Report {
+    validate(&wat::parse_str(wat).unwrap(), &Limits::default())
+}
+
+#[test]
+fn a_valid_bot_passes() {
+    let report = check(
+        r#"(module
+            (import "env" "log" (func (param i32 i32)))
+            (memory (export "memory") 1 16)
+            (func (export "init") (param i32))
+            (func (export "compute_actions")
+              (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+              (result i32)
+              i32.const 0))"#,
+    );
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(report.imports, ["env.log"]);
+    assert_eq!(report.maximum_memory_pages, Some(16));
+}
+
+#[test]
+fn a_disallowed_import_is_reported() {
+    let report = check(
+        r#"(module
+            (import "wasi_snapshot_preview1" "fd_write"
+              (func (param i32 i32 i32 i32) (result i32)))
+            (func (export "init") (param i32))
+            (func (export "compute_actions")
+              (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+              (result i32)
+              i32.const 0))"#,
+    );
+    assert_eq!(
+        report.violations,
+        [Violation::ForbiddenImport {
+            module: "wasi_snapshot_preview1".to_string(),
+            name: "fd_write".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn too_many_memory_pages_are_reported() {
+    let report = check(
+        r#"(module
+            (memory 300 400)
+            (func (export "init") (param i32))
+            (func (export "compute_actions")
+              (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+              (result i32)
+              i32.const 0))"#,
+    );
+    assert_eq!(
+        report.violations,
+        [
+            Violation::InitialMemoryTooLarge {
+                pages: 300,
+                limit: 256
+            },
+            Violation::MaximumMemoryTooLarge {
+                pages: 400,
+                limit: 256
+            },
+        ]
+    );
+}
+
+#[test]
+fn a_memory_without_maximum_is_reported() {
+    let report = check(
+        r#"(module
+            (memory 1)
+            (func (export "init") (param i32))
+            (func (export "compute_actions")
+              (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+              (result i32)
+              i32.const 0))"#,
+    );
+    assert_eq!(report.violations, [Violation::UnboundedMemory]);
+}
+
+#[test]
+fn a_table_too_large_is_reported() {
+    let report = check(
+        r#"(module
+            (table 1 20000 funcref)
+            (func (export "init") (param i32))
+            (func (export "compute_actions")
+              (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+              (result i32)
+              i32.const 0))"#,
+    );
+    assert_eq!(
+        report.violations,
+        [Violation::TableTooLarge {
+            size: 20000,
+            limit: 10000
+        }]
+    );
+}
+
+#[test]
+fn a_missing_export_is_reported() {
+    let report = check(r#"(module (func (export "init") (param i32)))"#);
+    assert_eq!(
+        report.violations,
+        [Violation::MissingExport {
+            name: "compute_actions".to_string()
+        }]
+    );
+}
+
+#[test]
+fn a_memory_without_maximum_can_be_allowed() {
+    let wasm = wat::parse_str(
+        r#"(module
+            (memory 1)
+            (func (export "init") (param i32))
+            (func (export "compute_actions")
+              (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+              (result i32)
+              i32.const 0))"#,
+    )
+    .unwrap();
+    let limits = Limits {
+        allow_unbounded_memory: true,
+        ..Limits::default()
+    };
+    assert!(validate(&wasm, &limits).is_valid());
+}
@@ -26,6 +26,10 @@ pub extern "C" fn compute_actions(
 
     // Normalize the vector
     let magnitude = (to_ball[0].powi(2) + to_ball[1].powi(2) + to_ball[2].powi(2)).sqrt();
+    if magnitude == 0.0 {
+        // already on the ball: the direction is undefined, do nothing (128 is 0)
+        return 0x80_80_80;
+    }
     let normalized_to_ball = [
         to_ball[0] / magnitude,
         to_ball[1] / magnitude,
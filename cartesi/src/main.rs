@@ -1,50 +1,168 @@
+use bot_validator::validate;
 use core::str;
 use json::{object, JsonValue};
 use lz4_flex::decompress;
-use machine::Battle;
-use std::{env, slice};
+use machine::{play_series, validator_limits, Battle, MatchMode, Rules};
+use std::collections::HashMap;
+use std::env;
 
-fn execute_battle(payload: &str) {
-    let s = unsafe {
-        // First, we build a &[u8]...
-        let slice = slice::from_raw_parts(payload.as_ptr().add(2), payload.len() - 2);
+/// What each bot stored at the end of its last match, by bot hash.
+type BotStorage = HashMap<String, Vec<u8>>;
 
-        // ... and then convert that slice into a string slice
-        str::from_utf8(slice)
+/// Bytes of a `0x` prefixed hex string.
+fn decode_hex(payload: &str) -> Result<Vec<u8>, String> {
+    let digits = payload
+        .strip_prefix("0x")
+        .ok_or("hex string without 0x prefix")?;
+    hex::decode(digits).map_err(|err| format!("invalid hex: {}", err))
+}
+
+/// Decompress a bot and check it before it gets anywhere near the machine.
+fn load_bot(compressed_bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let wasm_bytes = decompress(compressed_bytes, 1000000)
+        .map_err(|err| format!("failed to decompress in to 1,000,000 bytes: {}", err))?;
+    let report = validate(&wasm_bytes, &validator_limits(&rules()));
+    if !report.is_valid() {
+        return Err(report.to_string());
     }
-    .expect("failed to slice payload");
+    Ok(wasm_bytes)
+}
+
+/// Rule set of the battles played by the dapp. Setting `DROP_BOT_LOGS` in the environment
+/// keeps what the bots log out of the machine output.
+fn rules() -> Rules {
+    let mut rules = Rules::default();
+    if env::var_os("DROP_BOT_LOGS").is_some() {
+        rules.logs = None;
+    }
+    rules
+}
+
+fn parse_roster(payload_bytes: &[u8]) -> Result<JsonValue, String> {
+    let roster =
+        str::from_utf8(payload_bytes).map_err(|err| format!("roster is not utf8: {}", err))?;
+    json::parse(roster).map_err(|err| format!("failed to parse roster: {}", err))
+}
 
-    let compressed_bytes = hex::decode(s).expect("failed to decode payload");
+/// A roster is a JSON document (hex encoded in the payload):
+/// `{"mode": "teams" | "free-for-all", "bots": [{"wasm": "0x<lz4 wasm>", "team": 0}, ...]}`
+/// where `team` is optional. Any other payload is a single compressed bot playing itself.
+fn create_battle(payload_bytes: &[u8]) -> Result<Battle, String> {
+    if payload_bytes.first() != Some(&b'{') {
+        let mut battle = Battle::new_with_rules(MatchMode::Teams, rules());
+        for i in 1..=2 {
+            let mut wasm_bytes = load_bot(payload_bytes)?;
+            println!("addding bot {} ...", i);
+            battle.add_bot(&mut wasm_bytes)?;
+        }
+        return Ok(battle);
+    }
 
-    let mut wasm_bytes_1 =
-        decompress(&compressed_bytes, 1000000).expect("failed to decompress in to 1,000,000 bytes");
-    let wasm_bytes_1: &mut [u8] = &mut wasm_bytes_1; //cast to `&mut [u8]`
+    let roster = parse_roster(payload_bytes)?;
+    let mode = match roster["mode"].as_str() {
+        Some("free-for-all") => MatchMode::FreeForAll,
+        _ => MatchMode::Teams,
+    };
+    let mut battle = Battle::new_with_rules(mode, rules());
+    for (i, bot) in roster["bots"].members().enumerate() {
+        let wasm = bot["wasm"]
+            .as_str()
+            .ok_or_else(|| format!("bot {} without wasm", i + 1))?;
+        let mut wasm_bytes = load_bot(&decode_hex(wasm)?)?;
+        println!("addding bot {} ...", i + 1);
+        match bot["team"].as_u8() {
+            Some(team) => battle.add_bot_to_team(&mut wasm_bytes, team)?,
+            None => battle.add_bot(&mut wasm_bytes)?,
+        };
+    }
+    Ok(battle)
+}
 
-    let mut wasm_bytes_2 =
-        decompress(&compressed_bytes, 1000000).expect("failed to decompress in to 1,000,000 bytes");
-    let wasm_bytes_2: &mut [u8] = &mut wasm_bytes_2; //cast to `&mut [u8]`
+/// With `"fair": true`, a team roster is played twice with sides swapped instead.
+fn execute_series(roster: &JsonValue) -> Result<(), String> {
+    let mut sides: [Vec<Vec<u8>>; 2] = [Vec::new(), Vec::new()];
+    for (i, bot) in roster["bots"].members().enumerate() {
+        let wasm = bot["wasm"]
+            .as_str()
+            .ok_or_else(|| format!("bot {} without wasm", i + 1))?;
+        let team = bot["team"].as_usize().unwrap_or(i % 2);
+        sides
+            .get_mut(team)
+            .ok_or_else(|| {
+                format!(
+                    "bot {} is in team {}, a fair series is played by teams 0 and 1",
+                    i + 1,
+                    team
+                )
+            })?
+            .push(load_bot(&decode_hex(wasm)?)?);
+    }
 
-    let mut battle = Battle::new();
-    println!("addding bot 1 ...");
-    battle.add_bot(wasm_bytes_1);
-    println!("addding bot 2 ...");
-    battle.add_bot(wasm_bytes_2);
+    println!("series!");
+    let series = play_series(&sides[0], &sides[1], &rules(), None)?;
+    println!(
+        "series: {} - {} ({} draws), winner: {}",
+        series.wins_a,
+        series.wins_b,
+        series.draws,
+        series.winner()
+    );
+    Ok(())
+}
+
+fn execute_battle(payload: &str, match_id: &str, storage: &mut BotStorage) -> Result<(), String> {
+    let payload_bytes = decode_hex(payload)?;
+    if payload_bytes.first() == Some(&b'{') {
+        let roster = parse_roster(&payload_bytes)?;
+        if roster["fair"].as_bool() == Some(true) {
+            return execute_series(&roster);
+        }
+    }
+
+    let mut battle = create_battle(&payload_bytes)?;
+    battle.set_match_id(match_id.as_bytes());
+    for index in 0..battle.num_bots() {
+        if let Some(stored) = storage.get(&battle.get_bot_hash(index)) {
+            battle.set_bot_storage(index, stored.clone())?;
+        }
+    }
 
     println!("battle!");
-    battle.execute();
+    let winner = battle.execute()?;
+    for index in 0..battle.num_bots() {
+        storage.insert(battle.get_bot_hash(index), battle.get_bot_storage(index));
+    }
+    for log in battle.logs() {
+        println!("{}", log);
+    }
+    println!("winner: {}", winner);
+    for index in 0..battle.num_bots() {
+        let faults = battle.bot_faults(index);
+        if faults.total() > 0 {
+            println!("bot {} faults: {:?}", index + 1, faults);
+        }
+    }
+    Ok(())
 }
 
 pub async fn handle_advance(
     _client: &hyper::Client<hyper::client::HttpConnector>,
     _server_addr: &str,
     request: JsonValue,
+    storage: &mut BotStorage,
 ) -> Result<&'static str, Box<dyn std::error::Error>> {
     println!("Received advance request data {}", &request);
     let payload = request["data"]["payload"]
         .as_str()
         .ok_or("Missing payload")?;
 
-    execute_battle(payload);
+    // the input index identifies the match to the bots
+    let match_id = request["data"]["metadata"]["input_index"].to_string();
+
+    if let Err(err) = execute_battle(payload, &match_id, storage) {
+        println!("Rejecting battle: {}", err);
+        return Ok("reject");
+    }
 
     Ok("accept")
 }
@@ -67,6 +185,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = hyper::Client::new();
     let server_addr = env::var("ROLLUP_HTTP_SERVER_URL")?;
 
+    let mut storage = BotStorage::new();
     let mut status = "accept";
     loop {
         println!("Sending finish");
@@ -90,7 +209,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .as_str()
                 .ok_or("request_type is not a string")?;
             status = match request_type {
-                "advance_state" => handle_advance(&client, &server_addr[..], req).await?,
+                "advance_state" => {
+                    handle_advance(&client, &server_addr[..], req, &mut storage).await?
+                }
                 "inspect_state" => handle_inspect(&client, &server_addr[..], req).await?,
                 &_ => {
                     eprintln!("Unknown request type");
@@ -0,0 +1,281 @@
+//! `machine-executor batch`: play every matchup of a manifest on a pool of threads, each
+//! match in its own `Battle`, and stream one JSON line per match.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use machine::{play_series, Battle, FaultCounts, GasBank, MatchMode, ModuleCache, Rules};
+use serde::{Deserialize, Serialize};
+
+use crate::input::load_bot;
+
+/// File given to `batch`:
+///
+/// ```json
+/// {
+///   "matches": [
+///     { "bot1": "a.json", "bot2": "b.json", "seeds": ["1", "2", "3"] },
+///     { "bot1": "a.json", "bot2": "c.json", "fair": true, "max_ticks": 5000, "gas_bank": true }
+///   ]
+/// }
+/// ```
+///
+/// Bots are given like `--bot` (see [`load_bot`]), relative paths being resolved from the
+/// manifest.
+#[derive(Deserialize)]
+struct Manifest {
+    matches: Vec<Matchup>,
+}
+
+#[derive(Deserialize)]
+struct Matchup {
+    bot1: String,
+    bot2: String,
+    /// One match per seed, each used as the match id. Defaults to a single match whose id
+    /// is the index of the matchup.
+    #[serde(default)]
+    seeds: Vec<String>,
+    /// Play a series with the sides swapped instead of a single match.
+    #[serde(default)]
+    fair: bool,
+    /// Overrides `Rules::max_ticks`.
+    max_ticks: Option<u64>,
+    /// Enables the default gas bank.
+    #[serde(default)]
+    gas_bank: bool,
+}
+
+impl Matchup {
+    fn rules(&self) -> Rules {
+        let mut rules = Rules::default();
+        if let Some(max_ticks) = self.max_ticks {
+            rules.max_ticks = max_ticks;
+        }
+        if self.gas_bank {
+            rules.gas_bank = Some(GasBank::default());
+        }
+        rules
+    }
+}
+
+/// A single match (or series) to play.
+struct Job {
+    index: usize,
+    matchup: usize,
+    match_id: String,
+}
+
+/// Line written for every match.
+#[derive(Serialize)]
+struct MatchResult<'a> {
+    index: usize,
+    bot1: &'a str,
+    bot2: &'a str,
+    match_id: &'a str,
+    /// 0 for a draw, 1 or 2 for the winning bot. For a series, the bot that won more legs.
+    winner: u8,
+    /// Updates played, 0 for a series.
+    ticks: u64,
+    /// Faults of each bot, only for single matches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    faults: Option<[FaultCounts; 2]>,
+    /// Legs won by each bot and drawn, only for series.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    series: Option<[u32; 3]>,
+    elapsed_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct Outcome {
+    winner: u8,
+    ticks: u64,
+    faults: Option<[FaultCounts; 2]>,
+    series: Option<[u32; 3]>,
+}
+
+fn play(
+    matchup: &Matchup,
+    match_id: &str,
+    bots: &HashMap<String, Result<Vec<u8>, String>>,
+    cache: &Arc<ModuleCache>,
+) -> Result<Outcome, String> {
+    let bot = |bot: &String| {
+        bots[bot]
+            .as_ref()
+            .map_err(|err| format!("{}: {}", bot, err))
+    };
+    let bot1 = bot(&matchup.bot1)?;
+    let bot2 = bot(&matchup.bot2)?;
+    let rules = matchup.rules();
+
+    if matchup.fair {
+        let series = play_series(
+            std::slice::from_ref(bot1),
+            std::slice::from_ref(bot2),
+            &rules,
+            Some(cache.clone()),
+        )?;
+        return Ok(Outcome {
+            winner: series.winner(),
+            // series do not report their length
+            ticks: 0,
+            faults: None,
+            series: Some([series.wins_a, series.wins_b, series.draws]),
+        });
+    }
+
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.set_module_cache(cache.clone());
+    battle.set_match_id(match_id.as_bytes());
+    battle.add_bot(&mut bot1.clone())?;
+    battle.add_bot(&mut bot2.clone())?;
+    let winner = battle.execute()?;
+    Ok(Outcome {
+        winner,
+        ticks: battle.get_tick(),
+        faults: Some([battle.bot_faults(0).clone(), battle.bot_faults(1).clone()]),
+        series: None,
+    })
+}
+
+pub fn run(
+    manifest_path: &Path,
+    threads: Option<usize>,
+    output: Option<&PathBuf>,
+    cache: ModuleCache,
+) -> Result<(), String> {
+    let manifest: Manifest = serde_json::from_str(
+        &fs::read_to_string(manifest_path)
+            .map_err(|err| format!("failed to read {}: {}", manifest_path.display(), err))?,
+    )
+    .map_err(|err| format!("invalid manifest {}: {}", manifest_path.display(), err))?;
+
+    // every bot is decoded once and compiled once, whatever the number of matches it plays,
+    // and one that cannot be decoded only fails the matches it plays
+    let base = manifest_path.parent().unwrap_or(Path::new("."));
+    let mut bots = HashMap::new();
+    for matchup in &manifest.matches {
+        for bot in [&matchup.bot1, &matchup.bot2] {
+            if !bots.contains_key(bot) {
+                let path = base.join(bot);
+                let input = if path.is_file() {
+                    path.to_string_lossy().into_owned()
+                } else {
+                    bot.clone()
+                };
+                bots.insert(bot.clone(), load_bot(&input));
+            }
+        }
+    }
+    let cache = Arc::new(cache);
+
+    let mut jobs = Vec::new();
+    for (matchup_index, matchup) in manifest.matches.iter().enumerate() {
+        let seeds = if matchup.seeds.is_empty() {
+            vec![matchup_index.to_string()]
+        } else {
+            matchup.seeds.clone()
+        };
+        for match_id in seeds {
+            jobs.push(Job {
+                index: jobs.len(),
+                matchup: matchup_index,
+                match_id,
+            });
+        }
+    }
+
+    let threads = threads
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .clamp(1, jobs.len().max(1));
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|err| {
+                format!("failed to create {}: {}", path.display(), err)
+            })?))
+        }
+        None => Box::new(io::stdout()),
+    };
+
+    eprintln!("{} matches on {} threads", jobs.len(), threads);
+    let start = Instant::now();
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut played = 0;
+    let mut failed = 0;
+    let mut ticks = 0;
+    thread::scope(|scope| -> Result<(), String> {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (jobs, next, manifest, bots, cache) = (&jobs, &next, &manifest, &bots, &cache);
+            scope.spawn(move || {
+                while let Some(job) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let started = Instant::now();
+                    // a match taking the machine down must not take the whole batch with it
+                    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+                        play(&manifest.matches[job.matchup], &job.match_id, bots, cache)
+                    }))
+                    .unwrap_or_else(|_| Err("the match panicked".to_string()));
+                    if sender.send((job, outcome, started.elapsed())).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // results are written as they come, not in the order of the manifest
+        for (job, outcome, elapsed) in receiver {
+            let (outcome, error) = match outcome {
+                Ok(outcome) => (outcome, None),
+                Err(err) => (Outcome::default(), Some(err)),
+            };
+            played += 1;
+            failed += error.is_some() as usize;
+            ticks += outcome.ticks;
+            let matchup = &manifest.matches[job.matchup];
+            let line = serde_json::to_string(&MatchResult {
+                index: job.index,
+                bot1: &matchup.bot1,
+                bot2: &matchup.bot2,
+                match_id: &job.match_id,
+                winner: outcome.winner,
+                ticks: outcome.ticks,
+                faults: outcome.faults,
+                series: outcome.series,
+                elapsed_ms: elapsed.as_millis(),
+                error,
+            })
+            .map_err(|err| err.to_string())?;
+            writeln!(out, "{}", line).map_err(|err| format!("failed to write result: {}", err))?;
+        }
+        Ok(())
+    })?;
+    out.flush()
+        .map_err(|err| format!("failed to write results: {}", err))?;
+
+    report_throughput(played, failed, ticks, start.elapsed());
+    Ok(())
+}
+
+fn report_throughput(played: usize, failed: usize, ticks: u64, elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    eprintln!(
+        "{} matches ({} failed) in {:.1}s: {:.2} matches/s, {:.0} ticks/s",
+        played,
+        failed,
+        seconds,
+        played as f64 / seconds,
+        ticks as f64 / seconds
+    );
+}
@@ -0,0 +1,208 @@
+//! `machine-executor gym`: let a training script drive a [`VecEnv`] over a line protocol,
+//! one JSON request per line and one JSON response per line, on stdin and stdout or on a
+//! TCP connection:
+//!
+//! ```json
+//! {"cmd": "reset", "seed": 1}
+//! {"num_envs": 2, "observations": [[...], [...]]}
+//! {"cmd": "step", "actions": [{"impulse": [1, 0, 0], "boost": false}, {"impulse": [0, 0, 1]}]}
+//! {"steps": [{"observation": [...], "reward": 0.0, "done": false, "info": {...}}, ...]}
+//! {"cmd": "close"}
+//! ```
+//!
+//! A request that fails gets `{"error": "..."}` and the session goes on.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::TcpListener;
+
+use bot_validator::validate;
+use clap::Args;
+use machine::{
+    validator_limits, Action, EnvConfig, GasBank, Opponent, ReferenceBot, RewardShaping, Rules,
+    Step, VecEnv,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::input::load_bot;
+
+#[derive(Args)]
+pub struct GymArgs {
+    /// Environments stepped together, each playing its own matches
+    #[arg(long, default_value_t = 1)]
+    envs: usize,
+
+    /// Built-in opponent of the agent: idle, chaser, goalkeeper, random or scripted.
+    /// Defaults to chaser
+    #[arg(long, conflicts_with = "bot")]
+    opponent: Option<ReferenceBot>,
+
+    /// Compiled bot playing against the agent instead, given like --bot
+    #[arg(long)]
+    bot: Option<String>,
+
+    /// Team of the agent, 0 or 1
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=1))]
+    team: u8,
+
+    /// Reward weights as JSON, inline or in a file: {"goal", "ball_progress",
+    /// "ball_distance", "time", "fault"}. Only goals count by default
+    #[arg(long)]
+    reward: Option<String>,
+
+    /// Ticks before a match is a draw
+    #[arg(long)]
+    max_ticks: Option<u64>,
+
+    /// Enable the default gas bank
+    #[arg(long)]
+    gas_bank: bool,
+
+    /// Serve on this TCP address (for example 127.0.0.1:5555) instead of stdin and stdout,
+    /// one client at a time, each with fresh environments
+    #[arg(long)]
+    listen: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    Reset {
+        #[serde(default)]
+        seed: u64,
+    },
+    Step {
+        actions: Vec<ActionRequest>,
+    },
+    Close,
+}
+
+#[derive(Deserialize)]
+struct ActionRequest {
+    impulse: [f32; 3],
+    #[serde(default)]
+    boost: bool,
+}
+
+impl From<ActionRequest> for Action {
+    fn from(action: ActionRequest) -> Action {
+        Action {
+            impulse: action.impulse,
+            boost: action.boost,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response {
+    Reset {
+        num_envs: usize,
+        observations: Vec<Vec<f32>>,
+    },
+    Step {
+        steps: Vec<Step>,
+    },
+    Error {
+        error: String,
+    },
+}
+
+pub fn run(args: &GymArgs) -> Result<(), String> {
+    let config = config(args)?;
+    // checked once up front rather than on every connection
+    VecEnv::new(config.clone(), args.envs)?;
+
+    match &args.listen {
+        None => serve(&config, args.envs, io::stdin().lock(), io::stdout().lock()),
+        Some(address) => {
+            let listener = TcpListener::bind(address)
+                .map_err(|err| format!("can not listen on {}: {}", address, err))?;
+            eprintln!("listening on {}", address);
+            for stream in listener.incoming() {
+                let stream = stream.map_err(|err| err.to_string())?;
+                let reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+                if let Err(err) = serve(&config, args.envs, reader, stream) {
+                    eprintln!("connection closed: {}", err);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn config(args: &GymArgs) -> Result<EnvConfig, String> {
+    let mut rules = Rules::default();
+    if let Some(max_ticks) = args.max_ticks {
+        rules.max_ticks = max_ticks;
+    }
+    if args.gas_bank {
+        rules.gas_bank = Some(GasBank::default());
+    }
+    let opponent = match (&args.bot, &args.opponent) {
+        (Some(bot), _) => {
+            let wasm = load_bot(bot).map_err(|err| format!("--bot: {}", err))?;
+            let report = validate(&wasm, &validator_limits(&rules));
+            if !report.is_valid() {
+                return Err(format!("--bot: invalid bot\n{}", report));
+            }
+            Opponent::Bot(wasm)
+        }
+        (None, Some(opponent)) => Opponent::Reference(opponent.clone()),
+        (None, None) => Opponent::Reference(ReferenceBot::Chaser),
+    };
+    let reward = match &args.reward {
+        Some(reward) => load_reward(reward)?,
+        None => RewardShaping::default(),
+    };
+    Ok(EnvConfig {
+        rules,
+        team: args.team,
+        opponents: vec![opponent],
+        reward,
+    })
+}
+
+/// Reward weights given inline when they look like JSON, otherwise read from a file.
+fn load_reward(reward: &str) -> Result<RewardShaping, String> {
+    let json = if reward.trim_start().starts_with('{') {
+        reward.to_string()
+    } else {
+        fs::read_to_string(reward).map_err(|err| format!("--reward {}: {}", reward, err))?
+    };
+    serde_json::from_str(&json).map_err(|err| format!("--reward: {}", err))
+}
+
+/// Answer the requests of a client until it closes the session or disconnects.
+fn serve(
+    config: &EnvConfig,
+    num_envs: usize,
+    reader: impl BufRead,
+    writer: impl Write,
+) -> Result<(), String> {
+    let mut envs = VecEnv::new(config.clone(), num_envs)?;
+    let mut writer = BufWriter::new(writer);
+    for line in reader.lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Close) => break,
+            Ok(Request::Reset { seed }) => envs.reset(seed).map(|observations| Response::Reset {
+                num_envs: envs.len(),
+                observations,
+            }),
+            Ok(Request::Step { actions }) => {
+                let actions: Vec<Action> = actions.into_iter().map(Action::from).collect();
+                envs.step(&actions).map(|steps| Response::Step { steps })
+            }
+            Err(err) => Err(format!("invalid request: {}", err)),
+        };
+        let response = response.unwrap_or_else(|error| Response::Error { error });
+        serde_json::to_writer(&mut writer, &response).map_err(|err| err.to_string())?;
+        writeln!(writer).map_err(|err| err.to_string())?;
+        writer.flush().map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
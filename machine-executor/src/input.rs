@@ -0,0 +1,96 @@
+//! Bots given on the command line: a path to a file or the content itself, in any of the
+//! formats we hand bots around in.
+
+use std::fs;
+use std::path::Path;
+
+use lz4_flex::decompress;
+use serde::Deserialize;
+
+/// Magic bytes starting every wasm module.
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Upper bound of a decompressed bot, same as the dapp.
+const MAX_WASM_SIZE: usize = 1_000_000;
+
+/// Output of `wasm2hex`.
+#[derive(Deserialize)]
+struct WasmData {
+    wasm: String,
+}
+
+/// Load a bot from `input`, a path to a file or the content itself. Accepted, whether in a
+/// file or not:
+///
+/// - a raw wasm module,
+/// - an lz4 compressed wasm module,
+/// - either of them hex encoded, with or without `0x`,
+/// - the JSON written by `wasm2hex`.
+///
+/// The format is told by its content, not by the file extension.
+pub fn load_bot(input: &str) -> Result<Vec<u8>, String> {
+    let path = Path::new(input);
+    if path.is_file() {
+        let bytes =
+            fs::read(path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        decode(&bytes).map_err(|err| format!("{}: {}", path.display(), err))
+    } else if looks_like_hex(input.as_bytes()) || input.trim_start().starts_with('{') {
+        decode(input.as_bytes())
+    } else {
+        Err(format!(
+            "{} is neither a file nor a hex encoded bot",
+            abbreviate(input)
+        ))
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.starts_with(WASM_MAGIC) {
+        return Ok(bytes.to_vec());
+    }
+    let text = std::str::from_utf8(bytes).map(str::trim);
+    match text {
+        Ok(text) if text.starts_with('{') => {
+            let data: WasmData = serde_json::from_str(text)
+                .map_err(|err| format!("not a wasm2hex JSON: {}", err))?;
+            decode_binary(&decode_hex(&data.wasm)?)
+        }
+        Ok(text) if looks_like_hex(text.as_bytes()) => decode_binary(&decode_hex(text)?),
+        _ => decode_binary(bytes),
+    }
+}
+
+/// A wasm module, compressed or not.
+fn decode_binary(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.starts_with(WASM_MAGIC) {
+        return Ok(bytes.to_vec());
+    }
+    match decompress(bytes, MAX_WASM_SIZE) {
+        Ok(wasm) if wasm.starts_with(WASM_MAGIC) => Ok(wasm),
+        Ok(_) => Err("decompressed to something that is not a wasm module".to_string()),
+        Err(err) => Err(format!(
+            "not a wasm module, and could not decompress it: {}",
+            err
+        )),
+    }
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text.trim();
+    let digits = digits.strip_prefix("0x").unwrap_or(digits);
+    hex::decode(digits).map_err(|err| format!("invalid hex: {}", err))
+}
+
+fn looks_like_hex(bytes: &[u8]) -> bool {
+    let text = bytes.trim_ascii();
+    let digits = text.strip_prefix(b"0x").unwrap_or(text);
+    !digits.is_empty() && digits.iter().all(u8::is_ascii_hexdigit)
+}
+
+/// Keep errors readable when the input is a whole bot.
+fn abbreviate(input: &str) -> String {
+    match input.char_indices().nth(40) {
+        Some((end, _)) => format!("`{}...`", &input[..end]),
+        None => format!("`{}`", input),
+    }
+}
@@ -1,88 +1,412 @@
-use core::str;
-use std::slice;
+use bot_validator::{validate, Limits};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use machine::{
+    play_series, set_verbose, validator_limits, Battle, MatchMode, ModuleCache, Perception,
+    ReferenceBot, Rules, ScriptedMove,
+};
 
-use clap::Parser;
-use lz4_flex::decompress;
-use machine::Battle;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufReader;
+mod batch;
+mod gym;
+mod input;
+mod report;
+mod trajectory;
+mod view;
 
-#[derive(Serialize, Deserialize, Debug)]
-struct WasmData {
-    wasm: String,
-}
+use input::load_bot;
+use report::{MatchReport, SeriesReport};
+use trajectory::Trajectory;
+use view::View;
+
+/// Exit status of a match (or series) from the point of view of bot 1, so that a script
+/// only has to check for success. 2 is left to clap, which uses it for invalid arguments.
+const EXIT_BOT1_WON: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_BOT2_WON: i32 = 3;
+const EXIT_DRAW: i32 = 4;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(group(ArgGroup::new("bots").required(true).args(["bot", "bot1"])))]
 struct Cli {
-    /// Input file to compress and convert
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Bot playing itself. Bots are given as a path or inline, as raw or lz4 compressed
+    /// wasm, hex encoded (with or without 0x) or the JSON written by wasm2hex
+    #[arg(short, long, conflicts_with_all = ["bot1", "bot2"])]
+    bot: Option<String>,
+
+    /// Bot playing as team 1
+    #[arg(long)]
+    bot1: Option<String>,
+
+    /// Bot playing as team 2, bot 1 plays itself when missing
+    #[arg(long, requires = "bot1")]
+    bot2: Option<String>,
+
+    /// Play against a built-in opponent as team 2: idle, chaser, goalkeeper, random or
+    /// scripted
+    #[arg(long, conflicts_with_all = ["bot2", "fair"])]
+    opponent: Option<ReferenceBot>,
+
+    /// Moves of the scripted opponent, a JSON array of {"ticks", "impulse": [x, y, z],
+    /// "boost"} played in a loop. Defaults to going around the arena
+    #[arg(long, requires = "opponent")]
+    script: Option<PathBuf>,
+
+    /// Play twice, swapping sides, and report the aggregated series
+    #[arg(long)]
+    fair: bool,
+
+    /// Directory where the bots keep their storage between matches, one file per bot hash
+    #[arg(long, conflicts_with = "fair")]
+    storage_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+
+    /// Identifies the match to the bots, and seeds them
+    #[arg(long, conflicts_with = "fair")]
+    match_id: Option<String>,
+
+    /// Updates after which the match is a draw, instead of the default of the rules
+    #[arg(long)]
+    max_ticks: Option<u64>,
+
+    /// Ticks from a decision of a bot to the tick it applies
+    #[arg(long, default_value_t = 0)]
+    action_delay: u32,
+
+    /// Every coordinate the bots observe is off by up to this much, drawn from the match id
+    #[arg(long, default_value_t = 0.0)]
+    position_noise: f32,
+
+    /// Opponents farther than this on the ground are hidden from the bots
+    #[arg(long)]
+    view_radius: Option<f32>,
+
+    /// How the result is printed. With json, stdout only gets the report and everything
+    /// else goes to stderr
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Only print the result: no validation report, no bot logs, nothing from the machine
     #[arg(short, long)]
-    bot: String,
+    quiet: bool,
+
+    /// Write where the ball and the bots are after every tick, as JSON if the file ends with
+    /// .json and as CSV otherwise
+    #[arg(long, conflicts_with = "fair")]
+    trajectory: Option<PathBuf>,
+
+    /// Draw the arena from above on stderr while the match is played
+    #[arg(long, conflicts_with = "fair")]
+    watch: bool,
+
+    /// Playback speed of --watch: 1 for real time, 2 for twice as fast, 0 for as fast as
+    /// possible
+    #[arg(long, default_value_t = 1.0, requires = "watch")]
+    speed: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    /// A single line of JSON, see `report.rs`
+    Json,
+}
+
+#[derive(Args)]
+struct CacheArgs {
+    /// Directory where compiled bots are kept, only the ones this run compiled are loaded
+    /// back unless runs share --cache-secret
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// File holding the secret compiled bots are signed with in --cache-dir, the runs
+    /// knowing it load each other's. Keep it out of that directory
+    #[arg(long, requires = "cache_dir")]
+    cache_secret: Option<PathBuf>,
+}
+
+impl CacheArgs {
+    fn module_cache(&self) -> Result<ModuleCache, String> {
+        let Some(cache_dir) = &self.cache_dir else {
+            return Ok(ModuleCache::new());
+        };
+        let cache = ModuleCache::with_directory(cache_dir);
+        match &self.cache_secret {
+            Some(path) => fs::read(path)
+                .map(|secret| cache.with_secret(secret))
+                .map_err(|err| format!("--cache-secret {}: {}", path.display(), err)),
+            None => Ok(cache),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play every matchup of a manifest in parallel, one JSON line per match
+    Batch {
+        /// JSON manifest listing the matchups
+        manifest: PathBuf,
+
+        /// Matches played at the same time, defaults to the number of cores
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// File receiving the results instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        cache: CacheArgs,
+    },
+    /// Serve reinforcement learning environments to a training script, see `gym.rs`
+    Gym(gym::GymArgs),
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let payload: &str = &cli.bot.as_str();
-
-    println!("payload: {}", payload);
+    if let Some(command) = &cli.command {
+        // stdout is for the results, or the protocol
+        set_verbose(false);
+        let result = match command {
+            Command::Batch {
+                manifest,
+                threads,
+                output,
+                cache,
+            } => cache
+                .module_cache()
+                .and_then(|cache| batch::run(manifest, *threads, output.as_ref(), cache)),
+            Command::Gym(args) => gym::run(args),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let compressed_bytes = if payload.starts_with("0x") {
-        let s = unsafe {
-            // First, we build a &[u8]...
-            let slice = slice::from_raw_parts(payload.as_ptr().add(2), payload.len() - 2);
+    // the machine would scroll the arena away
+    set_verbose(!cli.quiet && cli.format == Format::Text && !cli.watch);
+    let code = match play(&cli) {
+        Ok(0) => EXIT_DRAW,
+        Ok(1) => EXIT_BOT1_WON,
+        Ok(_) => EXIT_BOT2_WON,
+        Err(err) => {
+            eprintln!("{}", err);
+            EXIT_ERROR
+        }
+    };
+    std::process::exit(code);
+}
 
-            // ... and then convert that slice into a string slice
-            str::from_utf8(slice)
+impl Cli {
+    /// Print something that is not the result: on stdout for text, on stderr for json so
+    /// that the report can be piped, and nowhere when quiet.
+    fn note(&self, message: impl std::fmt::Display) {
+        match (self.quiet, self.format) {
+            (true, _) => {}
+            (false, Format::Text) => println!("{}", message),
+            (false, Format::Json) => eprintln!("{}", message),
         }
-        .expect("failed to slice payload");
+    }
+}
 
-        hex::decode(s).expect("failed to decode payload")
-    } else {
-        // Open the file
-        let file = File::open(payload).expect("failed to read file");
-        let reader = BufReader::new(file);
-        // Parse the JSON
-        let wasm_data: WasmData = serde_json::from_reader(reader).expect("fauled to parse json");
-
-        let s = unsafe {
-            // First, we build a &[u8]...
-            let slice =
-                slice::from_raw_parts(wasm_data.wasm.as_ptr().add(2), wasm_data.wasm.len() - 2);
-
-            // ... and then convert that slice into a string slice
-            str::from_utf8(slice)
+/// Load and check the bot given with `flag`.
+fn load_checked(cli: &Cli, limits: &Limits, flag: &str, input: &str) -> Result<Vec<u8>, String> {
+    let wasm = load_bot(input).map_err(|err| format!("{}: {}", flag, err))?;
+    let report = validate(&wasm, limits);
+    if !report.is_valid() {
+        return Err(format!("{}: invalid bot\n{}", flag, report));
+    }
+    cli.note(report);
+    Ok(wasm)
+}
+
+/// Play the match (or series) and print the result. Returns 0 for a draw, 1 or 2 for the
+/// winning bot.
+fn play(cli: &Cli) -> Result<u8, String> {
+    if !(cli.speed >= 0.0 && cli.speed.is_finite()) {
+        return Err(format!("--speed {} is not a speed", cli.speed));
+    }
+    if !(cli.position_noise >= 0.0 && cli.position_noise.is_finite()) {
+        return Err(format!(
+            "--position-noise {} is not a distance",
+            cli.position_noise
+        ));
+    }
+    if let Some(view_radius) = cli.view_radius {
+        if !(view_radius > 0.0 && view_radius.is_finite()) {
+            return Err(format!("--view-radius {} is not a distance", view_radius));
         }
-        .expect("failed to slice payload");
+    }
+    let mut rules = Rules::default();
+    if let Some(max_ticks) = cli.max_ticks {
+        rules.max_ticks = max_ticks;
+    }
+    rules.perception = Perception {
+        action_delay: cli.action_delay,
+        position_noise: cli.position_noise,
+        view_radius: cli.view_radius,
+    };
 
-        hex::decode(s).expect("failed to decode payload")
+    let limits = validator_limits(&rules);
+    let (bot1, bot2) = match (&cli.bot, &cli.bot1, &cli.bot2) {
+        (Some(bot), _, _) => {
+            let wasm = load_checked(cli, &limits, "--bot", bot)?;
+            (wasm.clone(), wasm)
+        }
+        (None, Some(bot1), bot2) => {
+            let wasm_1 = load_checked(cli, &limits, "--bot1", bot1)?;
+            let wasm_2 = match bot2 {
+                Some(bot2) => load_checked(cli, &limits, "--bot2", bot2)?,
+                None => wasm_1.clone(),
+            };
+            (wasm_1, wasm_2)
+        }
+        (None, None, _) => return Err("no bot given".to_string()),
     };
 
-    let mut wasm_bytes_1 = if let Ok(b) = decompress(&compressed_bytes, 1000000) {
-        b
-    } else {
-        println!("could not decompress, assume it is not compressed");
-        compressed_bytes.clone()
+    let opponent = match (&cli.opponent, &cli.script) {
+        (Some(ReferenceBot::Scripted(_)), Some(script)) => {
+            Some(ReferenceBot::Scripted(load_script(script)?))
+        }
+        (Some(_), Some(_)) => {
+            return Err("--script only applies to the scripted opponent".to_string())
+        }
+        (opponent, _) => opponent.clone(),
     };
-    let wasm_bytes_1: &mut [u8] = &mut wasm_bytes_1; //cast to `&mut [u8]`
 
-    let mut wasm_bytes_2 = if let Ok(b) = decompress(&compressed_bytes, 1000000) {
-        b
+    // a bot playing both sides only needs to be compiled once
+    let cache = Arc::new(cli.cache.module_cache()?);
+
+    if cli.fair {
+        cli.note("series!");
+        let series = play_series(&[bot1], &[bot2], &rules, Some(cache))
+            .map_err(|err| format!("failed to play series: {}", err))?;
+        match cli.format {
+            Format::Text => {
+                for (i, leg) in series.legs.iter().enumerate() {
+                    cli.note(format!(
+                        "leg {}: winner {} (sides swapped: {})",
+                        i + 1,
+                        leg.winner,
+                        leg.swapped
+                    ));
+                }
+                println!(
+                    "series: {} - {} ({} draws), winner: {}",
+                    series.wins_a,
+                    series.wins_b,
+                    series.draws,
+                    series.winner()
+                );
+            }
+            Format::Json => print_json(&SeriesReport::new(&series))?,
+        }
+        return Ok(series.winner());
+    }
+
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.set_module_cache(cache);
+    if let Some(match_id) = &cli.match_id {
+        battle.set_match_id(match_id.as_bytes());
+    }
+    cli.note("adding bot 1 ...");
+    battle
+        .add_bot(&mut bot1.clone())
+        .map_err(|err| format!("failed to add bot 1: {}", err))?;
+    cli.note("adding bot 2 ...");
+    match opponent {
+        Some(opponent) => battle.add_reference_bot(opponent),
+        None => battle.add_bot(&mut bot2.clone()),
+    }
+    .map_err(|err| format!("failed to add bot 2: {}", err))?;
+
+    if let Some(storage_dir) = &cli.storage_dir {
+        for index in 0..battle.num_bots() {
+            let path = storage_dir.join(format!("{}.bin", battle.get_bot_hash(index)));
+            if let Ok(storage) = fs::read(&path) {
+                battle
+                    .set_bot_storage(index, storage)
+                    .map_err(|err| format!("{}: {}", path.display(), err))?;
+            }
+        }
+    }
+
+    cli.note("battle!");
+    let winner = if cli.trajectory.is_some() || cli.watch {
+        let mut trajectory = cli
+            .trajectory
+            .as_deref()
+            .map(Trajectory::create)
+            .transpose()?;
+        let mut view = cli.watch.then(|| View::new(cli.speed));
+        let winner = battle.execute_with(|battle| {
+            if let Some(trajectory) = &mut trajectory {
+                trajectory.record(battle)?;
+            }
+            match &mut view {
+                Some(view) => view.draw(battle),
+                None => Ok(()),
+            }
+        })?;
+        if let Some(trajectory) = trajectory {
+            trajectory.finish()?;
+        }
+        if let Some(view) = &mut view {
+            view.finish(&battle, winner)?;
+        }
+        winner
     } else {
-        println!("could not decompress, assume it is not compressed");
-        compressed_bytes.clone()
+        battle.execute()?
     };
-    let wasm_bytes_2: &mut [u8] = &mut wasm_bytes_2; //cast to `&mut [u8]`
 
-    let mut battle = Battle::new();
-    println!("addding bot 1 ...");
-    battle.add_bot(wasm_bytes_1);
-    println!("addding bot 2 ...");
-    battle.add_bot(wasm_bytes_2);
+    if let Some(storage_dir) = &cli.storage_dir {
+        fs::create_dir_all(storage_dir)
+            .map_err(|err| format!("failed to create {}: {}", storage_dir.display(), err))?;
+        for index in 0..battle.num_bots() {
+            let path = storage_dir.join(format!("{}.bin", battle.get_bot_hash(index)));
+            fs::write(&path, battle.get_bot_storage(index))
+                .map_err(|err| format!("failed to write {}: {}", path.display(), err))?;
+        }
+    }
+    for log in battle.logs() {
+        cli.note(log);
+    }
+    match cli.format {
+        Format::Text => {
+            println!("winner: {winner}");
+            for index in 0..battle.num_bots() {
+                let faults = battle.bot_faults(index);
+                if faults.total() > 0 {
+                    println!("bot {} faults: {:?}", index + 1, faults);
+                }
+            }
+        }
+        Format::Json => print_json(&MatchReport::new(&battle, winner, cli.match_id.as_deref()))?,
+    }
+    Ok(winner)
+}
+
+fn load_script(path: &Path) -> Result<Vec<ScriptedMove>, String> {
+    let script = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+    serde_json::from_str(&script)
+        .map_err(|err| format!("invalid script {}: {}", path.display(), err))
+}
 
-    println!("battle!");
-    let winner = battle.execute();
-    println!("winner: {winner}");
+fn print_json(report: &impl serde::Serialize) -> Result<(), String> {
+    let json = serde_json::to_string(report).map_err(|err| err.to_string())?;
+    println!("{}", json);
+    Ok(())
 }
@@ -0,0 +1,132 @@
+//! `--format json`: what a script needs to know about a match, on a single line of stdout.
+
+use machine::{Battle, FaultCounts, GasUsage, Perception, SeriesResult};
+use serde::Serialize;
+
+/// How the match (or series) ended, seen from the command line: `bot1` is whoever was given
+/// with `--bot1` (or `--bot`).
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Bot1,
+    Bot2,
+    Draw,
+}
+
+impl Outcome {
+    /// `winner` as returned by `Battle::execute` or `SeriesResult::winner`.
+    pub fn from_winner(winner: u8) -> Outcome {
+        match winner {
+            1 => Outcome::Bot1,
+            2 => Outcome::Bot2,
+            _ => Outcome::Draw,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BotReport {
+    pub hash: String,
+    pub team: u8,
+    /// Gas used by `compute_actions`.
+    pub gas: GasUsage,
+    pub gas_mean: f64,
+    pub gas_reserve: u64,
+    pub faults: FaultCounts,
+}
+
+/// `Rules::perception` the match was played with.
+#[derive(Serialize)]
+pub struct PerceptionReport {
+    pub action_delay: u32,
+    pub position_noise: f32,
+    /// `null` when the bots see every opponent.
+    pub view_radius: Option<f32>,
+}
+
+impl PerceptionReport {
+    fn new(perception: Perception) -> PerceptionReport {
+        PerceptionReport {
+            action_delay: perception.action_delay,
+            position_noise: perception.position_noise,
+            view_radius: perception.view_radius,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct MatchReport {
+    pub outcome: Outcome,
+    /// 0 for a draw, otherwise the winning team.
+    pub winner: u8,
+    pub ticks: u64,
+    pub max_ticks: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_id: Option<String>,
+    pub perception: PerceptionReport,
+    pub bots: Vec<BotReport>,
+    /// See `Battle::get_state_hash`, to tell whether a match still plays out the same.
+    pub state_hash: String,
+}
+
+impl MatchReport {
+    pub fn new(battle: &Battle, winner: u8, match_id: Option<&str>) -> MatchReport {
+        let bots = (0..battle.num_bots())
+            .map(|index| {
+                let gas = battle.bot_gas(index).clone();
+                BotReport {
+                    hash: battle.get_bot_hash(index),
+                    team: battle.get_bot_team(index) + 1,
+                    gas_mean: gas.mean(),
+                    gas,
+                    gas_reserve: battle.get_bot_gas_reserve(index),
+                    faults: battle.bot_faults(index).clone(),
+                }
+            })
+            .collect();
+        MatchReport {
+            outcome: Outcome::from_winner(winner),
+            winner,
+            ticks: battle.get_tick(),
+            max_ticks: battle.get_max_ticks(),
+            match_id: match_id.map(str::to_string),
+            perception: PerceptionReport::new(battle.perception()),
+            bots,
+            state_hash: battle.get_state_hash(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LegReport {
+    /// 0 for a draw, 1 when bot 1 won, 2 when bot 2 won.
+    pub winner: u8,
+    pub swapped: bool,
+}
+
+#[derive(Serialize)]
+pub struct SeriesReport {
+    pub outcome: Outcome,
+    /// Legs won by bot 1 and by bot 2.
+    pub wins: [u32; 2],
+    pub draws: u32,
+    pub legs: Vec<LegReport>,
+}
+
+impl SeriesReport {
+    pub fn new(series: &SeriesResult) -> SeriesReport {
+        SeriesReport {
+            outcome: Outcome::from_winner(series.winner()),
+            wins: [series.wins_a, series.wins_b],
+            draws: series.draws,
+            legs: series
+                .legs
+                .iter()
+                .map(|leg| LegReport {
+                    winner: leg.winner,
+                    swapped: leg.swapped,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! `--trajectory`: where the balls and the bots are after every tick, to plot or diff a match.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use machine::{Battle, Position};
+use serde::Serialize;
+
+enum Format {
+    /// One row per object and tick: `tick,object,team,x,y,z,energy`, the balls being `ball`
+    /// or, when there are several, `ball1`, `ball2`, ...
+    Csv,
+    /// An array with one object per tick.
+    Json,
+}
+
+pub struct Trajectory {
+    out: BufWriter<File>,
+    format: Format,
+    ticks: u64,
+}
+
+#[derive(Serialize)]
+struct Tick {
+    tick: u64,
+    /// The first ball.
+    ball: [f32; 3],
+    /// Every ball, when there are several.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    balls: Vec<[f32; 3]>,
+    bots: Vec<BotState>,
+}
+
+#[derive(Serialize)]
+struct BotState {
+    team: u8,
+    position: [f32; 3],
+    energy: f32,
+}
+
+fn coordinates(position: Position) -> [f32; 3] {
+    [position.x, position.y, position.z]
+}
+
+impl Trajectory {
+    /// JSON when `path` ends with `.json`, CSV otherwise.
+    pub fn create(path: &Path) -> Result<Trajectory, String> {
+        let format = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Format::Json,
+            _ => Format::Csv,
+        };
+        let file = File::create(path)
+            .map_err(|err| format!("failed to create {}: {}", path.display(), err))?;
+        let mut trajectory = Trajectory {
+            out: BufWriter::new(file),
+            format,
+            ticks: 0,
+        };
+        let header = match trajectory.format {
+            Format::Csv => "tick,object,team,x,y,z,energy\n",
+            Format::Json => "[\n",
+        };
+        trajectory.write(header)?;
+        Ok(trajectory)
+    }
+
+    pub fn record(&mut self, battle: &Battle) -> Result<(), String> {
+        let tick = battle.get_tick();
+        match self.format {
+            Format::Csv => {
+                let mut rows = String::new();
+                let num_balls = battle.num_balls();
+                for index in 0..num_balls {
+                    let [x, y, z] = coordinates(battle.get_ball_at(index));
+                    let name = if num_balls == 1 {
+                        "ball".to_string()
+                    } else {
+                        format!("ball{}", index + 1)
+                    };
+                    rows += &format!("{},{},,{},{},{},\n", tick, name, x, y, z);
+                }
+                for index in 0..battle.num_bots() {
+                    let [x, y, z] = coordinates(battle.get_bot(index));
+                    rows += &format!(
+                        "{},bot{},{},{},{},{},{}\n",
+                        tick,
+                        index + 1,
+                        battle.get_bot_team(index) + 1,
+                        x,
+                        y,
+                        z,
+                        battle.get_bot_energy(index)
+                    );
+                }
+                self.write(&rows)?;
+            }
+            Format::Json => {
+                let state = Tick {
+                    tick,
+                    ball: coordinates(battle.get_ball()),
+                    balls: match battle.num_balls() {
+                        1 => Vec::new(),
+                        num_balls => (0..num_balls)
+                            .map(|index| coordinates(battle.get_ball_at(index)))
+                            .collect(),
+                    },
+                    bots: (0..battle.num_bots())
+                        .map(|index| BotState {
+                            team: battle.get_bot_team(index) + 1,
+                            position: coordinates(battle.get_bot(index)),
+                            energy: battle.get_bot_energy(index),
+                        })
+                        .collect(),
+                };
+                let json = serde_json::to_string(&state).map_err(|err| err.to_string())?;
+                let separator = if self.ticks == 0 { "" } else { ",\n" };
+                self.write(&format!("{}{}", separator, json))?;
+            }
+        }
+        self.ticks += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        if let Format::Json = self.format {
+            self.write("\n]\n")?;
+        }
+        self.out
+            .flush()
+            .map_err(|err| format!("failed to write the trajectory: {}", err))
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        self.out
+            .write_all(text.as_bytes())
+            .map_err(|err| format!("failed to write the trajectory: {}", err))
+    }
+}
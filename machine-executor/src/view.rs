@@ -0,0 +1,142 @@
+//! `--watch`: the arena seen from above, drawn in the terminal as the match is played.
+
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use machine::{Battle, Position, ARENA_HALF_SIZE};
+
+/// Half the width of the goals, as the machine builds them. The goals of team 1 and 2 are on
+/// the left (-x) and right (+x) walls.
+const GOAL_HALF_WIDTH: f32 = 2.5;
+
+/// Characters across and down the arena, a character being about twice as tall as wide.
+const COLUMNS: usize = 60;
+const ROWS: usize = 30;
+
+/// Frames are skipped above this rate, the terminal would not keep up.
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+pub struct View {
+    /// 1 for real time, 2 for twice as fast, 0 for as fast as possible.
+    speed: f64,
+    started: Instant,
+    last_frame: Option<Instant>,
+}
+
+impl View {
+    pub fn new(speed: f64) -> View {
+        // clear the terminal once, frames are then drawn over each other
+        eprint!("\x1b[2J");
+        View {
+            speed,
+            started: Instant::now(),
+            last_frame: None,
+        }
+    }
+
+    /// Wait until the tick is due and draw it, unless the previous frame is too recent.
+    pub fn draw(&mut self, battle: &Battle) -> Result<(), String> {
+        if self.speed > 0.0 {
+            let tick = Duration::from_secs_f32(battle.get_tick_duration());
+            let due = self.started + tick.mul_f64(battle.get_tick() as f64 / self.speed);
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+        }
+        if let Some(last_frame) = self.last_frame {
+            if last_frame.elapsed() < MIN_FRAME_INTERVAL {
+                return Ok(());
+            }
+        }
+        self.render(battle, "")
+    }
+
+    /// Draw the final state, with the outcome under the arena.
+    pub fn finish(&mut self, battle: &Battle, winner: u8) -> Result<(), String> {
+        let outcome = match winner {
+            0 => "draw".to_string(),
+            team => format!("team {} wins", team),
+        };
+        self.render(battle, &outcome)
+    }
+
+    fn render(&mut self, battle: &Battle, status: &str) -> Result<(), String> {
+        let mut grid = vec![vec![' '; COLUMNS + 2]; ROWS + 2];
+        for (row, line) in grid.iter_mut().enumerate() {
+            if row == 0 || row == ROWS + 1 {
+                line.fill('-');
+                line[0] = '+';
+                line[COLUMNS + 1] = '+';
+            } else {
+                let wall = if row_to_z(row).abs() <= GOAL_HALF_WIDTH {
+                    '#'
+                } else {
+                    '|'
+                };
+                line[0] = wall;
+                line[COLUMNS + 1] = wall;
+            }
+        }
+
+        for index in 0..battle.num_balls() {
+            let (column, row) = cell(battle.get_ball_at(index));
+            grid[row][column] = 'o';
+        }
+        // bots are drawn over the balls, their team telling them apart
+        for index in 0..battle.num_bots() {
+            let (column, row) = cell(battle.get_bot(index));
+            let team = battle.get_bot_team(index) + 1;
+            grid[row][column] = char::from_digit(team as u32, 10).unwrap_or('?');
+        }
+
+        let mut frame = String::from("\x1b[H");
+        for line in grid {
+            frame.extend(line);
+            frame.push('\n');
+        }
+        let ball = battle.get_ball();
+        let teams = (0..battle.num_bots())
+            .map(|index| battle.get_bot_team(index) + 1)
+            .max()
+            .unwrap_or(0);
+        let score = (0..teams)
+            .map(|team| battle.get_team_score(team).to_string())
+            .collect::<Vec<_>>()
+            .join("-");
+        frame += &format!(
+            "tick {:>6}  ball ({:6.2}, {:5.2}, {:6.2})  score {}  {}\x1b[K\n",
+            battle.get_tick(),
+            ball.x,
+            ball.y,
+            ball.z,
+            score,
+            status
+        );
+        let mut stderr = io::stderr().lock();
+        stderr
+            .write_all(frame.as_bytes())
+            .and_then(|_| stderr.flush())
+            .map_err(|err| format!("failed to draw the arena: {}", err))?;
+        self.last_frame = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Column and row of the grid (walls included) showing `position`.
+fn cell(position: Position) -> (usize, usize) {
+    let scale = |value: f32, cells: usize| {
+        let ratio = (value + ARENA_HALF_SIZE) / (2.0 * ARENA_HALF_SIZE);
+        1 + ((ratio * cells as f32) as usize).min(cells - 1)
+    };
+    (
+        scale(position.x.max(-ARENA_HALF_SIZE), COLUMNS),
+        scale(position.z.max(-ARENA_HALF_SIZE), ROWS),
+    )
+}
+
+/// Centre of the arena cells on `row`.
+fn row_to_z(row: usize) -> f32 {
+    let ratio = (row as f32 - 0.5) / ROWS as f32;
+    ratio * 2.0 * ARENA_HALF_SIZE - ARENA_HALF_SIZE
+}
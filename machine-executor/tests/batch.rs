@@ -0,0 +1,123 @@
+mod common;
+
+use std::fs;
+
+use serde_json::Value;
+
+/// Results of the batch written to `output`, in the order of the manifest.
+fn results(output: &std::path::Path) -> Vec<Value> {
+    let mut results: Vec<Value> = fs::read_to_string(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    results.sort_by_key(|result| result["index"].as_u64().unwrap());
+    results
+}
+
+#[test]
+fn every_match_gets_a_line() {
+    let directory = common::directory("batch");
+    common::write(&directory, "idle.wasm", common::idle_bot());
+    common::write(&directory, "running.wasm", common::running_bot());
+    let manifest = common::write(
+        &directory,
+        "manifest.json",
+        r#"{
+          "matches": [
+            { "bot1": "idle.wasm", "bot2": "running.wasm", "seeds": ["a", "b"], "max_ticks": 100 },
+            { "bot1": "running.wasm", "bot2": "idle.wasm", "fair": true, "max_ticks": 100 }
+          ]
+        }"#,
+    );
+    let output = directory.join("results.jsonl");
+
+    let run = common::run([
+        "batch".as_ref(),
+        manifest.as_os_str(),
+        "--threads".as_ref(),
+        "2".as_ref(),
+        "--output".as_ref(),
+        output.as_os_str(),
+    ]);
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+
+    let results = results(&output);
+    assert_eq!(results.len(), 3);
+    for (index, match_id) in ["a", "b"].into_iter().enumerate() {
+        let result = &results[index];
+        assert_eq!(result["index"], index);
+        assert_eq!(result["bot1"], "idle.wasm");
+        assert_eq!(result["bot2"], "running.wasm");
+        assert_eq!(result["match_id"], match_id);
+        assert!(result["ticks"].as_u64().unwrap() > 0, "{}", result);
+        assert_eq!(result["faults"].as_array().unwrap().len(), 2);
+        assert!(result.get("series").is_none());
+        assert!(result.get("error").is_none());
+    }
+
+    // the series, identified by the index of its matchup
+    let series = &results[2];
+    assert_eq!(series["bot1"], "running.wasm");
+    assert_eq!(series["match_id"], "1");
+    assert_eq!(series["ticks"], 0);
+    let legs: u64 = series["series"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|legs| legs.as_u64().unwrap())
+        .sum();
+    assert_eq!(legs, 2);
+    assert!(series.get("faults").is_none());
+    assert!(series.get("error").is_none());
+}
+
+#[test]
+fn a_broken_bot_only_fails_its_matches() {
+    let directory = common::directory("batch-broken");
+    common::write(&directory, "idle.wasm", common::idle_bot());
+    common::write(&directory, "garbage.wasm", "not a bot");
+    // a module the machine refuses, it has nothing to play with
+    common::write(
+        &directory,
+        "empty.wasm",
+        wat::parse_str("(module)").unwrap(),
+    );
+    let manifest = common::write(
+        &directory,
+        "manifest.json",
+        r#"{
+          "matches": [
+            { "bot1": "idle.wasm", "bot2": "garbage.wasm" },
+            { "bot1": "empty.wasm", "bot2": "idle.wasm" },
+            { "bot1": "idle.wasm", "bot2": "idle.wasm", "max_ticks": 10 }
+          ]
+        }"#,
+    );
+    let output = directory.join("results.jsonl");
+
+    let run = common::run([
+        "batch".as_ref(),
+        manifest.as_os_str(),
+        "--output".as_ref(),
+        output.as_os_str(),
+    ]);
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+
+    let results = results(&output);
+    assert_eq!(results.len(), 3);
+    let error = results[0]["error"].as_str().unwrap();
+    assert!(error.starts_with("garbage.wasm: "), "{}", error);
+    assert_eq!(results[0]["winner"], 0);
+    assert!(results[1]["error"].is_string(), "{}", results[1]);
+    assert!(results[2].get("error").is_none(), "{}", results[2]);
+    assert!(results[2]["ticks"].as_u64().unwrap() > 0);
+}
@@ -0,0 +1,153 @@
+mod common;
+
+#[test]
+fn options_of_a_single_match_are_refused_with_fair() {
+    let directory = common::directory("cli-fair");
+    let bot = common::write(&directory, "idle.wasm", common::idle_bot());
+
+    for option in [["--storage-dir", "storage"], ["--match-id", "1"]] {
+        let run = common::run([
+            "--bot".as_ref(),
+            bot.as_os_str(),
+            "--fair".as_ref(),
+            option[0].as_ref(),
+            option[1].as_ref(),
+        ]);
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert_eq!(run.status.code(), Some(2), "{}", stderr);
+        assert!(stderr.contains(option[0]), "{}", stderr);
+    }
+}
+
+#[test]
+fn the_report_records_the_perception_rules() {
+    let directory = common::directory("cli-perception");
+    let bot = common::write(&directory, "idle.wasm", common::idle_bot());
+
+    let run = common::run([
+        "--bot".as_ref(),
+        bot.as_os_str(),
+        "--max-ticks".as_ref(),
+        "5".as_ref(),
+        "--action-delay".as_ref(),
+        "2".as_ref(),
+        "--position-noise".as_ref(),
+        "0.5".as_ref(),
+        "--view-radius".as_ref(),
+        "3".as_ref(),
+        "--format".as_ref(),
+        "json".as_ref(),
+        "--quiet".as_ref(),
+    ]);
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    let report: serde_json::Value = serde_json::from_str(&stdout)
+        .unwrap_or_else(|_| panic!("{}{}", stdout, String::from_utf8_lossy(&run.stderr)));
+
+    assert_eq!(
+        report["perception"],
+        serde_json::json!({ "action_delay": 2, "position_noise": 0.5, "view_radius": 3.0 })
+    );
+}
+
+#[test]
+fn perception_distances_are_checked() {
+    let directory = common::directory("cli-distances");
+    let bot = common::write(&directory, "idle.wasm", common::idle_bot());
+
+    for (option, value) in [
+        ("--view-radius", "0"),
+        ("--view-radius", "-1"),
+        ("--view-radius", "inf"),
+        ("--view-radius", "NaN"),
+        ("--position-noise", "-1"),
+        ("--position-noise", "NaN"),
+    ] {
+        let run = common::run([
+            "--bot".as_ref(),
+            bot.as_os_str(),
+            format!("{}={}", option, value).as_ref(),
+        ]);
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert_eq!(
+            run.status.code(),
+            Some(1),
+            "{} {}: {}",
+            option,
+            value,
+            stderr
+        );
+        assert!(stderr.contains(option), "{}", stderr);
+    }
+}
+
+#[test]
+fn bots_are_validated_against_the_rules_of_the_match() {
+    // the battle caps a memory without maximum, so the executor accepts it too
+    let unbounded = wat::parse_str(
+        r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 8421504))
+"#,
+    )
+    .unwrap();
+    let directory = common::directory("cli-limits");
+    let bot = common::write(&directory, "unbounded.wasm", unbounded);
+
+    let run = common::run([
+        "--bot".as_ref(),
+        bot.as_os_str(),
+        "--max-ticks".as_ref(),
+        "5".as_ref(),
+        "--quiet".as_ref(),
+    ]);
+    // two idle bots draw
+    assert_eq!(
+        run.status.code(),
+        Some(4),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+}
+
+#[test]
+fn runs_sharing_the_cache_secret_reuse_compiled_bots() {
+    let directory = common::directory("cli-cache");
+    let bot = common::write(&directory, "idle.wasm", common::idle_bot());
+    let secret = common::write(&directory, "secret", "not in the cache");
+    let cache = directory.join("cache");
+    let play = |secret: &std::path::Path| {
+        let run = common::run([
+            "--bot".as_ref(),
+            bot.as_os_str(),
+            "--max-ticks".as_ref(),
+            "5".as_ref(),
+            "--quiet".as_ref(),
+            "--cache-dir".as_ref(),
+            cache.as_os_str(),
+            "--cache-secret".as_ref(),
+            secret.as_os_str(),
+        ]);
+        assert_eq!(
+            run.status.code(),
+            Some(4),
+            "{}",
+            String::from_utf8_lossy(&run.stderr)
+        );
+        std::fs::read_dir(&cache)
+            .unwrap()
+            .map(|entry| std::fs::read(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let written = play(&secret);
+    assert_eq!(written.len(), 1);
+    assert_eq!(play(&secret), written);
+    // another secret does not trust the artifact, and signs it again
+    let other = common::write(&directory, "other", "another secret");
+    assert_ne!(play(&other), written);
+}
@@ -0,0 +1,62 @@
+// each test file uses its own part of this module
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A bot that never moves, using the legacy `compute_actions` entry point.
+pub const IDLE_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 8421504))
+"#;
+
+/// Pushes towards +x every tick.
+pub const RUNNING_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 16744576))
+"#;
+
+pub fn idle_bot() -> Vec<u8> {
+    wat::parse_str(IDLE_BOT).unwrap()
+}
+
+pub fn running_bot() -> Vec<u8> {
+    wat::parse_str(RUNNING_BOT).unwrap()
+}
+
+/// An empty directory of its own for each test.
+pub fn directory(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("machine-executor-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Write `content` to `name` in `directory`, and tell where.
+pub fn write(directory: &Path, name: &str, content: impl AsRef<[u8]>) -> PathBuf {
+    let path = directory.join(name);
+    fs::write(&path, content).unwrap();
+    path
+}
+
+/// Run the executor with `args`, failing the test when it cannot be started.
+pub fn run<I, S>(args: I) -> Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    Command::new(env!("CARGO_BIN_EXE_machine-executor"))
+        .args(args)
+        .output()
+        .unwrap()
+}
@@ -0,0 +1,125 @@
+//! Bots in every format `load_bot` accepts, given through a batch manifest: bots that are
+//! not a file next to the manifest are read inline.
+
+mod common;
+
+use std::fs;
+
+use lz4_flex::compress;
+use serde_json::{json, Value};
+
+/// Play `bot` against an idle bot for each of `bots`, and tell the error of each match, if
+/// any.
+fn load(name: &str, bots: &[String]) -> Vec<Option<String>> {
+    let directory = common::directory(name);
+    common::write(&directory, "idle.wasm", common::idle_bot());
+    let matches: Vec<Value> = bots
+        .iter()
+        .map(|bot| json!({ "bot1": bot, "bot2": "idle.wasm", "max_ticks": 1 }))
+        .collect();
+    let manifest = common::write(
+        &directory,
+        "manifest.json",
+        json!({ "matches": matches }).to_string(),
+    );
+    let output = directory.join("results.jsonl");
+
+    let run = common::run([
+        "batch".as_ref(),
+        manifest.as_os_str(),
+        "--output".as_ref(),
+        output.as_os_str(),
+    ]);
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+
+    let mut results: Vec<Value> = fs::read_to_string(&output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    results.sort_by_key(|result| result["index"].as_u64().unwrap());
+    results
+        .iter()
+        .map(|result| result["error"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// Output of `wasm2hex` for `wasm`.
+fn wasm2hex(wasm: &[u8]) -> String {
+    json!({
+        "wasm": format!("0x{}", hex::encode(compress(wasm))),
+        "uncompressed": format!("0x{}", hex::encode(wasm)),
+    })
+    .to_string()
+}
+
+#[test]
+fn files_in_every_format() {
+    let directory = common::directory("input-files");
+    let wasm = common::running_bot();
+    let files = [
+        ("raw.wasm", wasm.clone()),
+        ("compressed.lz4", compress(&wasm)),
+        ("plain.hex", hex::encode(&wasm).into_bytes()),
+        (
+            "prefixed.hex",
+            format!("0x{}\n", hex::encode(compress(&wasm))).into_bytes(),
+        ),
+        // the extension does not matter
+        ("bot.wasm", wasm2hex(&wasm).into_bytes()),
+    ];
+    let bots: Vec<String> = files
+        .iter()
+        .map(|(name, content)| {
+            let path = common::write(&directory, name, content);
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+
+    assert_eq!(load("input-files-batch", &bots), vec![None; files.len()]);
+}
+
+#[test]
+fn inline_in_every_format() {
+    let wasm = common::running_bot();
+    let bots = [
+        hex::encode(&wasm),
+        format!("0x{}", hex::encode(&wasm)),
+        hex::encode(compress(&wasm)),
+        format!("0x{}", hex::encode(compress(&wasm))),
+        wasm2hex(&wasm),
+    ];
+
+    assert_eq!(load("input-inline", &bots), vec![None; bots.len()]);
+}
+
+#[test]
+fn what_is_not_a_bot_is_refused() {
+    let directory = common::directory("input-refused");
+    let not_wasm = common::write(&directory, "not_wasm.lz4", compress(b"not a wasm module"));
+    let not_lz4 = common::write(&directory, "not_lz4.bin", [0xff; 16]);
+    let bots = [
+        "missing.wasm".to_string(),
+        "0xnot hex".to_string(),
+        not_wasm.to_string_lossy().into_owned(),
+        not_lz4.to_string_lossy().into_owned(),
+        r#"{"uncompressed": "0x00"}"#.to_string(),
+    ];
+
+    let errors = load("input-refused-batch", &bots);
+    let expected = [
+        "is neither a file nor a hex encoded bot",
+        "is neither a file nor a hex encoded bot",
+        "decompressed to something that is not a wasm module",
+        "could not decompress it",
+        "not a wasm2hex JSON",
+    ];
+    for ((bot, error), expected) in bots.iter().zip(errors).zip(expected) {
+        let error = error.unwrap_or_else(|| panic!("{} was loaded", bot));
+        assert!(error.contains(expected), "{}: {}", bot, error);
+    }
+}
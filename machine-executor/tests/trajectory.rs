@@ -0,0 +1,113 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Play the running bot against the idle one for a few ticks, writing the trajectory to
+/// `path`, and return the JSON report.
+fn play(directory: &Path, path: &Path) -> Value {
+    let bot1 = common::write(directory, "running.wasm", common::running_bot());
+    let bot2 = common::write(directory, "idle.wasm", common::idle_bot());
+    let run = common::run([
+        "--bot1".as_ref(),
+        bot1.as_os_str(),
+        "--bot2".as_ref(),
+        bot2.as_os_str(),
+        "--max-ticks".as_ref(),
+        "20".as_ref(),
+        "--trajectory".as_ref(),
+        path.as_os_str(),
+        "--format".as_ref(),
+        "json".as_ref(),
+        "--quiet".as_ref(),
+    ]);
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert_eq!(stdout.lines().count(), 1, "{}{}", stdout, stderr);
+    serde_json::from_str(&stdout).unwrap()
+}
+
+/// Positions of the bots at every tick, from the JSON trajectory.
+fn json_positions(trajectory: &[Value]) -> Vec<[f32; 3]> {
+    trajectory
+        .iter()
+        .flat_map(|tick| tick["bots"].as_array().unwrap())
+        .map(|bot| {
+            let position = bot["position"].as_array().unwrap();
+            [0, 1, 2].map(|axis| position[axis].as_f64().unwrap() as f32)
+        })
+        .collect()
+}
+
+#[test]
+fn csv_has_a_row_per_object_and_tick() {
+    let directory = common::directory("trajectory-csv");
+    let path = directory.join("trajectory.csv");
+    let report = play(&directory, &path);
+    let ticks = report["ticks"].as_u64().unwrap();
+
+    let csv = fs::read_to_string(&path).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("tick,object,team,x,y,z,energy"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    // from the end of init to the last update, the ball then both bots
+    assert_eq!(rows.len() as u64, 3 * (ticks + 1));
+    for (index, row) in rows.iter().enumerate() {
+        assert_eq!(row.len(), 7, "{:?}", row);
+        assert_eq!(row[0], (index / 3).to_string());
+        let (object, team) = [("ball", ""), ("bot1", "1"), ("bot2", "2")][index % 3];
+        assert_eq!((row[1], row[2]), (object, team), "{:?}", row);
+        for value in &row[3..6] {
+            value.parse::<f32>().unwrap();
+        }
+        assert_eq!(row[6].is_empty(), object == "ball", "{:?}", row);
+    }
+
+    // the running bot moved
+    assert_ne!(&rows[1][3..6], &rows[rows.len() - 2][3..6]);
+}
+
+#[test]
+fn json_has_an_object_per_tick() {
+    let directory = common::directory("trajectory-json");
+    let path = directory.join("trajectory.json");
+    let report = play(&directory, &path);
+    let ticks = report["ticks"].as_u64().unwrap();
+
+    let trajectory: Vec<Value> = serde_json::from_str(&fs::read_to_string(&path).unwrap())
+        .expect("the trajectory is a JSON array");
+    assert_eq!(trajectory.len() as u64, ticks + 1);
+    for (index, tick) in trajectory.iter().enumerate() {
+        assert_eq!(tick["tick"], index);
+        assert_eq!(tick["ball"].as_array().unwrap().len(), 3);
+        // a single ball
+        assert!(tick.get("balls").is_none());
+        let bots = tick["bots"].as_array().unwrap();
+        assert_eq!(bots.len(), 2);
+        assert_eq!((&bots[0]["team"], &bots[1]["team"]), (&1.into(), &2.into()));
+        assert!(bots[0]["energy"].is_number());
+    }
+}
+
+#[test]
+fn both_formats_record_the_same_match() {
+    let directory = common::directory("trajectory-both");
+    let csv_path = directory.join("trajectory.csv");
+    let json_path = directory.join("trajectory.json");
+    play(&directory, &csv_path);
+    play(&directory, &json_path);
+
+    let csv: Vec<[f32; 3]> = fs::read_to_string(&csv_path)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').collect::<Vec<_>>())
+        .filter(|row| row[1].starts_with("bot"))
+        .map(|row| [3, 4, 5].map(|column| row[column].parse().unwrap()))
+        .collect();
+    let json: Vec<Value> = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+
+    assert_eq!(csv, json_positions(&json));
+}
@@ -0,0 +1,459 @@
+use bot_validator::{validate, Limits};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::ModuleCache;
+use crate::context::MatchContext;
+use crate::engine::{instantiate, instantiate_reference, BotInstance, Export, Trap};
+use crate::fault::Fault;
+use crate::globals::export_private_globals;
+use crate::logs::LogLevel;
+use crate::perception::closest;
+use crate::reference::ReferenceBot;
+use crate::rules::Rules;
+use crate::snapshot::ModuleState;
+
+/// Bit of the returned action asking for a boost.
+pub(crate) const BOOST_BIT: u32 = 1 << 24;
+
+/// Enemy position given to bots using the legacy `compute_actions` when no opponent is in
+/// view (see [`crate::Perception::view_radius`]), far outside the arena.
+pub const ENEMY_OUT_OF_VIEW: [f32; 3] = [f32::MAX; 3];
+
+/// Number of f32 values of a float action.
+const ACTION_LEN: usize = 3;
+
+/// What a bot asked for on a tick.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    /// Impulse direction, scaled by the rule set and clamped to unit length by the caller.
+    pub impulse: [f32; 3],
+    pub boost: bool,
+}
+
+impl Action {
+    /// Doing nothing.
+    pub const NEUTRAL: Action = Action {
+        impulse: [0.0; 3],
+        boost: false,
+    };
+
+    /// Action returned as 3 bytes (x, y, z impulse, 128 being 0) packed into a u32, with
+    /// [`BOOST_BIT`] asking for a boost.
+    pub(crate) fn from_packed(action: u32) -> Action {
+        let component = |shift: u32| (((action >> shift) & 0xFF) as f32 - 128.0) / 127.0;
+        Action {
+            impulse: [component(16), component(8), component(0)],
+            boost: action & BOOST_BIT != 0,
+        }
+    }
+}
+
+/// Observations are only made of finite values: anything else becomes 0.
+fn finite(value: f32) -> f32 {
+    if value.is_finite() {
+        value
+    } else {
+        0.0
+    }
+}
+
+/// Observation handed to a bot each tick.
+pub struct Observation<'a> {
+    pub self_position: [f32; 3],
+    /// The closest ball when there are several.
+    pub ball: [f32; 3],
+    pub team: u8,
+    pub teammates: &'a [[f32; 3]],
+    pub opponents: &'a [[f32; 3]],
+    pub energy: f32,
+    /// Gas available for this call.
+    pub gas: f32,
+    /// Every ball in play, `ball` among them.
+    pub balls: &'a [[f32; 3]],
+}
+
+impl Observation<'_> {
+    /// Number of f32 values written by [`Observation::write_to`] for the given roster.
+    pub(crate) fn len(num_teammates: usize, num_opponents: usize, num_balls: usize) -> usize {
+        3 + 3 + 1 + 1 + num_teammates * 3 + 1 + num_opponents * 3 + 1 + 1 + 1 + num_balls * 3
+    }
+
+    /// Flat layout used by the extended ABI (all values are little-endian f32):
+    ///
+    /// `self.xyz, ball.xyz, team, T, T * teammate.xyz, O, O * opponent.xyz, energy, gas, B,
+    /// B * ball.xyz`
+    pub(crate) fn write_to(&self, out: &mut Vec<f32>) {
+        out.extend_from_slice(&self.self_position);
+        out.extend_from_slice(&self.ball);
+        out.push(self.team as f32);
+        out.push(self.teammates.len() as f32);
+        for teammate in self.teammates {
+            out.extend_from_slice(teammate);
+        }
+        out.push(self.opponents.len() as f32);
+        for opponent in self.opponents {
+            out.extend_from_slice(opponent);
+        }
+        out.push(self.energy);
+        out.push(self.gas);
+        out.push(self.balls.len() as f32);
+        for ball in self.balls {
+            out.extend_from_slice(ball);
+        }
+        for value in out.iter_mut() {
+            *value = finite(*value);
+        }
+    }
+
+    /// The legacy ABI only knows about a single enemy: we give it the closest opponent, or
+    /// [`ENEMY_OUT_OF_VIEW`] when it sees none.
+    fn closest_opponent(&self) -> [f32; 3] {
+        if self.opponents.is_empty() {
+            return ENEMY_OUT_OF_VIEW;
+        }
+        closest(self.self_position, self.opponents)
+    }
+}
+
+/// How the host talks to the bot.
+enum Abi {
+    /// `compute_actions(self_x, self_y, self_z, ball_x, ball_y, ball_z, enemy_x, enemy_y, enemy_z) -> u32`
+    Legacy,
+    /// `observation_buffer(len) -> ptr` is called once before `init` so the bot can reserve
+    /// room for `len` f32 values, then every tick the host writes the observation at `ptr`
+    /// and calls `compute_actions_ext(len) -> u32`.
+    ///
+    /// When the bot also exports `action_buffer(len) -> ptr`, it is called once with 3 after
+    /// `observation_buffer` and the bot writes its impulse there as 3 f32 values instead of
+    /// packing it in the result, which then only carries the flags.
+    Extended {
+        pointer: Option<u32>,
+        action_pointer: Option<u32>,
+    },
+}
+
+/// How the host starts the bot.
+enum Init {
+    /// `init(seed)`
+    Seed,
+    /// `context_buffer(len) -> ptr` reserves room for `len` bytes, the host writes the match
+    /// context there and calls `init_with_context(len)`.
+    Context,
+}
+
+pub(crate) struct BotModule {
+    instance: Box<dyn BotInstance>,
+    init: Init,
+    /// Whether the bot exports `on_match_end(result)`.
+    has_match_end: bool,
+    /// Whether the module defines a memory, which snapshots need exported.
+    has_memory: bool,
+    abi: Abi,
+    observation: Vec<f32>,
+}
+
+/// What the validator accepts from a bot playing under `rules`, the same checks as when
+/// adding it to a battle.
+pub fn validator_limits(rules: &Rules) -> Limits {
+    let limits = &rules.limits;
+    Limits {
+        max_table_size: limits.max_table_elements as u64,
+        max_initial_memory_pages: limits.max_memory_pages as u64,
+        max_memory_pages: limits.max_memory_pages as u64,
+        // the tunables cap a memory without maximum at `max_memory_pages`
+        allow_unbounded_memory: true,
+        ..Limits::default()
+    }
+}
+
+pub(crate) fn create_bot_module(
+    wasm_bytes: &mut [u8],
+    rules: &Rules,
+    cache: Option<&ModuleCache>,
+) -> Result<BotModule, String> {
+    let report = validate(wasm_bytes, &validator_limits(rules));
+    if !report.is_valid() {
+        return Err(report.to_string());
+    }
+
+    let wasm_bytes = export_private_globals(wasm_bytes)?;
+    BotModule::new(instantiate(&wasm_bytes, rules, cache)?, report.has_memory)
+}
+
+pub(crate) fn create_reference_module(
+    bot: ReferenceBot,
+    rules: &Rules,
+) -> Result<BotModule, String> {
+    BotModule::new(instantiate_reference(bot, rules), false)
+}
+
+impl BotModule {
+    fn new(mut instance: Box<dyn BotInstance>, has_memory: bool) -> Result<BotModule, String> {
+        let init = if instance.has_export(Export::ContextBuffer)
+            && instance.has_export(Export::InitWithContext)
+        {
+            Init::Context
+        } else if instance.has_export(Export::Init) {
+            Init::Seed
+        } else {
+            return Err("failed to get function 'init'".to_string());
+        };
+
+        // Bots exporting the extended entry points receive the full roster, others get the
+        // closest opponent through the original 9 arguments.
+        let abi = if instance.memory_pages().is_some()
+            && instance.has_export(Export::ObservationBuffer)
+            && instance.has_export(Export::ComputeActionsExt)
+        {
+            Abi::Extended {
+                pointer: None,
+                action_pointer: None,
+            }
+        } else if instance.has_export(Export::ComputeActions) {
+            Abi::Legacy
+        } else {
+            return Err("failed to get function 'compute_actions'".to_string());
+        };
+
+        Ok(BotModule {
+            has_match_end: instance.has_export(Export::OnMatchEnd),
+            has_memory,
+            instance,
+            init,
+            abi,
+            observation: Vec::new(),
+        })
+    }
+
+    /// Let the bot reserve room for observations of `len` f32 values (extended ABI only).
+    pub fn prepare_observation_buffer(&mut self, len: usize) -> Result<(), Fault> {
+        if let Abi::Legacy = self.abi {
+            return Ok(());
+        }
+        let pointer = match self.instance.call(Export::ObservationBuffer, len as u32) {
+            Ok(ptr) => ptr,
+            Err(trap) => {
+                verbose!("Calling `observation_buffer` failed.");
+                return Err(self.fault(trap));
+            }
+        };
+        let action_pointer = if self.instance.has_export(Export::ActionBuffer) {
+            match self.instance.call(Export::ActionBuffer, ACTION_LEN as u32) {
+                Ok(ptr) => Some(ptr),
+                Err(trap) => {
+                    verbose!("Calling `action_buffer` failed.");
+                    return Err(self.fault(trap));
+                }
+            }
+        } else {
+            None
+        };
+        self.abi = Abi::Extended {
+            pointer: Some(pointer),
+            action_pointer,
+        };
+        Ok(())
+    }
+
+    pub fn init(&mut self, context: &MatchContext) -> Result<(), Fault> {
+        let result = match self.init {
+            Init::Seed => self.instance.call(Export::Init, context.seed),
+            Init::Context => {
+                let mut bytes = Vec::with_capacity(MatchContext::len(context.opponents.len()));
+                context.write_to(&mut bytes);
+                let len = bytes.len() as u32;
+                match self.instance.call(Export::ContextBuffer, len) {
+                    Ok(pointer) => {
+                        if let Err(err) = self.instance.write_memory(pointer, &bytes) {
+                            verbose!("Writing context failed: {}", err);
+                            return Err(Fault::Trap);
+                        }
+                        self.instance.call(Export::InitWithContext, len)
+                    }
+                    Err(trap) => Err(trap),
+                }
+            }
+        };
+        result.map(|_| ()).map_err(|trap| {
+            verbose!("Calling `init` failed.");
+            self.fault(trap)
+        })
+    }
+
+    /// Tell the bot how the match went (0 draw, 1 won, 2 lost), the only moment it can
+    /// write to its storage.
+    pub fn match_end(&mut self, result: u32) -> Result<(), Fault> {
+        if !self.has_match_end {
+            return Ok(());
+        }
+        self.instance.host().storage_writable = true;
+        let call = self.instance.call(Export::OnMatchEnd, result);
+        self.instance.host().storage_writable = false;
+        call.map(|_| ()).map_err(|trap| {
+            verbose!("Calling `on_match_end` failed.");
+            self.fault(trap)
+        })
+    }
+
+    pub fn set_storage(&mut self, storage: Vec<u8>) {
+        self.instance.host().storage = storage;
+    }
+
+    pub fn storage(&self) -> &[u8] {
+        &self.instance.host_ref().storage
+    }
+
+    pub fn compute_actions(&mut self, observation: &Observation) -> Result<Action, Fault> {
+        let result = match self.abi {
+            Abi::Legacy => {
+                let enemy = observation.closest_opponent();
+                self.instance.call_legacy(
+                    [
+                        observation.self_position[0],
+                        observation.self_position[1],
+                        observation.self_position[2],
+                        observation.ball[0],
+                        observation.ball[1],
+                        observation.ball[2],
+                        enemy[0],
+                        enemy[1],
+                        enemy[2],
+                    ]
+                    .map(finite),
+                )
+            }
+            Abi::Extended { pointer, .. } => {
+                let Some(pointer) = pointer else {
+                    return Ok(Action::NEUTRAL);
+                };
+                self.observation.clear();
+                observation.write_to(&mut self.observation);
+                let bytes: Vec<u8> = self
+                    .observation
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect();
+                if let Err(err) = self.instance.write_memory(pointer, &bytes) {
+                    verbose!("Writing observation failed: {}", err);
+                    return Err(Fault::Trap);
+                }
+                self.instance
+                    .call(Export::ComputeActionsExt, self.observation.len() as u32)
+            }
+        };
+
+        let action = result.map_err(|trap| {
+            verbose!("Calling `update` failed.");
+            self.fault(trap)
+        })?;
+        match self.abi {
+            Abi::Extended {
+                action_pointer: Some(action_pointer),
+                ..
+            } => self.read_action(action_pointer, action),
+            _ => Ok(Action::from_packed(action)),
+        }
+    }
+
+    /// Read the float action written by the bot, any component that is not finite makes
+    /// the whole action invalid.
+    fn read_action(&mut self, pointer: u32, flags: u32) -> Result<Action, Fault> {
+        let mut bytes = [0; ACTION_LEN * 4];
+        if let Err(err) = self.instance.read_memory(pointer, &mut bytes) {
+            verbose!("Reading action failed: {}", err);
+            return Err(Fault::Trap);
+        }
+        let mut impulse = [0.0; ACTION_LEN];
+        for (value, chunk) in impulse.iter_mut().zip(bytes.chunks_exact(4)) {
+            *value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        if !impulse.iter().all(|value| value.is_finite()) {
+            return Err(Fault::InvalidAction);
+        }
+        Ok(Action {
+            impulse,
+            boost: flags & BOOST_BIT != 0,
+        })
+    }
+
+    /// Messages logged since the last call to this function.
+    pub fn take_logs(&mut self) -> Vec<(LogLevel, String)> {
+        std::mem::take(&mut self.instance.host().logs)
+    }
+
+    /// Give the bot the log budget of a new tick, shared by the calls it gets until the next.
+    pub fn begin_tick(&mut self) {
+        self.instance.host().tick_bytes = 0;
+    }
+
+    /// Tell which limit, if any, made the last call trap.
+    fn fault(&mut self, trap: Trap) -> Fault {
+        match trap {
+            Trap::OutOfGas => {
+                verbose!("Not enough points remaining");
+                Fault::OutOfGas
+            }
+            Trap::CallDepthExceeded => {
+                verbose!("Call depth limit exceeded");
+                Fault::CallDepthExceeded
+            }
+            Trap::MemoryLimit => {
+                verbose!("Trapped after failing to grow the memory");
+                Fault::MemoryLimit
+            }
+            Trap::Other(message) => {
+                verbose!("Error: {}", message);
+                Fault::Trap
+            }
+        }
+    }
+
+    /// Copy the linear memory and the mutable exported globals of the instance.
+    pub fn save_state(&mut self) -> Result<ModuleState, String> {
+        if self.has_memory && self.instance.memory_pages().is_none() {
+            return Err("the bot does not export its memory".to_string());
+        }
+        let (memory, mut globals) = self.instance.save_state()?;
+        for (_, value) in globals.iter_mut() {
+            *value = value.clone().canonical();
+        }
+        let (observation_pointer, action_pointer) = match self.abi {
+            Abi::Extended {
+                pointer,
+                action_pointer,
+            } => (pointer, action_pointer),
+            Abi::Legacy => (None, None),
+        };
+        Ok(ModuleState {
+            memory,
+            globals,
+            observation_pointer,
+            action_pointer,
+            log_bytes: self.instance.host_ref().match_bytes,
+        })
+    }
+
+    /// Put the instance back in the state returned by [`BotModule::save_state`].
+    pub fn load_state(&mut self, state: &ModuleState) -> Result<(), String> {
+        self.instance.load_state(&state.memory, &state.globals)?;
+        if let Abi::Extended {
+            pointer,
+            action_pointer,
+        } = &mut self.abi
+        {
+            *pointer = state.observation_pointer;
+            *action_pointer = state.action_pointer;
+        }
+        self.instance.host().match_bytes = state.log_bytes;
+        Ok(())
+    }
+
+    /// Gas left by the last call, 0 if it ran out.
+    pub fn remaining_gas(&mut self) -> u64 {
+        self.instance.gas()
+    }
+
+    pub fn set_remaining_points(&mut self, points: u64) {
+        self.instance.set_gas(points);
+    }
+}
@@ -0,0 +1,116 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Length of the tag written in front of each artifact on disk.
+const TAG_LEN: usize = 32;
+
+/// Compiled bot modules (serialized wasmer artifacts), so that a bot playing many matches is
+/// compiled once. Keys cover the wasm bytes and everything that changes the generated code
+/// (cost table, limits, machine version), so a stale artifact is never picked up.
+///
+/// Share one cache between battles with [`crate::Battle::set_module_cache`]. With a
+/// directory, artifacts are also written there, signed with a secret, and only loaded back
+/// when the signature matches: they are native code. The `interpreter` backend has nothing
+/// to compile and ignores the cache.
+#[derive(Default)]
+pub struct ModuleCache {
+    artifacts: Mutex<HashMap<String, Arc<Vec<u8>>>>,
+    directory: Option<PathBuf>,
+    secret: Vec<u8>,
+}
+
+impl ModuleCache {
+    /// A cache kept in memory only.
+    pub fn new() -> Self {
+        ModuleCache::default()
+    }
+
+    /// A cache backed by `directory`, created if needed. Artifacts are signed with a secret
+    /// drawn for the process, so only the ones it wrote are loaded, see
+    /// [`ModuleCache::with_secret`] to share them between runs.
+    pub fn with_directory(directory: impl Into<PathBuf>) -> Self {
+        ModuleCache {
+            artifacts: Mutex::new(HashMap::new()),
+            directory: Some(directory.into()),
+            secret: process_secret().to_vec(),
+        }
+    }
+
+    /// Sign the artifacts with `secret` instead, so that the processes knowing it load each
+    /// other's. Keep it out of the directory.
+    pub fn with_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.secret = secret.into();
+        self
+    }
+
+    /// Number of artifacts held in memory.
+    pub fn len(&self) -> usize {
+        self.artifacts.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Arc<Vec<u8>>> {
+        if let Some(artifact) = self.artifacts.lock().unwrap().get(key) {
+            return Some(artifact.clone());
+        }
+        let directory = self.directory.as_ref()?;
+        let file = fs::read(directory.join(key)).ok()?;
+        let (tag, artifact) = file.split_at_checked(TAG_LEN)?;
+        if self.mac(key, artifact).verify_slice(tag).is_err() {
+            verbose!("ignoring unsigned module {}", key);
+            return None;
+        }
+        let artifact = Arc::new(artifact.to_vec());
+        self.artifacts
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), artifact.clone());
+        Some(artifact)
+    }
+
+    pub(crate) fn insert(&self, key: String, artifact: Vec<u8>) {
+        if let Some(directory) = &self.directory {
+            // write then rename, so that another process never reads half an artifact
+            let path = directory.join(&key);
+            let partial = directory.join(format!("{}.partial", key));
+            let written = fs::create_dir_all(directory)
+                .and_then(|_| {
+                    let tag = self.mac(&key, &artifact).finalize().into_bytes();
+                    fs::write(&partial, [tag.as_slice(), &artifact].concat())
+                })
+                .and_then(|_| fs::rename(&partial, &path));
+            if let Err(err) = written {
+                verbose!("failed to write {}: {}", path.display(), err);
+            }
+        }
+        self.artifacts
+            .lock()
+            .unwrap()
+            .insert(key, Arc::new(artifact));
+    }
+
+    /// Signature of the artifact stored under `key`, which binds it to its key.
+    fn mac(&self, key: &str, artifact: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("any key length");
+        mac.update(key.as_bytes());
+        mac.update(artifact);
+        mac
+    }
+}
+
+/// Secret shared by the caches of this process.
+fn process_secret() -> &'static [u8; 32] {
+    static SECRET: OnceLock<[u8; 32]> = OnceLock::new();
+    SECRET.get_or_init(|| {
+        let mut secret = [0; 32];
+        getrandom::getrandom(&mut secret).expect("no source of randomness");
+        secret
+    })
+}
@@ -0,0 +1,66 @@
+/// Version of the layout written by [`MatchContext::write_to`].
+pub(crate) const CONTEXT_VERSION: u32 = 1;
+
+/// What a bot is told about the match before it starts.
+pub struct MatchContext<'a> {
+    pub seed: u32,
+    pub match_id: [u8; 32],
+    pub bot_hash: [u8; 32],
+    pub team: u8,
+    pub rules_version: u32,
+    pub max_ticks: u64,
+    pub init_gas: u64,
+    /// Gas of every `compute_actions` call, or the allowance added to the reserve each tick
+    /// with a gas bank.
+    pub tick_gas: u64,
+    /// 0 without a gas bank.
+    pub gas_bank_capacity: u64,
+    pub match_end_gas: u64,
+    pub opponents: &'a [[u8; 32]],
+}
+
+impl MatchContext<'_> {
+    /// Number of bytes written by [`MatchContext::write_to`].
+    pub(crate) fn len(num_opponents: usize) -> usize {
+        4 + 4 + 32 + 32 + 4 + 4 + 8 * 5 + 4 + num_opponents * 32
+    }
+
+    /// Layout passed to `init_with_context` (integers are little-endian):
+    ///
+    /// | offset | type         | field                                     |
+    /// |--------|--------------|-------------------------------------------|
+    /// | 0      | u32          | layout version                            |
+    /// | 4      | u32          | seed                                      |
+    /// | 8      | [u8; 32]     | match id                                  |
+    /// | 40     | [u8; 32]     | hash of the bot itself                    |
+    /// | 72     | u32          | team, which is also the side of the arena |
+    /// | 76     | u32          | rule set version                          |
+    /// | 80     | u64          | ticks before the match is a draw          |
+    /// | 88     | u64          | gas of `init`                             |
+    /// | 96     | u64          | gas per tick                              |
+    /// | 104    | u64          | gas bank capacity, 0 without a bank       |
+    /// | 112    | u64          | gas of `on_match_end`                     |
+    /// | 120    | u32          | number of opponents O                     |
+    /// | 124    | O * [u8; 32] | hash of each opponent                     |
+    pub(crate) fn write_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&CONTEXT_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&self.match_id);
+        out.extend_from_slice(&self.bot_hash);
+        out.extend_from_slice(&(self.team as u32).to_le_bytes());
+        out.extend_from_slice(&self.rules_version.to_le_bytes());
+        for value in [
+            self.max_ticks,
+            self.init_gas,
+            self.tick_gas,
+            self.gas_bank_capacity,
+            self.match_end_gas,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.opponents.len() as u32).to_le_bytes());
+        for opponent in self.opponents {
+            out.extend_from_slice(opponent);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! What decides what a bot does: a compiled bot or any Rust code implementing
+//! [`Controller`], for example a test fixture or a policy being trained.
+
+use crate::bot::{Action, BotModule, Observation};
+use crate::context::MatchContext;
+use crate::fault::Fault;
+use crate::logs::LogLevel;
+use crate::snapshot::ModuleState;
+
+/// Plays a bot. Closures taking an [`Observation`] and returning an [`Action`] are
+/// controllers too.
+pub trait Controller: Send {
+    /// Called once before the first tick.
+    fn init(&mut self, _context: &MatchContext) -> Result<(), Fault> {
+        Ok(())
+    }
+
+    /// What the bot does this tick. An error, or an impulse that is not finite, leaves the
+    /// bot idle for the tick and counts as a fault.
+    fn compute_actions(&mut self, observation: &Observation) -> Result<Action, Fault>;
+
+    /// Called once the match is over with 0 for a draw, 1 for a win and 2 for a loss.
+    fn match_end(&mut self, _result: u32) -> Result<(), Fault> {
+        Ok(())
+    }
+
+    /// Shown to the other bots as the SHA-256 of `controller:<name>`.
+    fn name(&self) -> &str {
+        "controller"
+    }
+}
+
+impl<F> Controller for F
+where
+    F: FnMut(&Observation) -> Action + Send,
+{
+    fn compute_actions(&mut self, observation: &Observation) -> Result<Action, Fault> {
+        Ok(self(observation))
+    }
+}
+
+impl Controller for BotModule {
+    fn init(&mut self, context: &MatchContext) -> Result<(), Fault> {
+        BotModule::init(self, context)
+    }
+
+    fn compute_actions(&mut self, observation: &Observation) -> Result<Action, Fault> {
+        BotModule::compute_actions(self, observation)
+    }
+
+    fn match_end(&mut self, result: u32) -> Result<(), Fault> {
+        BotModule::match_end(self, result)
+    }
+}
+
+/// A bot of the battle. Only wasm bots have gas, logs, storage and snapshots, Rust
+/// controllers run for free and can not be snapshotted.
+pub(crate) enum Driver {
+    Module(BotModule),
+    Controller {
+        controller: Box<dyn Controller>,
+        /// Gas given for the call, all of it is left afterwards.
+        gas: u64,
+    },
+}
+
+impl Driver {
+    pub fn controller(controller: impl Controller + 'static) -> Driver {
+        Driver::Controller {
+            controller: Box::new(controller),
+            gas: 0,
+        }
+    }
+
+    fn as_controller(&mut self) -> &mut dyn Controller {
+        match self {
+            Driver::Module(module) => module,
+            Driver::Controller { controller, .. } => controller.as_mut(),
+        }
+    }
+
+    pub fn prepare_observation_buffer(&mut self, len: usize) -> Result<(), Fault> {
+        match self {
+            Driver::Module(module) => module.prepare_observation_buffer(len),
+            Driver::Controller { .. } => Ok(()),
+        }
+    }
+
+    pub fn init(&mut self, context: &MatchContext) -> Result<(), Fault> {
+        self.as_controller().init(context)
+    }
+
+    pub fn compute_actions(&mut self, observation: &Observation) -> Result<Action, Fault> {
+        let action = self.as_controller().compute_actions(observation)?;
+        // compiled bots are checked when their action is read
+        if !action.impulse.iter().all(|value| value.is_finite()) {
+            return Err(Fault::InvalidAction);
+        }
+        Ok(action)
+    }
+
+    pub fn match_end(&mut self, result: u32) -> Result<(), Fault> {
+        self.as_controller().match_end(result)
+    }
+
+    pub fn begin_tick(&mut self) {
+        if let Driver::Module(module) = self {
+            module.begin_tick();
+        }
+    }
+
+    pub fn set_remaining_points(&mut self, points: u64) {
+        match self {
+            Driver::Module(module) => module.set_remaining_points(points),
+            Driver::Controller { gas, .. } => *gas = points,
+        }
+    }
+
+    pub fn remaining_gas(&mut self) -> u64 {
+        match self {
+            Driver::Module(module) => module.remaining_gas(),
+            Driver::Controller { gas, .. } => *gas,
+        }
+    }
+
+    pub fn take_logs(&mut self) -> Vec<(LogLevel, String)> {
+        match self {
+            Driver::Module(module) => module.take_logs(),
+            Driver::Controller { .. } => Vec::new(),
+        }
+    }
+
+    pub fn set_storage(&mut self, storage: Vec<u8>) -> Result<(), String> {
+        match self {
+            Driver::Module(module) => {
+                module.set_storage(storage);
+                Ok(())
+            }
+            Driver::Controller { .. } => Err("Rust controllers have no storage".to_string()),
+        }
+    }
+
+    pub fn storage(&self) -> &[u8] {
+        match self {
+            Driver::Module(module) => module.storage(),
+            Driver::Controller { .. } => &[],
+        }
+    }
+
+    pub fn save_state(&mut self) -> Result<ModuleState, String> {
+        match self {
+            Driver::Module(module) => module.save_state(),
+            Driver::Controller { controller, .. } => Err(format!(
+                "{} is a Rust controller, it can not be snapshotted",
+                controller.name()
+            )),
+        }
+    }
+
+    pub fn load_state(&mut self, state: &ModuleState) -> Result<(), String> {
+        match self {
+            Driver::Module(module) => module.load_state(state),
+            Driver::Controller { controller, .. } => Err(format!(
+                "{} is a Rust controller, it can not be restored",
+                controller.name()
+            )),
+        }
+    }
+}
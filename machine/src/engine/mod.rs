@@ -0,0 +1,166 @@
+//! Engines running the bots. [`crate::bot::BotModule`] only talks to a [`BotInstance`], the
+//! backend behind it is picked at build time:
+//!
+//! - `wasmer` (default): Cranelift natively, the JS engine in the browser.
+//! - `interpreter`: wasmi, a pure Rust interpreter needing no JIT, for the rollup. It takes
+//!   precedence when both features are enabled.
+//!
+//! Both meter every instruction and enforce [`crate::rules::BotLimits`], but an instruction
+//! does not cost the same in both, so a bot close to its gas budget can run out on one
+//! backend and not on the other. Matches meant to be replayed must use the same backend.
+//!
+//! Built-in opponents ([`crate::reference::ReferenceBot`]) are not wasm at all: they run as
+//! Rust behind the same [`BotInstance`] interface, whatever the backend.
+//!
+//! Natively, Cranelift canonicalizes every NaN a bot computes, and the interpreter runs bots
+//! rewritten to do the same. The JS engine does not, so [`crate::bot::BotModule`] also
+//! canonicalizes what crosses the boundary: observations never hold NaN or infinities,
+//! float actions and saved globals only ever hold the canonical NaN.
+
+use crate::cache::ModuleCache;
+use crate::host::HostState;
+use crate::reference::ReferenceBot;
+use crate::rules::Rules;
+use crate::snapshot::GlobalValue;
+
+#[cfg(feature = "interpreter")]
+mod wasmi_engine;
+#[cfg(feature = "interpreter")]
+use wasmi_engine as backend;
+
+#[cfg(all(feature = "wasmer", not(feature = "interpreter")))]
+mod wasmer_engine;
+#[cfg(all(feature = "wasmer", not(feature = "interpreter")))]
+use wasmer_engine as backend;
+
+mod native;
+
+#[cfg(not(any(feature = "wasmer", feature = "interpreter")))]
+compile_error!("enable the `wasmer` or the `interpreter` feature to run bots");
+
+/// Size of a page of linear memory.
+pub(crate) const PAGE_SIZE: usize = 0x10000;
+
+/// Functions of the bot ABI called by the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Export {
+    /// `init(seed)`
+    Init,
+    /// `context_buffer(len) -> ptr`
+    ContextBuffer,
+    /// `init_with_context(len)`
+    InitWithContext,
+    /// `observation_buffer(len) -> ptr`
+    ObservationBuffer,
+    /// `compute_actions_ext(len) -> u32`
+    ComputeActionsExt,
+    /// `action_buffer(len) -> ptr`
+    ActionBuffer,
+    /// `compute_actions(self_x, self_y, self_z, ball_x, ball_y, ball_z, enemy_x, enemy_y, enemy_z) -> u32`
+    ComputeActions,
+    /// `on_match_end(result)`
+    OnMatchEnd,
+}
+
+impl Export {
+    pub const ALL: [Export; 8] = [
+        Export::Init,
+        Export::ContextBuffer,
+        Export::InitWithContext,
+        Export::ObservationBuffer,
+        Export::ComputeActionsExt,
+        Export::ActionBuffer,
+        Export::ComputeActions,
+        Export::OnMatchEnd,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Export::Init => "init",
+            Export::ContextBuffer => "context_buffer",
+            Export::InitWithContext => "init_with_context",
+            Export::ObservationBuffer => "observation_buffer",
+            Export::ComputeActionsExt => "compute_actions_ext",
+            Export::ActionBuffer => "action_buffer",
+            Export::ComputeActions => "compute_actions",
+            Export::OnMatchEnd => "on_match_end",
+        }
+    }
+
+    /// Whether the function returns a `u32`, the others return nothing.
+    pub fn returns_u32(self) -> bool {
+        matches!(
+            self,
+            Export::ContextBuffer
+                | Export::ObservationBuffer
+                | Export::ComputeActionsExt
+                | Export::ActionBuffer
+                | Export::ComputeActions
+        )
+    }
+}
+
+/// Why a call into the bot did not return.
+#[derive(Debug)]
+pub(crate) enum Trap {
+    OutOfGas,
+    CallDepthExceeded,
+    /// Any trap after a `memory.grow` failed during the call.
+    MemoryLimit,
+    Other(String),
+}
+
+/// An instantiated bot, whatever runs it.
+pub(crate) trait BotInstance: Send {
+    /// Whether the bot exports `export` with the signature of the ABI.
+    fn has_export(&self, export: Export) -> bool;
+
+    /// Call an export taking a `u32`, exports returning nothing give 0.
+    fn call(&mut self, export: Export, arg: u32) -> Result<u32, Trap>;
+
+    /// Call the legacy `compute_actions`.
+    fn call_legacy(&mut self, args: [f32; 9]) -> Result<u32, Trap>;
+
+    fn read_memory(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), String>;
+
+    fn write_memory(&mut self, offset: u32, bytes: &[u8]) -> Result<(), String>;
+
+    /// Size of the exported memory, `None` when the bot does not export one.
+    fn memory_pages(&mut self) -> Option<u32>;
+
+    /// Gas of the next call.
+    fn set_gas(&mut self, gas: u64);
+
+    /// Gas left by the last call, 0 if it ran out.
+    fn gas(&mut self) -> u64;
+
+    fn host(&mut self) -> &mut HostState;
+
+    fn host_ref(&self) -> &HostState;
+
+    /// Linear memory (empty without an exported one) and mutable exported globals.
+    #[allow(clippy::type_complexity)]
+    fn save_state(&mut self) -> Result<(Vec<u8>, Vec<(String, GlobalValue)>), String>;
+
+    /// Put back what [`BotInstance::save_state`] returned. Memory can only grow, so if it
+    /// grew since, the extra pages are zeroed.
+    fn load_state(
+        &mut self,
+        memory: &[u8],
+        globals: &[(String, GlobalValue)],
+    ) -> Result<(), String>;
+}
+
+/// Compile (or fetch from `cache`) and instantiate an already validated bot.
+pub(crate) fn instantiate(
+    wasm_bytes: &[u8],
+    rules: &Rules,
+    cache: Option<&ModuleCache>,
+) -> Result<Box<dyn BotInstance>, String> {
+    backend::instantiate(wasm_bytes, rules, HostState::new(rules), cache)
+}
+
+/// Instantiate a built-in opponent.
+pub(crate) fn instantiate_reference(bot: ReferenceBot, rules: &Rules) -> Box<dyn BotInstance> {
+    native::instantiate(bot, rules)
+}
@@ -0,0 +1,162 @@
+//! A [`ReferenceBot`] dressed up as a bot instance: it exports the extended ABI, reads its
+//! observation from and writes its float action to a page of memory like a compiled bot
+//! would, and uses no gas.
+
+use crate::bot::BOOST_BIT;
+use crate::engine::{BotInstance, Export, Trap, PAGE_SIZE};
+use crate::host::HostState;
+use crate::reference::{ReferenceBot, ReferenceState};
+use crate::rules::Rules;
+use crate::snapshot::GlobalValue;
+
+/// Where the observation is written, then the action. An observation is a few hundred bytes
+/// at most.
+const OBSERVATION_POINTER: u32 = 0;
+const ACTION_POINTER: u32 = (PAGE_SIZE / 2) as u32;
+
+struct NativeInstance {
+    bot: ReferenceBot,
+    state: ReferenceState,
+    memory: Vec<u8>,
+    gas: u64,
+    host: HostState,
+}
+
+pub(crate) fn instantiate(bot: ReferenceBot, rules: &Rules) -> Box<dyn BotInstance> {
+    Box::new(NativeInstance {
+        bot,
+        state: ReferenceState::default(),
+        memory: vec![0; PAGE_SIZE],
+        gas: 0,
+        host: HostState::new(rules),
+    })
+}
+
+impl NativeInstance {
+    fn compute_actions(&mut self, len: u32) -> Result<u32, Trap> {
+        let start = OBSERVATION_POINTER as usize;
+        let bytes = self
+            .memory
+            .get(start..start + len as usize * 4)
+            .ok_or_else(|| Trap::Other("observation out of bounds".to_string()))?;
+        let observation: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        let (impulse, boost) = self.bot.act(&mut self.state, &observation);
+        let action: Vec<u8> = impulse
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.write_memory(ACTION_POINTER, &action)
+            .map_err(Trap::Other)?;
+        Ok(if boost { BOOST_BIT } else { 0 })
+    }
+}
+
+impl BotInstance for NativeInstance {
+    fn has_export(&self, export: Export) -> bool {
+        matches!(
+            export,
+            Export::Init
+                | Export::ObservationBuffer
+                | Export::ActionBuffer
+                | Export::ComputeActionsExt
+        )
+    }
+
+    fn call(&mut self, export: Export, arg: u32) -> Result<u32, Trap> {
+        match export {
+            Export::Init => {
+                self.state.rng = arg as u64;
+                Ok(0)
+            }
+            Export::ObservationBuffer => Ok(OBSERVATION_POINTER),
+            Export::ActionBuffer => Ok(ACTION_POINTER),
+            Export::ComputeActionsExt => self.compute_actions(arg),
+            _ => Err(Trap::Other(format!(
+                "{} does not export `{}`",
+                self.bot,
+                export.name()
+            ))),
+        }
+    }
+
+    fn call_legacy(&mut self, _args: [f32; 9]) -> Result<u32, Trap> {
+        Err(Trap::Other(format!(
+            "{} does not export `compute_actions`",
+            self.bot
+        )))
+    }
+
+    fn read_memory(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), String> {
+        let start = offset as usize;
+        let source = self
+            .memory
+            .get(start..start + bytes.len())
+            .ok_or_else(|| "out of bounds".to_string())?;
+        bytes.copy_from_slice(source);
+        Ok(())
+    }
+
+    fn write_memory(&mut self, offset: u32, bytes: &[u8]) -> Result<(), String> {
+        let start = offset as usize;
+        self.memory
+            .get_mut(start..start + bytes.len())
+            .ok_or_else(|| "out of bounds".to_string())?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn memory_pages(&mut self) -> Option<u32> {
+        Some(1)
+    }
+
+    fn set_gas(&mut self, gas: u64) {
+        self.gas = gas;
+    }
+
+    fn gas(&mut self) -> u64 {
+        self.gas
+    }
+
+    fn host(&mut self) -> &mut HostState {
+        &mut self.host
+    }
+
+    fn host_ref(&self) -> &HostState {
+        &self.host
+    }
+
+    fn save_state(&mut self) -> Result<(Vec<u8>, Vec<(String, GlobalValue)>), String> {
+        Ok((
+            self.memory.clone(),
+            vec![
+                ("tick".to_string(), GlobalValue::I64(self.state.tick as i64)),
+                ("rng".to_string(), GlobalValue::I64(self.state.rng as i64)),
+            ],
+        ))
+    }
+
+    fn load_state(
+        &mut self,
+        memory: &[u8],
+        globals: &[(String, GlobalValue)],
+    ) -> Result<(), String> {
+        if memory.len() != PAGE_SIZE {
+            return Err(format!("{} expects a single page of memory", self.bot));
+        }
+        self.memory.copy_from_slice(memory);
+        for (name, value) in globals {
+            let GlobalValue::I64(value) = value else {
+                return Err(format!("unexpected value for `{}`", name));
+            };
+            match name.as_str() {
+                "tick" => self.state.tick = *value as u64,
+                "rng" => self.state.rng = *value as u64,
+                _ => return Err(format!("{} has no global `{}`", self.bot, name)),
+            }
+        }
+        Ok(())
+    }
+}
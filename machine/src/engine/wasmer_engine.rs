@@ -0,0 +1,557 @@
+//! Wasmer backend: Cranelift with the metering and call depth middlewares natively, the JS
+//! engine in the browser (where calls are not metered).
+
+use wasmer::RuntimeError;
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    crate::limits::{
+        CallDepth, LimitingTunables, MemoryGrowth, CALL_DEPTH_EXCEEDED_GLOBAL, CALL_DEPTH_GLOBAL,
+        MEMORY_GROW_FAILED_GLOBAL,
+    },
+    crate::rules::BotLimits,
+    sha2::{Digest, Sha256},
+    std::sync::Arc,
+    wasmer::sys::{BaseTunables, EngineBuilder, NativeEngineExt},
+    wasmer::wasmparser::Operator,
+    wasmer::{CompilerConfig, Engine, Target},
+    wasmer_compiler_cranelift::Cranelift,
+    wasmer_middlewares::{
+        metering::{get_remaining_points, set_remaining_points, MeteringPoints},
+        Metering,
+    },
+};
+
+use wasmer::Function;
+use wasmer::{
+    imports, Extern, FunctionEnv, FunctionEnvMut, Instance, Memory, Module, Mutability, Pages,
+    Store, TypedFunction, Value, WASM_PAGE_SIZE,
+};
+
+use super::{BotInstance, Export, Trap};
+use crate::cache::ModuleCache;
+use crate::host::HostState;
+use crate::logs::LogLevel;
+use crate::rules::Rules;
+use crate::snapshot::GlobalValue;
+
+/// Host side state reachable from the imports.
+struct WasmerEnv {
+    host: HostState,
+    memory: Option<Memory>,
+}
+
+fn log_from_memory(mut env: FunctionEnvMut<WasmerEnv>, level: LogLevel, ptr: u32, len: u32) {
+    let (data, store) = env.data_and_store_mut();
+    // never read more than what could be kept
+    let len = (len as usize).min(data.host.log_budget());
+    if len == 0 {
+        return;
+    }
+    let Some(memory) = &data.memory else {
+        return;
+    };
+    let mut bytes = vec![0; len];
+    if memory.view(&store).read(ptr as u64, &mut bytes).is_err() {
+        return;
+    }
+    data.host.log_bytes(level, &bytes);
+}
+
+fn log(env: FunctionEnvMut<WasmerEnv>, ptr: u32, len: u32) {
+    log_from_memory(env, LogLevel::Info, ptr, len);
+}
+
+fn log_level(env: FunctionEnvMut<WasmerEnv>, level: u32, ptr: u32, len: u32) {
+    log_from_memory(env, LogLevel::from_u32(level), ptr, len);
+}
+
+fn log_f32(mut env: FunctionEnvMut<WasmerEnv>, value: f32) {
+    env.data_mut()
+        .host
+        .push_log(LogLevel::Info, value.to_string());
+}
+
+fn print_u32(mut env: FunctionEnvMut<WasmerEnv>, num: u32) {
+    env.data_mut()
+        .host
+        .push_log(LogLevel::Info, num.to_string());
+}
+
+/// Copy up to `len` bytes of the stored blob to `ptr` and return the size of the blob.
+fn storage_read(mut env: FunctionEnvMut<WasmerEnv>, ptr: u32, len: u32) -> u32 {
+    let (data, store) = env.data_and_store_mut();
+    let size = data.host.storage.len();
+    let copied = size.min(len as usize);
+    if copied > 0 {
+        if let Some(memory) = &data.memory {
+            if memory
+                .view(&store)
+                .write(ptr as u64, &data.host.storage[..copied])
+                .is_err()
+            {
+                return 0;
+            }
+        }
+    }
+    size as u32
+}
+
+/// Replace the stored blob with the `len` bytes at `ptr`. Only works from `on_match_end`,
+/// returns 1 on success and 0 otherwise.
+fn storage_write(mut env: FunctionEnvMut<WasmerEnv>, ptr: u32, len: u32) -> u32 {
+    let (data, store) = env.data_and_store_mut();
+    if !data.host.can_write_storage(len as usize) {
+        return 0;
+    }
+    let Some(memory) = &data.memory else {
+        return 0;
+    };
+    let mut bytes = vec![0; len as usize];
+    if memory.view(&store).read(ptr as u64, &mut bytes).is_err() {
+        return 0;
+    }
+    data.host.storage = bytes;
+    1
+}
+
+type LegacyFunction = TypedFunction<(f32, f32, f32, f32, f32, f32, f32, f32, f32), u32>;
+
+/// Exports of the ABI found in the instance.
+#[derive(Default)]
+struct Exports {
+    /// Exports returning nothing.
+    void: Vec<(Export, TypedFunction<u32, ()>)>,
+    /// Exports returning a `u32`.
+    value: Vec<(Export, TypedFunction<u32, u32>)>,
+    legacy: Option<LegacyFunction>,
+}
+
+struct WasmerInstance {
+    instance: Instance,
+    store: Store,
+    env: FunctionEnv<WasmerEnv>,
+    memory: Option<Memory>,
+    exports: Exports,
+    /// Calls are not metered in the browser, the points set are simply reported back.
+    #[cfg(target_arch = "wasm32")]
+    points: u64,
+}
+
+// Safety: the JS objects behind the instance never leave the thread, the browser runs the
+// machine on a single one.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for WasmerInstance {}
+
+/// Bump whenever [`operator_cost`] changes, so that cached modules metered with the old costs
+/// are not used anymore.
+#[cfg(not(target_arch = "wasm32"))]
+const COST_TABLE_VERSION: u32 = 1;
+
+/// Bump whenever the middlewares of [`crate::limits`] change the code they generate.
+#[cfg(not(target_arch = "wasm32"))]
+const INSTRUMENTATION_VERSION: u32 = 2;
+
+/// Whether the generated code canonicalizes NaNs, part of the cache key.
+#[cfg(not(target_arch = "wasm32"))]
+const CANONICALIZE_NANS: bool = true;
+
+/// Gas charged for each operator executed by a bot.
+#[cfg(not(target_arch = "wasm32"))]
+fn operator_cost(_operator: &Operator) -> u64 {
+    1
+    // match operator {
+    //     _ => 100,
+    // }
+}
+
+/// Engine running the bots: memories and tables are capped by the tunables.
+#[cfg(not(target_arch = "wasm32"))]
+fn limited_engine(mut engine: Engine, limits: &BotLimits) -> Engine {
+    engine.set_tunables(LimitingTunables::new(
+        BaseTunables::for_target(&Target::default()),
+        limits,
+    ));
+    engine
+}
+
+/// Key of the compiled module in the cache: the artifact depends on the wasm bytes and on
+/// everything that instruments or shapes the code generated for them.
+#[cfg(not(target_arch = "wasm32"))]
+fn cache_key(wasm_bytes: &[u8], limits: &BotLimits) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(std::env::consts::ARCH.as_bytes());
+    hasher.update(COST_TABLE_VERSION.to_le_bytes());
+    hasher.update(INSTRUMENTATION_VERSION.to_le_bytes());
+    hasher.update([CANONICALIZE_NANS as u8]);
+    hasher.update(limits.max_memory_pages.to_le_bytes());
+    hasher.update(limits.max_table_elements.to_le_bytes());
+    hasher.update(limits.max_call_depth.to_le_bytes());
+    hasher.update(wasm_bytes);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Compile the bot, or load it from the cache when it was compiled before.
+#[cfg(not(target_arch = "wasm32"))]
+fn compile(
+    wasm_bytes: &[u8],
+    limits: &BotLimits,
+    cache: Option<&ModuleCache>,
+) -> Result<(Store, Module), String> {
+    let key = cache.map(|_| cache_key(wasm_bytes, limits));
+    if let (Some(cache), Some(key)) = (cache, &key) {
+        if let Some(artifact) = cache.get(key) {
+            let store = Store::new(limited_engine(Engine::headless(), limits));
+            // Safety: artifacts only get in the cache through `compile` below, the ones read
+            // from disk are checked against the signature the cache wrote with them.
+            match unsafe { Module::deserialize(&store, artifact.as_slice()) } {
+                Ok(module) => return Ok((store, module)),
+                Err(err) => verbose!("ignoring cached module: {}", err),
+            }
+        }
+    }
+
+    // Now let's create our metering middleware.
+    //
+    // `Metering` needs to be configured with a limit and a cost function.
+    //
+    // For each `Operator`, the metering middleware will call the cost
+    // function and subtract the cost from the remaining points.
+    let initial_points = 100;
+    let metering = Arc::new(Metering::new(initial_points, operator_cost));
+    let mut compiler_config = Cranelift::default();
+    // NaN bit patterns otherwise depend on the host CPU
+    compiler_config.canonicalize_nans(CANONICALIZE_NANS);
+    compiler_config.push_middleware(metering);
+    compiler_config.push_middleware(Arc::new(CallDepth::new(limits.max_call_depth)));
+    compiler_config.push_middleware(Arc::new(MemoryGrowth::default()));
+
+    // Create a Store.
+    //
+    // We use our previously create compiler configuration
+    // with the Universal engine, capping memories and tables.
+    let store = Store::new(limited_engine(
+        EngineBuilder::new(compiler_config).into(),
+        limits,
+    ));
+
+    verbose!("Compiling wasm module...");
+    // Let's compile the Wasm module.
+    let module = Module::new(&store, wasm_bytes)
+        .map_err(|err| format!("failed to create module: {}", err))?;
+
+    if let (Some(cache), Some(key)) = (cache, key) {
+        match module.serialize() {
+            Ok(artifact) => cache.insert(key, artifact.to_vec()),
+            Err(err) => verbose!("failed to serialize module: {}", err),
+        }
+    }
+    Ok((store, module))
+}
+
+pub(super) fn instantiate(
+    wasm_bytes: &[u8],
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))] rules: &Rules,
+    host: HostState,
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))] cache: Option<&ModuleCache>,
+) -> Result<Box<dyn BotInstance>, String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let (mut store, module) = compile(wasm_bytes, &rules.limits, cache)?;
+
+    #[cfg(target_arch = "wasm32")]
+    let (mut store, module) = {
+        let store = Store::default();
+        verbose!("Compiling wasm module...");
+        let module = Module::new(&store, wasm_bytes)
+            .map_err(|err| format!("failed to create module: {}", err))?;
+        (store, module)
+    };
+
+    // Let's define the import object used to import our function
+    // into our webassembly sample application.
+
+    let env = FunctionEnv::new(&mut store, WasmerEnv { host, memory: None });
+
+    // this is how you import values:
+    // let value = Global::new(&mut store, Value::I32(value)); // there is no u32 but here we can pass as if
+
+    let import_object = imports! {
+        "env" => {
+            "print_u32" => Function::new_typed_with_env(&mut store, &env, print_u32),
+            "log" => Function::new_typed_with_env(&mut store, &env, log),
+            "log_f32" => Function::new_typed_with_env(&mut store, &env, log_f32),
+            "log_level" => Function::new_typed_with_env(&mut store, &env, log_level),
+            "storage_read" => Function::new_typed_with_env(&mut store, &env, storage_read),
+            "storage_write" => Function::new_typed_with_env(&mut store, &env, storage_write),
+        },
+    };
+
+    verbose!("Instantiating wasm module...");
+    // Let's instantiate the Wasm module.
+    let instance = Instance::new(&mut store, &module, &import_object)
+        .map_err(|err| format!("failed to instantiate module: {}", err))?;
+
+    let memory = instance.exports.get_memory("memory").ok().cloned();
+    env.as_mut(&mut store).memory = memory.clone();
+
+    let mut exports = Exports::default();
+    for export in Export::ALL {
+        if export == Export::ComputeActions {
+            exports.legacy = instance
+                .exports
+                .get_typed_function(&store, export.name())
+                .ok();
+        } else if export.returns_u32() {
+            if let Ok(function) = instance.exports.get_typed_function(&store, export.name()) {
+                exports.value.push((export, function));
+            }
+        } else if let Ok(function) = instance.exports.get_typed_function(&store, export.name()) {
+            exports.void.push((export, function));
+        }
+    }
+
+    Ok(Box::new(WasmerInstance {
+        instance,
+        store,
+        env,
+        memory,
+        exports,
+        #[cfg(target_arch = "wasm32")]
+        points: 0,
+    }))
+}
+
+/// Print where the bot trapped.
+#[cfg(not(target_arch = "wasm32"))]
+fn print_frames(e: &RuntimeError) {
+    let frames = e.trace();
+    let frames_len = frames.len();
+
+    for (i, frame) in frames.iter().enumerate() {
+        verbose!(
+            "  Frame #{}: {:?}::{:?}",
+            frames_len - i,
+            frame.module_name(),
+            frame.function_name().unwrap_or("<func>")
+        );
+    }
+}
+
+impl WasmerInstance {
+    /// Reset the globals of the middlewares before calling into the bot.
+    fn begin_call(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        for name in [
+            CALL_DEPTH_GLOBAL,
+            CALL_DEPTH_EXCEEDED_GLOBAL,
+            MEMORY_GROW_FAILED_GLOBAL,
+        ] {
+            if let Ok(global) = self.instance.exports.get_global(name) {
+                let _ = global.set(&mut self.store, Value::I32(0));
+            }
+        }
+    }
+
+    /// Tell which limit, if any, made the last call trap.
+    fn trap(&mut self, err: RuntimeError) -> Trap {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let MeteringPoints::Exhausted = get_remaining_points(&mut self.store, &self.instance)
+            {
+                return Trap::OutOfGas;
+            }
+            if let Ok(global) = self.instance.exports.get_global(CALL_DEPTH_EXCEEDED_GLOBAL) {
+                if global.get(&mut self.store) == Value::I32(1) {
+                    return Trap::CallDepthExceeded;
+                }
+            }
+            if let Ok(global) = self.instance.exports.get_global(MEMORY_GROW_FAILED_GLOBAL) {
+                if global.get(&mut self.store) == Value::I32(1) {
+                    return Trap::MemoryLimit;
+                }
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        print_frames(&err);
+        Trap::Other(err.message())
+    }
+}
+
+impl BotInstance for WasmerInstance {
+    fn has_export(&self, export: Export) -> bool {
+        match export {
+            Export::ComputeActions => self.exports.legacy.is_some(),
+            _ => {
+                self.exports.void.iter().any(|(found, _)| *found == export)
+                    || self.exports.value.iter().any(|(found, _)| *found == export)
+            }
+        }
+    }
+
+    fn call(&mut self, export: Export, arg: u32) -> Result<u32, Trap> {
+        self.begin_call();
+        let result = if let Some((_, function)) = self
+            .exports
+            .value
+            .iter()
+            .find(|(found, _)| *found == export)
+        {
+            function.call(&mut self.store, arg)
+        } else if let Some((_, function)) =
+            self.exports.void.iter().find(|(found, _)| *found == export)
+        {
+            function.call(&mut self.store, arg).map(|_| 0)
+        } else {
+            return Err(Trap::Other(format!("missing export `{}`", export.name())));
+        };
+        result.map_err(|err| self.trap(err))
+    }
+
+    fn call_legacy(&mut self, args: [f32; 9]) -> Result<u32, Trap> {
+        self.begin_call();
+        let Some(function) = &self.exports.legacy else {
+            return Err(Trap::Other("missing export `compute_actions`".to_string()));
+        };
+        let [a, b, c, d, e, f, g, h, i] = args;
+        function
+            .call(&mut self.store, a, b, c, d, e, f, g, h, i)
+            .map_err(|err| self.trap(err))
+    }
+
+    fn read_memory(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), String> {
+        let Some(memory) = &self.memory else {
+            return Err("the bot does not export its memory".to_string());
+        };
+        memory
+            .view(&self.store)
+            .read(offset as u64, bytes)
+            .map_err(|err| err.to_string())
+    }
+
+    fn write_memory(&mut self, offset: u32, bytes: &[u8]) -> Result<(), String> {
+        let Some(memory) = &self.memory else {
+            return Err("the bot does not export its memory".to_string());
+        };
+        memory
+            .view(&self.store)
+            .write(offset as u64, bytes)
+            .map_err(|err| err.to_string())
+    }
+
+    fn memory_pages(&mut self) -> Option<u32> {
+        let memory = self.memory.as_ref()?;
+        Some(memory.view(&self.store).size().0)
+    }
+
+    fn set_gas(&mut self, gas: u64) {
+        #[cfg(not(target_arch = "wasm32"))]
+        set_remaining_points(&mut self.store, &self.instance, gas);
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.points = gas;
+        }
+    }
+
+    fn gas(&mut self) -> u64 {
+        #[cfg(not(target_arch = "wasm32"))]
+        let points = match get_remaining_points(&mut self.store, &self.instance) {
+            MeteringPoints::Remaining(points) => points,
+            MeteringPoints::Exhausted => 0,
+        };
+
+        #[cfg(target_arch = "wasm32")]
+        let points = self.points;
+
+        points
+    }
+
+    fn host(&mut self) -> &mut HostState {
+        &mut self.env.as_mut(&mut self.store).host
+    }
+
+    fn host_ref(&self) -> &HostState {
+        &self.env.as_ref(&self.store).host
+    }
+
+    fn save_state(&mut self) -> Result<(Vec<u8>, Vec<(String, GlobalValue)>), String> {
+        let memory = match &self.memory {
+            Some(memory) => {
+                let view = memory.view(&self.store);
+                let mut bytes = vec![0; view.data_size() as usize];
+                view.read(0, &mut bytes).map_err(|err| err.to_string())?;
+                bytes
+            }
+            None => Vec::new(),
+        };
+
+        let mut globals = Vec::new();
+        for (name, export) in self.instance.exports.iter() {
+            let Extern::Global(global) = export else {
+                continue;
+            };
+            if global.ty(&self.store).mutability != Mutability::Var {
+                continue;
+            }
+            let value = match global.get(&mut self.store) {
+                Value::I32(value) => GlobalValue::I32(value),
+                Value::I64(value) => GlobalValue::I64(value),
+                Value::F32(value) => GlobalValue::F32(value.to_bits()),
+                Value::F64(value) => GlobalValue::F64(value.to_bits()),
+                _ => return Err(format!("global `{}` has an unsupported type", name)),
+            };
+            globals.push((name.clone(), value));
+        }
+        Ok((memory, globals))
+    }
+
+    fn load_state(
+        &mut self,
+        bytes: &[u8],
+        globals: &[(String, GlobalValue)],
+    ) -> Result<(), String> {
+        match &self.memory {
+            Some(memory) => {
+                let current_size = memory.view(&self.store).data_size() as usize;
+                if current_size < bytes.len() {
+                    let missing_pages = (bytes.len() - current_size) / WASM_PAGE_SIZE;
+                    memory
+                        .grow(&mut self.store, Pages(missing_pages as u32))
+                        .map_err(|err| err.to_string())?;
+                }
+                let view = memory.view(&self.store);
+                view.write(0, bytes).map_err(|err| err.to_string())?;
+                let extra = view.data_size() as usize - bytes.len();
+                if extra > 0 {
+                    view.write(bytes.len() as u64, &vec![0; extra])
+                        .map_err(|err| err.to_string())?;
+                }
+            }
+            None if bytes.is_empty() => {}
+            None => return Err("the bot does not export its memory".to_string()),
+        }
+
+        for (name, value) in globals.iter() {
+            let global = self
+                .instance
+                .exports
+                .get_global(name)
+                .map_err(|err| err.to_string())?;
+            let value = match value {
+                GlobalValue::I32(value) => Value::I32(*value),
+                GlobalValue::I64(value) => Value::I64(*value),
+                GlobalValue::F32(bits) => Value::F32(f32::from_bits(*bits)),
+                GlobalValue::F64(bits) => Value::F64(f64::from_bits(*bits)),
+            };
+            global
+                .set(&mut self.store, value)
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,444 @@
+//! Interpreter backend: wasmi runs the bots without generating any code, which is what the
+//! rollup needs. Fuel meters the calls, the limits are enforced by the store and the bots
+//! are rewritten to canonicalize their NaNs (see [`crate::nans`]).
+
+use wasmi::core::{Pages, TrapCode, F32, F64};
+use wasmi::errors::{MemoryError, TableError};
+use wasmi::{
+    Caller, Config, Engine, Error, Extern, Instance, Linker, Memory, Module, Mutability,
+    ResourceLimiter, StackLimits, Store, StoreLimits, StoreLimitsBuilder, TypedFunc, Val,
+};
+
+use super::{BotInstance, Export, Trap, PAGE_SIZE};
+use crate::cache::ModuleCache;
+use crate::host::HostState;
+use crate::logs::LogLevel;
+use crate::nans::canonicalize_nans;
+use crate::rules::Rules;
+use crate::snapshot::GlobalValue;
+
+/// Values the interpreter may keep on its stack, a recursion deep enough to fill it is
+/// reported like one exceeding the call depth.
+const MAX_VALUE_STACK: usize = 1024 * 1024;
+
+/// Host side state reachable from the imports.
+struct WasmiState {
+    host: HostState,
+    limits: BotLimiter,
+}
+
+/// Limits of the store, remembering whether a `memory.grow` failed so that a trap following
+/// a failed allocation is told apart from any other trap.
+struct BotLimiter {
+    limits: StoreLimits,
+    memory_grow_failed: bool,
+}
+
+impl ResourceLimiter for BotLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> Result<bool, MemoryError> {
+        let allowed = self.limits.memory_growing(current, desired, maximum);
+        if !matches!(allowed, Ok(true)) {
+            self.memory_grow_failed = true;
+        }
+        allowed
+    }
+
+    fn table_growing(
+        &mut self,
+        current: u32,
+        desired: u32,
+        maximum: Option<u32>,
+    ) -> Result<bool, TableError> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn memory_grow_failed(&mut self, _error: &MemoryError) {
+        self.memory_grow_failed = true;
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+fn memory(caller: &Caller<WasmiState>) -> Option<Memory> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+fn log_from_memory(caller: &mut Caller<WasmiState>, level: LogLevel, ptr: u32, len: u32) {
+    // never read more than what could be kept
+    let len = (len as usize).min(caller.data().host.log_budget());
+    if len == 0 {
+        return;
+    }
+    let Some(memory) = memory(caller) else {
+        return;
+    };
+    let mut bytes = vec![0; len];
+    if memory.read(&*caller, ptr as usize, &mut bytes).is_err() {
+        return;
+    }
+    caller.data_mut().host.log_bytes(level, &bytes);
+}
+
+/// Copy up to `len` bytes of the stored blob to `ptr` and return the size of the blob.
+fn storage_read(mut caller: Caller<WasmiState>, ptr: u32, len: u32) -> u32 {
+    let size = caller.data().host.storage.len();
+    let copied = size.min(len as usize);
+    if copied > 0 {
+        if let Some(memory) = memory(&caller) {
+            let (bytes, state) = memory.data_and_store_mut(&mut caller);
+            let Some(target) = bytes.get_mut(ptr as usize..ptr as usize + copied) else {
+                return 0;
+            };
+            target.copy_from_slice(&state.host.storage[..copied]);
+        }
+    }
+    size as u32
+}
+
+/// Replace the stored blob with the `len` bytes at `ptr`. Only works from `on_match_end`,
+/// returns 1 on success and 0 otherwise.
+fn storage_write(mut caller: Caller<WasmiState>, ptr: u32, len: u32) -> u32 {
+    if !caller.data().host.can_write_storage(len as usize) {
+        return 0;
+    }
+    let Some(memory) = memory(&caller) else {
+        return 0;
+    };
+    let mut bytes = vec![0; len as usize];
+    if memory.read(&caller, ptr as usize, &mut bytes).is_err() {
+        return 0;
+    }
+    caller.data_mut().host.storage = bytes;
+    1
+}
+
+type LegacyFunction = TypedFunc<(f32, f32, f32, f32, f32, f32, f32, f32, f32), u32>;
+
+/// Exports of the ABI found in the instance.
+#[derive(Default)]
+struct Exports {
+    /// Exports returning nothing.
+    void: Vec<(Export, TypedFunc<u32, ()>)>,
+    /// Exports returning a `u32`.
+    value: Vec<(Export, TypedFunc<u32, u32>)>,
+    legacy: Option<LegacyFunction>,
+}
+
+struct WasmiInstance {
+    instance: Instance,
+    store: Store<WasmiState>,
+    memory: Option<Memory>,
+    exports: Exports,
+    /// Whether the last call ran out of fuel, wasmi may leave some fuel behind when the next
+    /// instructions cost more than what is left.
+    exhausted: bool,
+}
+
+/// Compile and instantiate the bot. Modules are not cached: wasmi translates functions
+/// lazily, the first time they are called, which is cheap next to a JIT compilation.
+pub(super) fn instantiate(
+    wasm_bytes: &[u8],
+    rules: &Rules,
+    host: HostState,
+    _cache: Option<&ModuleCache>,
+) -> Result<Box<dyn BotInstance>, String> {
+    let limits = &rules.limits;
+
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    config.set_stack_limits(
+        StackLimits::new(
+            StackLimits::default().initial_value_stack_height,
+            MAX_VALUE_STACK,
+            limits.max_call_depth as usize,
+        )
+        .map_err(|err| format!("invalid stack limits: {}", err))?,
+    );
+    let engine = Engine::new(&config);
+
+    verbose!("Compiling wasm module...");
+    let wasm_bytes = canonicalize_nans(wasm_bytes)?;
+    let module = Module::new(&engine, &wasm_bytes[..])
+        .map_err(|err| format!("failed to create module: {}", err))?;
+
+    let mut store = Store::new(
+        &engine,
+        WasmiState {
+            host,
+            limits: BotLimiter {
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(limits.max_memory_pages as usize * PAGE_SIZE)
+                    .table_elements(limits.max_table_elements)
+                    .build(),
+                memory_grow_failed: false,
+            },
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+
+    let mut linker = Linker::<WasmiState>::new(&engine);
+    let wrapped = linker
+        .func_wrap(
+            "env",
+            "print_u32",
+            |mut caller: Caller<WasmiState>, num: u32| {
+                caller
+                    .data_mut()
+                    .host
+                    .push_log(LogLevel::Info, num.to_string());
+            },
+        )
+        .and_then(|linker| {
+            linker.func_wrap(
+                "env",
+                "log",
+                |mut caller: Caller<WasmiState>, ptr: u32, len: u32| {
+                    log_from_memory(&mut caller, LogLevel::Info, ptr, len);
+                },
+            )
+        })
+        .and_then(|linker| {
+            linker.func_wrap(
+                "env",
+                "log_f32",
+                |mut caller: Caller<WasmiState>, value: f32| {
+                    caller
+                        .data_mut()
+                        .host
+                        .push_log(LogLevel::Info, value.to_string());
+                },
+            )
+        })
+        .and_then(|linker| {
+            linker.func_wrap(
+                "env",
+                "log_level",
+                |mut caller: Caller<WasmiState>, level: u32, ptr: u32, len: u32| {
+                    log_from_memory(&mut caller, LogLevel::from_u32(level), ptr, len);
+                },
+            )
+        })
+        .and_then(|linker| linker.func_wrap("env", "storage_read", storage_read))
+        .and_then(|linker| linker.func_wrap("env", "storage_write", storage_write));
+    if let Err(err) = wrapped {
+        return Err(format!("failed to define imports: {}", err));
+    }
+
+    verbose!("Instantiating wasm module...");
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .map_err(|err| format!("failed to instantiate module: {}", err))?;
+
+    let memory = instance.get_memory(&store, "memory");
+
+    let mut exports = Exports::default();
+    for export in Export::ALL {
+        if export == Export::ComputeActions {
+            exports.legacy = instance.get_typed_func(&store, export.name()).ok();
+        } else if export.returns_u32() {
+            if let Ok(function) = instance.get_typed_func(&store, export.name()) {
+                exports.value.push((export, function));
+            }
+        } else if let Ok(function) = instance.get_typed_func(&store, export.name()) {
+            exports.void.push((export, function));
+        }
+    }
+
+    Ok(Box::new(WasmiInstance {
+        instance,
+        store,
+        memory,
+        exports,
+        exhausted: false,
+    }))
+}
+
+impl WasmiInstance {
+    /// Forget the failed `memory.grow` of the previous calls.
+    fn begin_call(&mut self) {
+        self.store.data_mut().limits.memory_grow_failed = false;
+    }
+
+    fn trap(&mut self, err: Error) -> Trap {
+        match err.as_trap_code() {
+            Some(TrapCode::OutOfFuel) => {
+                self.exhausted = true;
+                Trap::OutOfGas
+            }
+            Some(TrapCode::StackOverflow) => Trap::CallDepthExceeded,
+            _ if self.store.data().limits.memory_grow_failed => Trap::MemoryLimit,
+            _ => Trap::Other(err.to_string()),
+        }
+    }
+}
+
+impl BotInstance for WasmiInstance {
+    fn has_export(&self, export: Export) -> bool {
+        match export {
+            Export::ComputeActions => self.exports.legacy.is_some(),
+            _ => {
+                self.exports.void.iter().any(|(found, _)| *found == export)
+                    || self.exports.value.iter().any(|(found, _)| *found == export)
+            }
+        }
+    }
+
+    fn call(&mut self, export: Export, arg: u32) -> Result<u32, Trap> {
+        self.begin_call();
+        let result = if let Some((_, function)) = self
+            .exports
+            .value
+            .iter()
+            .find(|(found, _)| *found == export)
+        {
+            function.call(&mut self.store, arg)
+        } else if let Some((_, function)) =
+            self.exports.void.iter().find(|(found, _)| *found == export)
+        {
+            function.call(&mut self.store, arg).map(|_| 0)
+        } else {
+            return Err(Trap::Other(format!("missing export `{}`", export.name())));
+        };
+        result.map_err(|err| self.trap(err))
+    }
+
+    fn call_legacy(&mut self, args: [f32; 9]) -> Result<u32, Trap> {
+        self.begin_call();
+        let Some(function) = &self.exports.legacy else {
+            return Err(Trap::Other("missing export `compute_actions`".to_string()));
+        };
+        let [a, b, c, d, e, f, g, h, i] = args;
+        function
+            .call(&mut self.store, (a, b, c, d, e, f, g, h, i))
+            .map_err(|err| self.trap(err))
+    }
+
+    fn read_memory(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), String> {
+        let Some(memory) = &self.memory else {
+            return Err("the bot does not export its memory".to_string());
+        };
+        memory
+            .read(&self.store, offset as usize, bytes)
+            .map_err(|err| err.to_string())
+    }
+
+    fn write_memory(&mut self, offset: u32, bytes: &[u8]) -> Result<(), String> {
+        let Some(memory) = &self.memory else {
+            return Err("the bot does not export its memory".to_string());
+        };
+        memory
+            .write(&mut self.store, offset as usize, bytes)
+            .map_err(|err| err.to_string())
+    }
+
+    fn memory_pages(&mut self) -> Option<u32> {
+        let memory = self.memory.as_ref()?;
+        Some(u32::from(memory.current_pages(&self.store)))
+    }
+
+    fn set_gas(&mut self, gas: u64) {
+        self.exhausted = false;
+        if let Err(err) = self.store.set_fuel(gas) {
+            verbose!("failed to set fuel: {}", err);
+        }
+    }
+
+    fn gas(&mut self) -> u64 {
+        if self.exhausted {
+            return 0;
+        }
+        self.store.get_fuel().unwrap_or(0)
+    }
+
+    fn host(&mut self) -> &mut HostState {
+        &mut self.store.data_mut().host
+    }
+
+    fn host_ref(&self) -> &HostState {
+        &self.store.data().host
+    }
+
+    fn save_state(&mut self) -> Result<(Vec<u8>, Vec<(String, GlobalValue)>), String> {
+        let memory = match &self.memory {
+            Some(memory) => memory.data(&self.store).to_vec(),
+            None => Vec::new(),
+        };
+
+        let mut globals = Vec::new();
+        for export in self.instance.exports(&self.store) {
+            let name = export.name().to_string();
+            let Some(global) = export.into_global() else {
+                continue;
+            };
+            if global.ty(&self.store).mutability() != Mutability::Var {
+                continue;
+            }
+            let value = match global.get(&self.store) {
+                Val::I32(value) => GlobalValue::I32(value),
+                Val::I64(value) => GlobalValue::I64(value),
+                Val::F32(value) => GlobalValue::F32(value.to_bits()),
+                Val::F64(value) => GlobalValue::F64(value.to_bits()),
+                _ => return Err(format!("global `{}` has an unsupported type", name)),
+            };
+            globals.push((name, value));
+        }
+        Ok((memory, globals))
+    }
+
+    fn load_state(
+        &mut self,
+        bytes: &[u8],
+        globals: &[(String, GlobalValue)],
+    ) -> Result<(), String> {
+        match self.memory {
+            Some(memory) => {
+                let current_size = memory.data(&self.store).len();
+                if current_size < bytes.len() {
+                    let missing_pages = ((bytes.len() - current_size) / PAGE_SIZE) as u32;
+                    let missing_pages = Pages::new(missing_pages).ok_or("too many pages")?;
+                    memory
+                        .grow(&mut self.store, missing_pages)
+                        .map_err(|err| err.to_string())?;
+                }
+                let data = memory.data_mut(&mut self.store);
+                data[..bytes.len()].copy_from_slice(bytes);
+                data[bytes.len()..].fill(0);
+            }
+            None if bytes.is_empty() => {}
+            None => return Err("the bot does not export its memory".to_string()),
+        }
+
+        for (name, value) in globals.iter() {
+            let global = self
+                .instance
+                .get_global(&self.store, name)
+                .ok_or_else(|| format!("missing global `{}`", name))?;
+            let value = match value {
+                GlobalValue::I32(value) => Val::I32(*value),
+                GlobalValue::I64(value) => Val::I64(*value),
+                GlobalValue::F32(bits) => Val::F32(F32::from_bits(*bits)),
+                GlobalValue::F64(bits) => Val::F64(F64::from_bits(*bits)),
+            };
+            global
+                .set(&mut self.store, value)
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
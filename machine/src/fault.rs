@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// Why a call into a bot did not return an action. The bot then does nothing for the tick
+/// and the battle goes on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fault {
+    /// The bot used up its gas for the call.
+    OutOfGas,
+    /// The bot nested more calls than `BotLimits::max_call_depth`.
+    CallDepthExceeded,
+    /// The bot trapped after a `memory.grow` failed in the same call, most likely because an
+    /// allocation could not get past `BotLimits::max_memory_pages`.
+    MemoryLimit,
+    /// The bot wrote a float action that is not finite, it was replaced by no impulse.
+    InvalidAction,
+    /// Any other trap (unreachable, out of bounds access, division by zero, ...).
+    Trap,
+}
+
+/// Number of faults of each kind a bot ran into during a battle.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultCounts {
+    pub out_of_gas: u64,
+    pub call_depth_exceeded: u64,
+    pub memory_limit: u64,
+    pub invalid_action: u64,
+    pub trap: u64,
+    /// Tick of the first fault.
+    pub first_tick: Option<u64>,
+}
+
+impl FaultCounts {
+    pub fn record(&mut self, fault: Fault, tick: u64) {
+        match fault {
+            Fault::OutOfGas => self.out_of_gas += 1,
+            Fault::CallDepthExceeded => self.call_depth_exceeded += 1,
+            Fault::MemoryLimit => self.memory_limit += 1,
+            Fault::InvalidAction => self.invalid_action += 1,
+            Fault::Trap => self.trap += 1,
+        }
+        self.first_tick.get_or_insert(tick);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.out_of_gas
+            + self.call_depth_exceeded
+            + self.memory_limit
+            + self.invalid_action
+            + self.trap
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Gas a bot burnt in its `compute_actions` calls during a battle. A call running out of
+/// gas counts as using all the gas it was given.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasUsage {
+    pub calls: u64,
+    pub total: u64,
+    /// Most gas used by a single call.
+    pub max: u64,
+}
+
+impl GasUsage {
+    pub fn record(&mut self, used: u64) {
+        self.calls += 1;
+        self.total += used;
+        self.max = self.max.max(used);
+    }
+
+    /// Gas used per call on average, 0 before the first call.
+    pub fn mean(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.total as f64 / self.calls as f64
+        }
+    }
+}
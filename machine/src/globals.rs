@@ -0,0 +1,131 @@
+//! Snapshots can only reach the globals a bot exports, so the mutable globals it keeps to
+//! itself (a shadow stack pointer, the heap pointer of an allocator, any static a compiler
+//! put in a global) are exported before the bot is compiled.
+
+use wasmparser::{ExternalKind, Parser, Payload, TypeRef};
+
+/// Prefix of the names private globals are exported under, followed by their index.
+pub(crate) const PRIVATE_GLOBAL_PREFIX: &str = "__private_global_";
+
+const EXPORT_SECTION: u8 = 7;
+/// Kind of an export of a global.
+const GLOBAL_EXPORT: u8 = 3;
+/// Sections coming after the export section: start, element, code, data and data count.
+const AFTER_EXPORTS: [u8; 5] = [8, 9, 10, 11, 12];
+
+/// `wasm` exporting every mutable global it defines, those it did not export yet being named
+/// `__private_global_<index>`. Returned as is when there are none.
+pub(crate) fn export_private_globals(wasm: &[u8]) -> Result<Vec<u8>, String> {
+    let private = private_globals(wasm).map_err(|err| err.to_string())?;
+    if private.is_empty() {
+        return Ok(wasm.to_vec());
+    }
+
+    let mut entries = Vec::new();
+    for index in private.iter() {
+        let name = format!("{}{}", PRIVATE_GLOBAL_PREFIX, index);
+        write_leb(&mut entries, name.len() as u32);
+        entries.extend_from_slice(name.as_bytes());
+        entries.push(GLOBAL_EXPORT);
+        write_leb(&mut entries, *index);
+    }
+    let export_section = |count: u32, existing: &[u8]| {
+        let mut content = Vec::new();
+        write_leb(&mut content, count + private.len() as u32);
+        content.extend_from_slice(existing);
+        content.extend_from_slice(&entries);
+        let mut section = vec![EXPORT_SECTION];
+        write_leb(&mut section, content.len() as u32);
+        section.extend_from_slice(&content);
+        section
+    };
+
+    let header = wasm.get(..8).ok_or("truncated module")?;
+    let mut out = header.to_vec();
+    let mut exported = false;
+    let mut offset = header.len();
+    while offset < wasm.len() {
+        let id = wasm[offset];
+        let (size, size_len) = read_leb(&wasm[offset + 1..])?;
+        let start = offset + 1 + size_len;
+        let end = start + size as usize;
+        let content = wasm.get(start..end).ok_or("truncated section")?;
+        if id == EXPORT_SECTION {
+            let (count, count_len) = read_leb(content)?;
+            out.extend(export_section(count, &content[count_len..]));
+            exported = true;
+        } else {
+            if !exported && AFTER_EXPORTS.contains(&id) {
+                out.extend(export_section(0, &[]));
+                exported = true;
+            }
+            out.extend_from_slice(&wasm[offset..end]);
+        }
+        offset = end;
+    }
+    if !exported {
+        out.extend(export_section(0, &[]));
+    }
+    Ok(out)
+}
+
+/// Indices of the mutable globals defined by the module and not exported.
+fn private_globals(wasm: &[u8]) -> wasmparser::Result<Vec<u32>> {
+    let mut imported = 0;
+    let mut mutable = Vec::new();
+    let mut exported = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let TypeRef::Global(_) = import?.ty {
+                        imported += 1;
+                    }
+                }
+            }
+            Payload::GlobalSection(reader) => {
+                for (index, global) in reader.into_iter().enumerate() {
+                    if global?.ty.mutable {
+                        mutable.push(imported + index as u32);
+                    }
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    if export.kind == ExternalKind::Global {
+                        exported.push(export.index);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mutable
+        .into_iter()
+        .filter(|index| !exported.contains(index))
+        .collect())
+}
+
+pub(crate) fn read_leb(bytes: &[u8]) -> Result<(u32, usize), String> {
+    let mut value = 0u32;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err("invalid LEB128 integer".to_string())
+}
+
+pub(crate) fn write_leb(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,331 @@
+//! A reinforcement learning environment around the battle, in the style of Gym: an agent
+//! is reset into a new match, then steps it one tick at a time with its action, getting
+//! back what it observes, a shaped reward and whether the match is over.
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{Action, Observation};
+use crate::controller::Controller;
+use crate::fault::Fault;
+use crate::reference::ReferenceBot;
+use crate::rules::Rules;
+use crate::{Battle, MatchMode, MAX_TEAMS};
+
+/// Who the agent plays against.
+#[derive(Clone, Debug)]
+pub enum Opponent {
+    Reference(ReferenceBot),
+    /// A compiled bot, as raw wasm.
+    Bot(Vec<u8>),
+}
+
+/// Weights of the terms summed into the reward of a step. Only goals count by default.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardShaping {
+    /// Times 1 when the team of the agent scores, -1 when it concedes.
+    pub goal: f32,
+    /// Times how far the ball moved towards the opposing goal during the tick, summed over
+    /// the balls when there are several.
+    pub ball_progress: f32,
+    /// Times the distance between the agent and the closest ball after the tick, negative
+    /// to reward staying close.
+    pub ball_distance: f32,
+    /// Added every tick, negative to hurry.
+    pub time: f32,
+    /// Times the faults of the agent during the tick, such as a non-finite impulse.
+    pub fault: f32,
+}
+
+impl Default for RewardShaping {
+    fn default() -> RewardShaping {
+        RewardShaping {
+            goal: 1.0,
+            ball_progress: 0.0,
+            ball_distance: 0.0,
+            time: 0.0,
+            fault: 0.0,
+        }
+    }
+}
+
+/// What an [`Env`] plays.
+#[derive(Clone, Debug)]
+pub struct EnvConfig {
+    pub rules: Rules,
+    /// Team of the agent in a team match, 0 or 1.
+    pub team: u8,
+    /// All in the other team.
+    pub opponents: Vec<Opponent>,
+    pub reward: RewardShaping,
+}
+
+impl Default for EnvConfig {
+    fn default() -> EnvConfig {
+        EnvConfig {
+            rules: Rules::default(),
+            team: 0,
+            opponents: vec![Opponent::Reference(ReferenceBot::Chaser)],
+            reward: RewardShaping::default(),
+        }
+    }
+}
+
+/// Outcome of [`Env::step`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Step {
+    /// What the agent sees before its next action, laid out like the observation of the
+    /// extended ABI.
+    pub observation: Vec<f32>,
+    pub reward: f32,
+    /// The match is over, by a goal or because it ran out of ticks: reset before stepping
+    /// again.
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StepInfo {
+    pub tick: u64,
+    /// 0 until a team scores, then the scoring team + 1, like [`Battle::update`].
+    pub winner: u8,
+    /// The match ended as a draw after `Rules::max_ticks`, rather than by a goal.
+    pub truncated: bool,
+    /// Faults of the agent since the reset.
+    pub faults: u64,
+    /// With [`VecEnv`], the last observation of a match that just ended, `observation`
+    /// being the first of the next one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_observation: Option<Vec<f32>>,
+}
+
+/// The seat of the agent in the battle: plays whatever action was set last.
+struct Agent(Arc<Mutex<Action>>);
+
+impl Controller for Agent {
+    fn compute_actions(&mut self, _observation: &Observation) -> Result<Action, Fault> {
+        Ok(*self.0.lock().unwrap())
+    }
+
+    fn name(&self) -> &str {
+        "agent"
+    }
+}
+
+/// A single match at a time, played by the agent against the configured opponents.
+pub struct Env {
+    config: EnvConfig,
+    battle: Option<Battle>,
+    action: Arc<Mutex<Action>>,
+    /// Index of the agent in the battle.
+    agent: usize,
+    done: bool,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Result<Env, String> {
+        if config.team > 1 {
+            return Err(format!(
+                "team {} does not exist in a team match",
+                config.team
+            ));
+        }
+        if config.opponents.is_empty() {
+            return Err("the agent needs at least one opponent".to_string());
+        }
+        Ok(Env {
+            config,
+            battle: None,
+            action: Arc::new(Mutex::new(Action::NEUTRAL)),
+            agent: 0,
+            done: true,
+        })
+    }
+
+    /// Start a new match, `seed` being its match id (little-endian) which seeds the bots.
+    /// Returns the first observation of the agent.
+    pub fn reset(&mut self, seed: u64) -> Result<Vec<f32>, String> {
+        let mut battle = Battle::new_with_rules(MatchMode::Teams, self.config.rules.clone());
+        battle.set_match_id(&seed.to_le_bytes());
+        *self.action.lock().unwrap() = Action::NEUTRAL;
+        self.agent = battle.add_controller_to_team(Agent(self.action.clone()), self.config.team)?;
+        let other = 1 - self.config.team;
+        for opponent in &self.config.opponents {
+            match opponent {
+                Opponent::Reference(bot) => battle.add_reference_bot_to_team(bot.clone(), other),
+                Opponent::Bot(wasm) => battle.add_bot_to_team(&mut wasm.clone(), other),
+            }?;
+        }
+        battle.init()?;
+        let observation = battle.observation(self.agent);
+        self.battle = Some(battle);
+        self.done = false;
+        Ok(observation)
+    }
+
+    /// Play `action` as the agent's until it decides again, a single tick unless the rule
+    /// set has a longer decision interval, the rewards of the ticks being summed. Fails
+    /// before the first reset and once the match is over.
+    pub fn step(&mut self, action: Action) -> Result<Step, String> {
+        let Some(battle) = self.battle.as_mut().filter(|_| !self.done) else {
+            return Err("the match is over, reset the environment".to_string());
+        };
+        let reward = &self.config.reward;
+        let forward = if self.config.team == 0 { 1.0 } else { -1.0 };
+        *self.action.lock().unwrap() = action;
+
+        let mut value = 0.0;
+        let mut winner = 0;
+        let mut truncated = false;
+        let mut faults = battle.bot_faults(self.agent).total();
+        for _ in 0..self.config.rules.timing.decision_interval.max(1) {
+            let balls_before = ball_xs(battle);
+            let scores_before = scores(battle);
+            let faults_before = faults;
+            winner = battle.update();
+
+            let bot = battle.get_bot(self.agent);
+            faults = battle.bot_faults(self.agent).total();
+            // every goal counts, not only the one winning the match
+            let goal = scores(battle)
+                .iter()
+                .zip(scores_before)
+                .enumerate()
+                .map(|(team, (score, before))| {
+                    let goals = (score - before) as f32;
+                    if team as u8 == self.config.team {
+                        goals
+                    } else {
+                        -goals
+                    }
+                })
+                .sum::<f32>();
+            let distance = (0..battle.num_balls())
+                .map(|index| {
+                    let ball = battle.get_ball_at(index);
+                    ((bot.x - ball.x).powi(2) + (bot.z - ball.z).powi(2)).sqrt()
+                })
+                .fold(f32::INFINITY, f32::min);
+            let progress = ball_xs(battle)
+                .iter()
+                .zip(balls_before)
+                .map(|(x, before)| (x - before) * forward)
+                .sum::<f32>();
+            value += reward.goal * goal
+                + reward.ball_progress * progress
+                + reward.ball_distance * distance
+                + reward.time
+                + reward.fault * (faults - faults_before) as f32;
+
+            truncated = winner == 0 && battle.get_tick() > battle.get_max_ticks();
+            if winner != 0 || truncated {
+                break;
+            }
+        }
+
+        self.done = winner != 0 || truncated;
+        if self.done {
+            battle.end(winner);
+        }
+        Ok(Step {
+            observation: battle.observation(self.agent),
+            reward: value,
+            done: self.done,
+            info: StepInfo {
+                tick: battle.get_tick(),
+                winner,
+                truncated,
+                faults,
+                final_observation: None,
+            },
+        })
+    }
+
+    /// The match being played, `None` before the first reset.
+    pub fn battle(&self) -> Option<&Battle> {
+        self.battle.as_ref()
+    }
+}
+
+fn scores(battle: &Battle) -> Vec<u32> {
+    (0..MAX_TEAMS as u8)
+        .map(|team| battle.get_team_score(team))
+        .collect()
+}
+
+fn ball_xs(battle: &Battle) -> Vec<f32> {
+    (0..battle.num_balls())
+        .map(|index| battle.get_ball_at(index).x)
+        .collect()
+}
+
+/// Several environments stepped together, each reset into its next match as soon as one
+/// ends. Environment `i` plays the seeds `seed + i`, `seed + i + n`, ... for `n`
+/// environments.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    seeds: Vec<u64>,
+}
+
+impl VecEnv {
+    pub fn new(config: EnvConfig, num_envs: usize) -> Result<VecEnv, String> {
+        if num_envs == 0 {
+            return Err("at least one environment is needed".to_string());
+        }
+        let envs = (0..num_envs)
+            .map(|_| Env::new(config.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(VecEnv {
+            envs,
+            seeds: vec![0; num_envs],
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Reset every environment, returning their first observations.
+    pub fn reset(&mut self, seed: u64) -> Result<Vec<Vec<f32>>, String> {
+        let mut observations = Vec::with_capacity(self.envs.len());
+        for (i, env) in self.envs.iter_mut().enumerate() {
+            self.seeds[i] = seed.wrapping_add(i as u64);
+            observations.push(env.reset(self.seeds[i])?);
+        }
+        Ok(observations)
+    }
+
+    /// Step every environment with its action, one per environment.
+    pub fn step(&mut self, actions: &[Action]) -> Result<Vec<Step>, String> {
+        if actions.len() != self.envs.len() {
+            return Err(format!(
+                "{} actions for {} environments",
+                actions.len(),
+                self.envs.len()
+            ));
+        }
+        let num_envs = self.envs.len() as u64;
+        let mut steps = Vec::with_capacity(self.envs.len());
+        for (i, (env, action)) in self.envs.iter_mut().zip(actions).enumerate() {
+            let mut step = env.step(*action)?;
+            if step.done {
+                self.seeds[i] = self.seeds[i].wrapping_add(num_envs);
+                let observation = env.reset(self.seeds[i])?;
+                step.info.final_observation =
+                    Some(std::mem::replace(&mut step.observation, observation));
+            }
+            steps.push(step);
+        }
+        Ok(steps)
+    }
+
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+}
@@ -0,0 +1,72 @@
+//! State behind the functions the machine provides to bots, shared by the engines: each one
+//! only moves bytes between the bot memory and [`HostState`].
+
+use crate::logs::LogLevel;
+use crate::rules::{LogLimits, Rules};
+
+/// Functions a bot can import from `env`, see `bot_validator::DEFAULT_ALLOWED_IMPORTS`.
+pub(crate) struct HostState {
+    limits: Option<LogLimits>,
+    pub logs: Vec<(LogLevel, String)>,
+    /// Bytes logged during the current tick.
+    pub tick_bytes: usize,
+    /// Bytes logged since the bot was created.
+    pub match_bytes: usize,
+    /// Blob kept across matches, read only until the match ends.
+    pub storage: Vec<u8>,
+    pub storage_writable: bool,
+    max_storage_bytes: usize,
+}
+
+impl HostState {
+    pub fn new(rules: &Rules) -> Self {
+        HostState {
+            limits: rules.logs,
+            logs: Vec::new(),
+            tick_bytes: 0,
+            match_bytes: 0,
+            storage: Vec::new(),
+            storage_writable: false,
+            max_storage_bytes: rules.storage.map_or(0, |storage| storage.max_bytes),
+        }
+    }
+
+    /// How many more bytes the bot may log right now, engines never read more than this
+    /// from the bot memory.
+    pub fn log_budget(&self) -> usize {
+        match &self.limits {
+            Some(limits) => limits
+                .max_bytes_per_tick
+                .saturating_sub(self.tick_bytes)
+                .min(limits.max_bytes_per_match.saturating_sub(self.match_bytes)),
+            None => 0,
+        }
+    }
+
+    pub fn push_log(&mut self, level: LogLevel, mut message: String) {
+        let budget = self.log_budget();
+        if budget == 0 {
+            return;
+        }
+        if message.len() > budget {
+            let mut end = budget;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+        }
+        self.tick_bytes += message.len();
+        self.match_bytes += message.len();
+        self.logs.push((level, message));
+    }
+
+    /// `log` and `log_level`, with the bytes read from the bot memory.
+    pub fn log_bytes(&mut self, level: LogLevel, bytes: &[u8]) {
+        self.push_log(level, String::from_utf8_lossy(bytes).into_owned());
+    }
+
+    /// Whether `storage_write` may store `len` bytes now.
+    pub fn can_write_storage(&self, len: usize) -> bool {
+        self.storage_writable && len <= self.max_storage_bytes
+    }
+}
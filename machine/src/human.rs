@@ -0,0 +1,59 @@
+//! A bot played by a person: the page reads the keyboard or a gamepad and sets the action
+//! of a [`HumanController`] before every update.
+
+use std::sync::{Arc, Mutex};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::bot::{Action, Observation};
+use crate::controller::Controller;
+use crate::fault::Fault;
+
+/// Holds the action of a human player. Clones share it: the page keeps one to set the
+/// action, [`Battle::add_human`](crate::Battle::add_human) hands another to the battle.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone)]
+pub struct HumanController {
+    action: Arc<Mutex<Action>>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl HumanController {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> HumanController {
+        HumanController {
+            action: Arc::new(Mutex::new(Action::NEUTRAL)),
+        }
+    }
+
+    /// What the bot does from the next update on, until set again. The impulse is clamped
+    /// to unit length and scaled like the impulse of any bot.
+    pub fn set_action(&self, x: f32, y: f32, z: f32, boost: bool) {
+        *self.action.lock().unwrap() = Action {
+            impulse: [x, y, z],
+            boost,
+        };
+    }
+
+    /// Stop pushing, for example when the page loses the focus.
+    pub fn release(&self) {
+        *self.action.lock().unwrap() = Action::NEUTRAL;
+    }
+}
+
+impl Default for HumanController {
+    fn default() -> HumanController {
+        HumanController::new()
+    }
+}
+
+impl Controller for HumanController {
+    fn compute_actions(&mut self, _observation: &Observation) -> Result<Action, Fault> {
+        Ok(*self.action.lock().unwrap())
+    }
+
+    fn name(&self) -> &str {
+        "human"
+    }
+}
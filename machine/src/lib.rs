@@ -1,64 +1,128 @@
-use std::cell::RefCell;
-use std::sync::atomic::AtomicI32;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::sync::RwLock;
 
 use nalgebra::ArrayStorage;
 use nalgebra::Const;
 use nalgebra::Matrix;
 use nalgebra::Vector3;
-use rapier3d::crossbeam;
+use sha2::{Digest, Sha256};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::wasm_bindgen;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::JsValue;
 
+/// See [`set_verbose`].
+static VERBOSE: AtomicBool = AtomicBool::new(true);
+
+/// `println!`, unless the machine was told to be quiet with [`set_verbose`].
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if crate::VERBOSE.load(std::sync::atomic::Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
 #[cfg(target_arch = "wasm32")]
 mod utils;
 #[cfg(target_arch = "wasm32")]
 use utils::set_panic_hook;
 
-use wasmer::RuntimeError;
+mod bot;
+// the interpreter compiles nothing to cache
+#[cfg_attr(feature = "interpreter", allow(dead_code))]
+mod cache;
+pub use cache::ModuleCache;
+mod context;
+pub use context::MatchContext;
+mod controller;
+pub use controller::Controller;
+use controller::Driver;
+mod engine;
 
-#[cfg(target_arch = "wasm32")]
-#[derive(Debug, Eq, PartialEq)]
-pub enum MeteringPoints {
-    /// The given number of metering points is left for the execution.
-    /// If the value is 0, all points are consumed but the execution
-    /// was not terminated.
-    Remaining(u64),
-
-    /// The execution was terminated because the metering points were
-    /// exhausted.  You can recover from this state by setting the
-    /// points via [`set_remaining_points`] and restart the execution.
-    Exhausted,
-}
+use bot::{create_bot_module, create_reference_module};
+pub use bot::{validator_limits, Action, Observation, ENEMY_OUT_OF_VIEW};
+
+mod fault;
+pub use fault::{Fault, FaultCounts};
+
+mod gas;
+pub use gas::GasUsage;
+
+mod globals;
+
+mod gym;
+pub use gym::{Env, EnvConfig, Opponent, RewardShaping, Step, StepInfo, VecEnv};
+
+mod host;
+
+mod human;
+pub use human::HumanController;
+
+#[cfg(all(
+    feature = "wasmer",
+    not(feature = "interpreter"),
+    not(target_arch = "wasm32")
+))]
+mod limits;
+
+mod logs;
+pub use logs::{BotLog, LogLevel};
+
+#[cfg(feature = "interpreter")]
+mod nans;
+
+mod perception;
+use perception::Sight;
+
+mod reference;
+pub use reference::{ReferenceBot, ScriptedMove};
+
+mod rng;
+
+mod rules;
+pub use rules::{
+    BallBody, BotBody, BotLimits, Energy, GasBank, LogLimits, Perception, Rules, SpawnLayout,
+    StorageLimits, Timing,
+};
+
+mod spawn;
+pub use spawn::SpawnError;
+
+mod series;
+pub use series::{play_series, Leg, SeriesResult};
+
+mod snapshot;
+use snapshot::{BotSnapshot, Snapshot, SNAPSHOT_VERSION};
 
 #[derive(Copy, Clone, PartialEq)]
 enum ObjectType {
     Any,
     Ball,
-    Bot1Goal,
-    Bot2Goal,
-    Bot1,
-    Bot2,
+    Goal,
+    Bot,
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-use {
-    std::sync::Arc,
-    wasmer::sys::EngineBuilder,
-    wasmer::wasmparser::Operator,
-    wasmer::CompilerConfig,
-    wasmer_compiler_cranelift::Cranelift,
-    wasmer_middlewares::{
-        metering::{get_remaining_points, set_remaining_points, MeteringPoints},
-        Metering,
-    },
-};
+impl ObjectType {
+    /// Collider user data: the object type in the low byte and, above it, the team for goals,
+    /// the bot index for bots or the ball index for balls.
+    fn user_data(self, index: usize) -> u128 {
+        ((index as u128) << 8) | self as u128
+    }
 
-use wasmer::Function;
-use wasmer::{imports, FunctionEnv, FunctionEnvMut, Instance, Module, Store, TypedFunction};
+    fn from_user_data(user_data: u128) -> (ObjectType, usize) {
+        let object_type = match user_data & 0xFF {
+            1 => ObjectType::Ball,
+            2 => ObjectType::Goal,
+            3 => ObjectType::Bot,
+            _ => ObjectType::Any,
+        };
+        (object_type, (user_data >> 8) as usize)
+    }
+}
 
 use rapier3d::prelude::*;
 
@@ -69,276 +133,80 @@ pub fn start() -> Result<(), JsValue> {
     Ok(())
 }
 
-// Function to reconstruct vector from u32
-fn reconstruct_action(action: u32) -> [f32; 3] {
-    // First, reconstruct [u8; 3] from u32
-    let action_u8 = [
-        ((action >> 16) & 0xFF) as u8,
-        ((action >> 8) & 0xFF) as u8,
-        (action & 0xFF) as u8,
-    ];
-
-    // Then, convert [u8; 3] back to [f32; 3]
-    [
-        (action_u8[0] as f32 - 128.0) / 127.0,
-        (action_u8[1] as f32 - 128.0) / 127.0,
-        (action_u8[2] as f32 - 128.0) / 127.0,
-    ]
+/// Whether the machine narrates what it does (compiling, calling bots, traps, goals) on
+/// stdout, which it does by default. This applies to every battle of the process.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
 }
 
+/// Identity of a bot: the hex encoded SHA-256 of its (uncompressed) wasm module.
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
-#[derive(Copy, Clone, PartialEq)]
+pub fn bot_hash(wasm_bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(wasm_bytes).into())
+}
+
+fn to_hex(hash: &[u8; 32]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
     pub z: f32,
 }
 
-struct BotModule {
-    #[allow(dead_code)]
-    instance: Instance,
-    store: Store,
-    init: TypedFunction<u32, ()>,
-    compute_actions: TypedFunction<(f32, f32, f32, f32, f32, f32, f32, f32, f32), u32>,
+/// How bots are grouped into teams.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MatchMode {
+    /// Two teams facing each other along the x axis (1v1, 2v2, 3v3, ...).
+    Teams,
+    /// Every bot is its own team and defends its own goal, one per wall.
+    FreeForAll,
+}
+
+struct Bot {
+    driver: Driver,
+    /// See [`bot_hash`].
+    hash: [u8; 32],
     handle: RigidBodyHandle,
+    team: u8,
+    energy: f32,
+    /// Gas saved for later ticks, only used with a gas bank.
+    gas_reserve: u64,
+    faults: FaultCounts,
+    gas: GasUsage,
+    /// Action applied on every tick until the next decision takes effect (see [`Timing`]).
+    action: Action,
+    /// Decisions waiting for the tick they take effect (see [`Perception::action_delay`]).
+    pending: VecDeque<(u64, Action)>,
+    /// Draws the noise of the observations, seeded from the match.
+    noise: u64,
 }
 
 const INIT_GAS: u64 = 100;
+const MATCH_END_GAS: u64 = 10_000;
 const COMPUTE_ACTIONS_GAS: u64 = 1_000;
-const MAX_NUM_UPDATES: u64 = 1_000_000;
-
-fn create_bot_module(wasm_bytes: &mut [u8], handle: RigidBodyHandle) -> BotModule {
-    #[cfg(not(target_arch = "wasm32"))]
-    let mut store = {
-        // Let's define our cost function.
-        // This function will be called for each `Operator` encountered during
-        // the Wasm module execution. It should return the cost of the operator
-        // that it received as it first argument.
-        let cost_function = |operator: &Operator| -> u64 {
-            1
-            // match operator {
-            //     _ => 100,
-            // }
-        };
-
-        // Now let's create our metering middleware.
-        //
-        // `Metering` needs to be configured with a limit and a cost function.
-        //
-        // For each `Operator`, the metering middleware will call the cost
-        // function and subtract the cost from the remaining points.
-        let initial_points = 100;
-        let metering = Arc::new(Metering::new(initial_points, cost_function));
-        let mut compiler_config = Cranelift::default();
-        compiler_config.push_middleware(metering);
-
-        // Create a Store.
-        //
-        // We use our previously create compiler configuration
-        // with the Universal engine.
-        Store::new(EngineBuilder::new(compiler_config))
-    };
 
-    #[cfg(target_arch = "wasm32")]
-    let mut store = { Store::default() };
-
-    println!("Compiling wasm module...");
-    // Let's compile the Wasm module.
-    let module = Module::new(&store, wasm_bytes).expect("failed to create module");
-
-    // Let's define the import object used to import our function
-    // into our webassembly sample application.
-
-    struct MyEnv;
-    let env = FunctionEnv::new(&mut store, MyEnv {});
-
-    fn print_u32(_env: FunctionEnvMut<MyEnv>, num: u32) {
-        // Print it!
-        println!("num: {}", num);
-    }
-    let print_u32 = Function::new_typed_with_env(&mut store, &env, print_u32);
-
-    // this is how you import values:
-    // let value = Global::new(&mut store, Value::I32(value)); // there is no u32 but here we can pass as if
-
-    let import_object = imports! {
-        "env" => {
-            "print_u32" => print_u32,
-        },
-    };
-
-    println!("Instantiating wasm module...");
-    // Let's instantiate the Wasm module.
-    let instance =
-        Instance::new(&mut store, &module, &import_object).expect("failed to instantiate module");
-
-    // We now have an instance ready to be used.
-    //
-    // Our module exports a single `add_one`  function. We want to
-    // measure the cost of executing this function.
-    let init: TypedFunction<u32, ()> = instance
-        .exports
-        .get_function("init")
-        .expect("failed to get function 'init'")
-        .typed(&mut store)
-        .expect("failed to get typed version of the function 'init'");
-
-    let compute_actions: TypedFunction<(f32, f32, f32, f32, f32, f32, f32, f32, f32), u32> =
-        instance
-            .exports
-            .get_function("compute_actions")
-            .expect("failed to get function 'compute_actions'")
-            .typed(&mut store)
-            .expect("failed to get typed version of the function 'compute_actions'");
-
-    BotModule {
-        instance,
-        store,
-        compute_actions,
-        init,
-        handle,
-    }
-}
-
-fn on_runtime_error(e: RuntimeError) {
-    #[cfg(target_arch = "wasm32")]
-    {
-        panic!("RuntimeError: {:?}", e);
-    }
+/// Goals sit on the -x, +x, -z and +z walls, so there can be at most 4 teams.
+const MAX_TEAMS: usize = 4;
 
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        println!("Error message: {}", e.message());
-        println!("Error: {}", e);
-
-        let frames = e.trace();
-        let frames_len = frames.len();
-
-        for i in 0..frames_len {
-            println!(
-                "  Frame #{}: {:?}::{:?}",
-                frames_len - i,
-                frames[i].module_name(),
-                frames[i].function_name().or(Some("<func>")).unwrap()
-            );
-        }
-    }
-}
-
-impl BotModule {
-    fn init(&mut self, seed: u32) -> () {
-        match self.init.call(&mut self.store, seed) {
-            Ok(_) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let remaining_points_after_call =
-                        get_remaining_points(&mut self.store, &self.instance);
-                    // println!(
-                    //     "Calling `init` succeeded. points left: {:?}",
-                    //     remaining_points_after_call
-                    // );
-                }
-            }
-            Err(_) => {
-                println!("Calling `init` failed.");
-
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    // Because the last needed more than the remaining points, we should have an error.
-                    let remaining_points = get_remaining_points(&mut self.store, &self.instance);
-
-                    match remaining_points {
-                        MeteringPoints::Remaining(..) => {
-                            panic!("No metering error: there are remaining points");
-                        }
-                        MeteringPoints::Exhausted => println!("Not enough points remaining"),
-                    }
-                }
-            }
-        }
-    }
-
-    fn compute_actions(
-        &mut self,
-        self_x: f32,
-        self_y: f32,
-        self_z: f32,
-        ball_x: f32,
-        ball_y: f32,
-        ball_z: f32,
-        enemy_x: f32,
-        enemy_y: f32,
-        enemy_z: f32,
-    ) -> u32 {
-        match self.compute_actions.call(
-            &mut self.store,
-            self_x,
-            self_y,
-            self_z,
-            ball_x,
-            ball_y,
-            ball_z,
-            enemy_x,
-            enemy_y,
-            enemy_z,
-        ) {
-            Ok(res) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let remaining_points_after_call =
-                        get_remaining_points(&mut self.store, &self.instance);
-                    // println!(
-                    //     "Calling `update` succeeded. points left: {:?}",
-                    //     remaining_points_after_call
-                    // );
-                }
-                res
-            }
-            Err(err) => {
-                println!("Calling `update` failed.");
-
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    // Because the last needed more than the remaining points, we should have an error.
-                    let remaining_points = get_remaining_points(&mut self.store, &self.instance);
-
-                    match remaining_points {
-                        MeteringPoints::Remaining(..) => {
-                            on_runtime_error(err);
-                        }
-                        MeteringPoints::Exhausted => println!("Not enough points remaining"),
-                    }
-                }
-                #[cfg(target_arch = "wasm32")]
-                {
-                    on_runtime_error(err);
-                }
-                8421504
-            }
-        }
-    }
-
-    fn get_remaining_points(&mut self) -> MeteringPoints {
-        #[cfg(not(target_arch = "wasm32"))]
-        let points = get_remaining_points(&mut self.store, &self.instance);
-
-        #[cfg(target_arch = "wasm32")]
-        let points = MeteringPoints::Remaining(0);
-
-        points
-    }
-
-    #[allow(unused_variables)]
-    fn set_remaining_points(&mut self, points: u64) {
-        #[cfg(not(target_arch = "wasm32"))]
-        set_remaining_points(&mut self.store, &self.instance, points)
-    }
-}
+/// Height of the top of the ground.
+const GROUND_LEVEL: f32 = 0.1;
+/// Gap left between a freshly spawned bot and the ground.
+const SPAWN_CLEARANCE: f32 = 0.05;
+/// Distance from the centre of the arena to the inner side of its walls.
+pub const ARENA_HALF_SIZE: f32 = 9.9;
 
 // Define a struct to hold our custom event handler
 struct CustomEventHandler {
-    // pub winner: AtomicI32,
-    pub winner: RwLock<u8>,
+    /// Balls that hit a goal during the step, with the team defending it.
+    goals: RwLock<Vec<(usize, u8)>>,
+    /// Balls touched by a bot during the step, with the bot.
+    touches: RwLock<Vec<(usize, usize)>>,
 }
 
 impl EventHandler for CustomEventHandler {
@@ -354,49 +222,30 @@ impl EventHandler for CustomEventHandler {
             CollisionEvent::Started(h1, h2, _) => {
                 if let Some(collider1) = colliders.get(h1) {
                     if let Some(collider2) = colliders.get(h2) {
-                        let user_data1 = collider1.user_data;
-                        let user_data2 = collider2.user_data;
-
-                        if user_data1 == ObjectType::Ball as u128
-                            && user_data2 == ObjectType::Bot1 as u128
-                        {
-                            println!("Ball <-> Bot1");
-
-                            // Add your logic for Bot1's goal here
-                        } else if user_data2 == ObjectType::Ball as u128
-                            && user_data1 == ObjectType::Bot1 as u128
-                        {
-                            println!("Ball <-> Bot1");
-                        } else if user_data1 == ObjectType::Ball as u128
-                            && user_data2 == ObjectType::Bot2Goal as u128
-                        {
-                            println!("Ball <-> Bot2's goal!");
-                            // self.winner.store(1, Ordering::SeqCst);
-                            if let Ok(mut winner) = self.winner.write() {
-                                *winner = 1;
-                            }
-                        } else if user_data2 == ObjectType::Ball as u128
-                            && user_data1 == ObjectType::Bot2Goal as u128
-                        {
-                            println!("Ball <-> Bot2's goal!");
-                            if let Ok(mut winner) = self.winner.write() {
-                                *winner = 1;
+                        let object1 = ObjectType::from_user_data(collider1.user_data);
+                        let object2 = ObjectType::from_user_data(collider2.user_data);
+
+                        let (ball, other) = if object1.0 == ObjectType::Ball {
+                            (object1.1, object2)
+                        } else if object2.0 == ObjectType::Ball {
+                            (object2.1, object1)
+                        } else {
+                            return;
+                        };
+
+                        match other {
+                            (ObjectType::Bot, index) => {
+                                if let Ok(mut touches) = self.touches.write() {
+                                    touches.push((ball, index));
+                                }
                             }
-                        } else if user_data1 == ObjectType::Ball as u128
-                            && user_data2 == ObjectType::Bot1Goal as u128
-                        {
-                            println!("Ball <-> Bot1's goal!");
-                            // self.winner.store(1, Ordering::SeqCst);
-                            if let Ok(mut winner) = self.winner.write() {
-                                *winner = 2;
-                            }
-                        } else if user_data2 == ObjectType::Ball as u128
-                            && user_data1 == ObjectType::Bot1Goal as u128
-                        {
-                            println!("Ball <-> Bot1's goal!");
-                            if let Ok(mut winner) = self.winner.write() {
-                                *winner = 2;
+                            (ObjectType::Goal, team) => {
+                                verbose!("Ball {} <-> team {}'s goal!", ball, team + 1);
+                                if let Ok(mut goals) = self.goals.write() {
+                                    goals.push((ball, team as u8));
+                                }
                             }
+                            _ => {}
                         }
                     }
                 }
@@ -409,20 +258,46 @@ impl EventHandler for CustomEventHandler {
 
     fn handle_contact_force_event(
         &self,
-        dt: f32,
-        bodies: &RigidBodySet,
-        colliders: &ColliderSet,
-        contact_pair: &ContactPair,
-        total_force_magnitude: f32,
+        _dt: f32,
+        _bodies: &RigidBodySet,
+        _colliders: &ColliderSet,
+        _contact_pair: &ContactPair,
+        _total_force_magnitude: f32,
     ) {
-        println!("contact_force_event")
+        verbose!("contact_force_event")
+    }
+}
+
+fn read_events<T: Clone>(lock: &RwLock<Vec<T>>) -> Vec<T> {
+    match lock.read() {
+        Ok(guard) => guard.clone(),
+        Err(_) => {
+            // Handle potential poisoned lock
+            verbose!("Warning: RwLock was poisoned. Ignoring events.");
+            Vec::new()
+        }
     }
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub struct Battle {
-    bot1: Option<BotModule>,
-    bot2: Option<BotModule>,
+    mode: MatchMode,
+    rules: Rules,
+    bots: Vec<Bot>,
+    /// Number of updates since `init`.
+    tick: u64,
+    /// Index of the last bot that touched each ball.
+    last_touch: Vec<Option<usize>>,
+    /// Goals scored by each team so far.
+    scores: [u32; MAX_TEAMS],
+    /// Draws where the balls are dropped, seeded from the match.
+    drop_rng: u64,
+    /// Where compiled bots are looked up before compiling them, see [`ModuleCache`].
+    module_cache: Option<Arc<ModuleCache>>,
+    /// See [`Battle::set_match_id`], all zeros until set.
+    match_id: [u8; 32],
+    /// Messages logged by the bots, until drained.
+    logs: Vec<BotLog>,
     collider_set: ColliderSet,
     physics_pipeline: PhysicsPipeline,
     rigid_body_set: RigidBodySet,
@@ -436,14 +311,893 @@ pub struct Battle {
     ccd_solver: CCDSolver,
     query_pipeline: QueryPipeline,
     physics_hooks: (),
-    event_handler: (),
-    ball: RigidBodyHandle,
+    balls: Vec<RigidBodyHandle>,
+}
+
+impl Default for Battle {
+    fn default() -> Battle {
+        Battle::new()
+    }
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 impl Battle {
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
     pub fn new() -> Battle {
+        Battle::new_with_mode(MatchMode::Teams)
+    }
+
+    pub fn new_with_mode(mode: MatchMode) -> Battle {
+        Battle::new_with_rules(mode, Rules::default())
+    }
+
+    fn create_bot_handle(&mut self, position: Position, bot_index: usize) -> RigidBodyHandle {
+        let body = self.rules.bot;
+        let rigid_body = RigidBodyBuilder::dynamic()
+            .translation(vector![position.x, position.y, position.z])
+            .linear_damping(body.linear_damping)
+            .angular_damping(body.angular_damping)
+            .build();
+        let mut collider = ColliderBuilder::ball(body.radius)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
+            .friction(body.friction)
+            .restitution(body.restitution)
+            .user_data(ObjectType::Bot.user_data(bot_index));
+        if let Some(mass) = body.mass {
+            collider = collider.mass(mass);
+        }
+        let handle = self.rigid_body_set.insert(rigid_body);
+        self.collider_set
+            .insert_with_parent(collider.build(), handle, &mut self.rigid_body_set);
+        handle
+    }
+    fn body_position(&self, handle: RigidBodyHandle) -> Position {
+        let body = &self.rigid_body_set[handle];
+        Position {
+            x: body.translation().x,
+            y: body.translation().y,
+            z: body.translation().z,
+        }
+    }
+
+    pub fn num_bots(&self) -> usize {
+        self.bots.len()
+    }
+
+    pub fn get_bot(&self, index: usize) -> Position {
+        self.body_position(self.bots[index].handle)
+    }
+
+    pub fn get_bot_team(&self, index: usize) -> u8 {
+        self.bots[index].team
+    }
+
+    pub fn get_bot_energy(&self, index: usize) -> f32 {
+        self.bots[index].energy
+    }
+
+    pub fn get_bot_hash(&self, index: usize) -> String {
+        to_hex(&self.bots[index].hash)
+    }
+
+    /// Identify the match to the bots, the id they get is the SHA-256 of `id`. Its first 4
+    /// bytes are also the seed given to `init`.
+    pub fn set_match_id(&mut self, id: &[u8]) {
+        self.match_id = Sha256::digest(id).into();
+    }
+
+    pub fn get_max_ticks(&self) -> u64 {
+        self.rules.max_ticks
+    }
+
+    /// Seconds of simulated time per tick, to play the battle in real time.
+    pub fn get_tick_duration(&self) -> f32 {
+        self.rules.timing.dt
+    }
+
+    /// Give the bot the blob it stored at the end of its previous match, before `init`.
+    pub fn set_bot_storage(&mut self, index: usize, storage: Vec<u8>) -> Result<(), String> {
+        let max_bytes = self.rules.storage.map_or(0, |storage| storage.max_bytes);
+        if storage.len() > max_bytes {
+            return Err(format!(
+                "storage of {} bytes is over the limit of {} bytes",
+                storage.len(),
+                max_bytes
+            ));
+        }
+        self.bots[index].driver.set_storage(storage)
+    }
+
+    /// The blob of the bot, as updated by `on_match_end` once the battle is over.
+    pub fn get_bot_storage(&self, index: usize) -> Vec<u8> {
+        self.bots[index].driver.storage().to_vec()
+    }
+
+    pub fn get_bot_gas_reserve(&self, index: usize) -> u64 {
+        self.bots[index].gas_reserve
+    }
+
+    /// Take the messages logged by the bots since the last call, formatted for display.
+    pub fn drain_logs(&mut self) -> Vec<String> {
+        self.logs.drain(..).map(|log| log.to_string()).collect()
+    }
+
+    /// Number of calls into the bot that faulted so far.
+    pub fn get_bot_faults(&self, index: usize) -> u64 {
+        self.bots[index].faults.total()
+    }
+
+    /// The first ball, the only one unless the rule set has several.
+    pub fn get_ball(&self) -> Position {
+        self.get_ball_at(0)
+    }
+
+    pub fn get_ball_at(&self, index: usize) -> Position {
+        self.body_position(self.balls[index])
+    }
+
+    pub fn num_balls(&self) -> usize {
+        self.balls.len()
+    }
+
+    /// Goals scored by `team` (0-based) so far.
+    pub fn get_team_score(&self, team: u8) -> u32 {
+        self.scores.get(team as usize).copied().unwrap_or(0)
+    }
+
+    pub fn get_tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Hex encoded SHA-256 of the tick, of where the bots and the balls are and how they move,
+    /// of what each bot has left (energy, gas reserve, faults) and of the score. Replaying a match with the
+    /// same bots and match id must end on the same hash.
+    pub fn get_state_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.tick.to_le_bytes());
+        let handles = self
+            .bots
+            .iter()
+            .map(|bot| bot.handle)
+            .chain(self.balls.iter().copied());
+        for handle in handles {
+            let body = &self.rigid_body_set[handle];
+            let rotation = body.rotation().coords;
+            for value in body
+                .translation()
+                .iter()
+                .chain(rotation.iter())
+                .chain(body.linvel().iter())
+                .chain(body.angvel().iter())
+            {
+                hasher.update(value.to_bits().to_le_bytes());
+            }
+        }
+        for bot in self.bots.iter() {
+            hasher.update(bot.energy.to_bits().to_le_bytes());
+            hasher.update(bot.gas_reserve.to_le_bytes());
+            hasher.update(bot.faults.total().to_le_bytes());
+        }
+        for score in self.scores {
+            hasher.update(score.to_le_bytes());
+        }
+        to_hex(&hasher.finalize().into())
+    }
+
+    /// Serialize the physics world and the state of every bot instance, so the battle can be
+    /// resumed later from this exact point with [`Battle::restore`].
+    pub fn snapshot(&mut self) -> Result<Vec<u8>, String> {
+        let mut bots = Vec::with_capacity(self.bots.len());
+        for bot in self.bots.iter_mut() {
+            bots.push(BotSnapshot {
+                module: bot.driver.save_state()?,
+                team: bot.team,
+                energy: bot.energy,
+                gas_reserve: bot.gas_reserve,
+                faults: bot.faults.clone(),
+                gas: bot.gas.clone(),
+                action: bot.action,
+                pending: bot.pending.iter().copied().collect(),
+                noise: bot.noise,
+            });
+        }
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            tick: self.tick,
+            last_touch: self.last_touch.clone(),
+            scores: self.scores,
+            drop_rng: self.drop_rng,
+            bots,
+            rigid_body_set: self.rigid_body_set.clone(),
+            collider_set: self.collider_set.clone(),
+            integration_parameters: self.integration_parameters,
+            island_manager: self.island_manager.clone(),
+            broad_phase: self.broad_phase.clone(),
+            narrow_phase: self.narrow_phase.clone(),
+            impulse_joint_set: self.impulse_joint_set.clone(),
+            multibody_joint_set: self.multibody_joint_set.clone(),
+            ccd_solver: self.ccd_solver.clone(),
+        };
+        bincode::serialize(&snapshot).map_err(|err| err.to_string())
+    }
+
+    /// Restore a snapshot. The battle must have been given the same roster, in the same order,
+    /// as the one the snapshot was taken from.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), String> {
+        let snapshot: Snapshot = bincode::deserialize(snapshot).map_err(|err| err.to_string())?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot version {} is not supported (expected {})",
+                snapshot.version, SNAPSHOT_VERSION
+            ));
+        }
+        if snapshot.bots.len() != self.bots.len()
+            || snapshot
+                .bots
+                .iter()
+                .zip(self.bots.iter())
+                .any(|(saved, bot)| saved.team != bot.team)
+        {
+            return Err("the snapshot was taken with a different roster".to_string());
+        }
+        if snapshot.last_touch.len() != self.balls.len() {
+            return Err(format!(
+                "the snapshot was taken with {} balls, not {}",
+                snapshot.last_touch.len(),
+                self.balls.len()
+            ));
+        }
+
+        // all or nothing: if a bot fails, it and those restored before it are put back
+        let mut current = Vec::with_capacity(self.bots.len());
+        for bot in self.bots.iter_mut() {
+            current.push(bot.driver.save_state()?);
+        }
+        for (index, saved) in snapshot.bots.iter().enumerate() {
+            if let Err(err) = self.bots[index].driver.load_state(&saved.module) {
+                for (bot, state) in self.bots.iter_mut().zip(current.iter()).take(index + 1) {
+                    bot.driver.load_state(state)?;
+                }
+                return Err(format!("bot {}: {}", index + 1, err));
+            }
+        }
+
+        for (bot, saved) in self.bots.iter_mut().zip(snapshot.bots.iter()) {
+            bot.energy = saved.energy;
+            bot.gas_reserve = saved.gas_reserve;
+            bot.faults = saved.faults.clone();
+            bot.gas = saved.gas.clone();
+            bot.action = saved.action;
+            bot.pending = saved.pending.iter().copied().collect();
+            bot.noise = saved.noise;
+        }
+        self.tick = snapshot.tick;
+        self.last_touch = snapshot.last_touch;
+        self.scores = snapshot.scores;
+        self.drop_rng = snapshot.drop_rng;
+        self.rigid_body_set = snapshot.rigid_body_set;
+        self.collider_set = snapshot.collider_set;
+        self.integration_parameters = snapshot.integration_parameters;
+        self.island_manager = snapshot.island_manager;
+        self.broad_phase = snapshot.broad_phase;
+        self.narrow_phase = snapshot.narrow_phase;
+        self.impulse_joint_set = snapshot.impulse_joint_set;
+        self.multibody_joint_set = snapshot.multibody_joint_set;
+        self.ccd_solver = snapshot.ccd_solver;
+        self.physics_pipeline = PhysicsPipeline::new();
+        self.query_pipeline = QueryPipeline::new();
+        Ok(())
+    }
+
+    /// Add a bot, picking its team from the mode: alternating sides for `Teams`, a new team
+    /// for `FreeForAll`. Returns the bot index.
+    pub fn add_bot(&mut self, wasm_bytes: &mut [u8]) -> Result<usize, String> {
+        self.add_bot_to_team(wasm_bytes, self.next_team())
+    }
+
+    /// Add a bot to the given team (0-based). Returns the bot index.
+    pub fn add_bot_to_team(&mut self, wasm_bytes: &mut [u8], team: u8) -> Result<usize, String> {
+        let hash = Sha256::digest(&*wasm_bytes).into();
+        self.add_driver_to_team(team, hash, |rules, cache| {
+            create_bot_module(wasm_bytes, rules, cache).map(Driver::Module)
+        })
+    }
+
+    /// Add a bot played by a person, its team picked like [`Battle::add_bot`]. Returns the
+    /// bot index.
+    pub fn add_human(&mut self, human: &HumanController) -> Result<usize, String> {
+        self.add_human_to_team(human, self.next_team())
+    }
+
+    /// Add a bot played by a person to the given team (0-based), its action being whatever
+    /// was last given to `human`. Its hash is the SHA-256 of `controller:human` and, like any
+    /// controller, it can not be snapshotted. Returns the bot index.
+    pub fn add_human_to_team(
+        &mut self,
+        human: &HumanController,
+        team: u8,
+    ) -> Result<usize, String> {
+        self.add_controller_to_team(human.clone(), team)
+    }
+
+    /// Team of the next bot added with [`Battle::add_bot`].
+    fn next_team(&self) -> u8 {
+        let team = match self.mode {
+            MatchMode::Teams => self.bots.len() % 2,
+            MatchMode::FreeForAll => self.bots.len(),
+        };
+        team as u8
+    }
+
+    /// Check that `team` has room left, then create the bot and spawn it.
+    fn add_driver_to_team(
+        &mut self,
+        team: u8,
+        hash: [u8; 32],
+        create: impl FnOnce(&Rules, Option<&ModuleCache>) -> Result<Driver, String>,
+    ) -> Result<usize, String> {
+        let team_index = team as usize;
+        let slot = self.bots.iter().filter(|bot| bot.team == team).count();
+        match self.mode {
+            MatchMode::Teams => {
+                if team_index >= 2 {
+                    return Err(format!("team {} does not exist in a team match", team));
+                }
+            }
+            MatchMode::FreeForAll => {
+                if team_index >= MAX_TEAMS {
+                    return Err(format!(
+                        "a free-for-all supports at most {} bots",
+                        MAX_TEAMS
+                    ));
+                }
+                if slot > 0 {
+                    return Err(format!(
+                        "team {} already has its bot in a free-for-all",
+                        team
+                    ));
+                }
+            }
+        }
+        let Some([x, z]) = self.rules.spawn.position(team, slot) else {
+            return Err(format!(
+                "team {} is full ({} bots)",
+                team,
+                self.rules.spawn.slots.len()
+            ));
+        };
+        let driver = create(&self.rules, self.module_cache.as_deref())?;
+
+        if team_index >= 2 && slot == 0 {
+            self.collider_set.insert(goal_collider(team));
+        }
+
+        let position = Position {
+            x,
+            y: GROUND_LEVEL + self.rules.bot.radius + SPAWN_CLEARANCE,
+            z,
+        };
+
+        let index = self.bots.len();
+        let handle = self.create_bot_handle(position, index);
+        self.bots.push(Bot {
+            driver,
+            hash,
+            handle,
+            team,
+            energy: self.rules.bot.energy.map_or(0.0, |energy| energy.capacity),
+            gas_reserve: self
+                .rules
+                .gas_bank
+                .map_or(0, |bank| bank.initial_reserve.min(bank.capacity)),
+            faults: FaultCounts::default(),
+            gas: GasUsage::default(),
+            action: Action::NEUTRAL,
+            pending: VecDeque::new(),
+            noise: 0,
+        });
+        Ok(index)
+    }
+
+    /// Start the match. Fails, before any bot is called, without bots in at least 2 teams or
+    /// when a bot spawns overlapping something.
+    pub fn init(&mut self) -> Result<(), String> {
+        if self.teams_in_play() < 2 {
+            return Err("a battle needs bots in at least 2 teams".to_string());
+        }
+        self.validate_spawns()
+            .map_err(|err| format!("invalid spawn layout: {}", err))?;
+
+        let teams: Vec<u8> = self.bots.iter().map(|bot| bot.team).collect();
+        let num_balls = self.balls.len();
+        for bot in self.bots.iter_mut() {
+            let num_teammates = teams.iter().filter(|team| **team == bot.team).count() - 1;
+            let num_opponents = teams.len() - 1 - num_teammates;
+            bot.driver.begin_tick();
+            bot.driver.set_remaining_points(INIT_GAS);
+            if let Err(fault) = bot.driver.prepare_observation_buffer(Observation::len(
+                num_teammates,
+                num_opponents,
+                num_balls,
+            )) {
+                bot.faults.record(fault, self.tick);
+            }
+        }
+
+        let hashes: Vec<[u8; 32]> = self.bots.iter().map(|bot| bot.hash).collect();
+        let seed = u32::from_le_bytes([
+            self.match_id[0],
+            self.match_id[1],
+            self.match_id[2],
+            self.match_id[3],
+        ]);
+        let (tick_gas, gas_bank_capacity) = match self.rules.gas_bank {
+            Some(bank) => (bank.allowance_per_tick, bank.capacity),
+            None => (COMPUTE_ACTIONS_GAS, 0),
+        };
+
+        verbose!("Calling `init` ...");
+        let mut opponents = Vec::new();
+        for (index, bot) in self.bots.iter_mut().enumerate() {
+            bot.noise = rng::splitmix(((seed as u64) << 8) | index as u64);
+            opponents.clear();
+            opponents.extend(
+                hashes
+                    .iter()
+                    .zip(teams.iter())
+                    .filter(|(_, team)| **team != bot.team)
+                    .map(|(hash, _)| *hash),
+            );
+            let context = MatchContext {
+                seed,
+                match_id: self.match_id,
+                bot_hash: bot.hash,
+                team: bot.team,
+                rules_version: self.rules.version,
+                max_ticks: self.rules.max_ticks,
+                init_gas: INIT_GAS,
+                tick_gas,
+                gas_bank_capacity,
+                match_end_gas: MATCH_END_GAS,
+                opponents: &opponents,
+            };
+            bot.driver.set_remaining_points(INIT_GAS);
+            if let Err(fault) = bot.driver.init(&context) {
+                bot.faults.record(fault, self.tick);
+            }
+            collect_logs(&mut self.logs, index, self.tick, &mut bot.driver);
+        }
+
+        for bot in self.bots.iter() {
+            let impulse = self.rules.initial_impulse(bot.team);
+            self.rigid_body_set[bot.handle].apply_impulse(Vector3::from(impulse), true);
+        }
+
+        // the bots draw from streams whose high bits are all 0
+        self.drop_rng = rng::splitmix(!(seed as u64));
+        if self.rules.ball.drop_spread > 0.0 {
+            for ball in 0..self.balls.len() {
+                self.drop_ball(ball);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self) -> u8 {
+        let balls = self.ball_positions();
+        let positions = self.bot_positions();
+        let teams: Vec<u8> = self.bots.iter().map(|bot| bot.team).collect();
+        let body = self.rules.bot;
+        let gas_bank = self.rules.gas_bank;
+        let timing = self.rules.timing;
+        let perception = self.rules.perception;
+        let decides = timing.decides(self.tick);
+
+        // println!("Calling `compute_actions` ...");
+
+        let mut sight = Sight::default();
+        let mut impulses = Vec::with_capacity(self.bots.len());
+        for (index, bot) in self.bots.iter_mut().enumerate() {
+            if decides {
+                sight.look(
+                    &perception,
+                    index,
+                    &positions,
+                    &teams,
+                    &balls,
+                    &mut bot.noise,
+                );
+
+                let gas = match gas_bank {
+                    Some(bank) => {
+                        bot.gas_reserve =
+                            (bot.gas_reserve + bank.allowance_per_tick).min(bank.capacity);
+                        bot.gas_reserve
+                    }
+                    None => COMPUTE_ACTIONS_GAS,
+                };
+                bot.driver.begin_tick();
+                bot.driver.set_remaining_points(gas);
+                let result = bot.driver.compute_actions(&Observation {
+                    self_position: sight.self_position,
+                    ball: sight.ball,
+                    team: bot.team,
+                    teammates: &sight.teammates,
+                    opponents: &sight.opponents,
+                    energy: bot.energy,
+                    gas: gas as f32,
+                    balls: &sight.balls,
+                });
+                let remaining = bot.driver.remaining_gas();
+                bot.gas.record(gas.saturating_sub(remaining));
+                if gas_bank.is_some() {
+                    bot.gas_reserve = remaining;
+                }
+                collect_logs(&mut self.logs, index, self.tick, &mut bot.driver);
+                let decision = match result {
+                    Ok(action) => action,
+                    Err(fault) => {
+                        bot.faults.record(fault, self.tick);
+                        Action::NEUTRAL
+                    }
+                };
+                let due = self.tick + perception.action_delay as u64;
+                bot.pending.push_back((due, decision));
+            }
+            while let Some(&(due, decision)) = bot.pending.front() {
+                if due > self.tick {
+                    break;
+                }
+                bot.action = decision;
+                bot.pending.pop_front();
+            }
+            let action = bot.action;
+
+            let mut impulse = Vector3::from(action.impulse);
+            let mut magnitude = match body.max_impulse {
+                Some(max_impulse) => {
+                    if impulse.norm() > 1.0 {
+                        impulse = impulse.normalize();
+                    }
+                    max_impulse
+                }
+                None => {
+                    impulse = impulse.map(|value| value.clamp(-1.0, 1.0));
+                    1.0
+                }
+            };
+            if let Some(energy) = body.energy {
+                if action.boost && bot.energy >= energy.boost_cost_per_tick {
+                    bot.energy -= energy.boost_cost_per_tick;
+                    magnitude *= energy.boost_multiplier;
+                } else {
+                    bot.energy = (bot.energy + energy.regeneration_per_tick).min(energy.capacity);
+                }
+            }
+            impulses.push(impulse * magnitude);
+        }
+
+        // let remain = bot1.get_remaining_points();
+        // println!("remaining points: {:?}", remain);
+
+        for (bot, impulse) in self.bots.iter().zip(impulses) {
+            self.rigid_body_set[bot.handle].apply_impulse(impulse, true);
+        }
+
+        self.tick += 1;
+
+        let event_handler = CustomEventHandler {
+            goals: RwLock::new(Vec::new()),
+            touches: RwLock::new(Vec::new()),
+        };
+
+        /* Run the game loop, stepping the simulation `substeps` times per tick. */
+        let magnus = self.rules.ball.magnus;
+        for _ in 0..timing.substeps.max(1) {
+            if magnus != 0.0 {
+                for handle in self.balls.iter() {
+                    let rigid_body = &mut self.rigid_body_set[*handle];
+                    let force = rigid_body.angvel().cross(rigid_body.linvel()) * magnus;
+                    rigid_body.apply_impulse(force * timing.step_dt(), true);
+                }
+            }
+
+            self.physics_pipeline.step(
+                &self.gravity,
+                &self.integration_parameters,
+                &mut self.island_manager,
+                &mut self.broad_phase,
+                &mut self.narrow_phase,
+                &mut self.rigid_body_set,
+                &mut self.collider_set,
+                &mut self.impulse_joint_set,
+                &mut self.multibody_joint_set,
+                &mut self.ccd_solver,
+                Some(&mut self.query_pipeline),
+                &self.physics_hooks,
+                &event_handler,
+            );
+
+            for bot in self.bots.iter() {
+                let rigid_body = &mut self.rigid_body_set[bot.handle];
+                let velocity = *rigid_body.linvel();
+                let speed = velocity.norm();
+                match body.max_speed {
+                    Some(max_speed) if speed > max_speed => {
+                        rigid_body.set_linvel(velocity * (max_speed / speed), true);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (ball, bot) in read_events(&event_handler.touches) {
+            self.last_touch[ball] = Some(bot);
+        }
+
+        let mut scored = vec![false; self.balls.len()];
+        for (ball, conceding_team) in read_events(&event_handler.goals) {
+            // a ball can only score once per tick
+            if std::mem::replace(&mut scored[ball], true) {
+                continue;
+            }
+            match self.scoring_team(ball, conceding_team) {
+                Some(team) => {
+                    self.scores[team as usize] += 1;
+                    if self.scores[team as usize] >= self.rules.goals_to_win.max(1) {
+                        return team + 1;
+                    }
+                    verbose!("team {} scores, dropping the ball again", team + 1);
+                }
+                None => verbose!("no team to credit, dropping the ball again"),
+            }
+            self.drop_ball(ball);
+        }
+        0
+    }
+
+    /// Play the match to its end, returning the winner like `update` (0 for a draw). Fails
+    /// like `init`.
+    pub fn execute(&mut self) -> Result<u8, String> {
+        self.execute_with(|_| Ok::<(), String>(()))
+    }
+
+    /// Let every bot know the outcome (`winner` as returned by `update`, 0 for a draw) through
+    /// its optional `on_match_end(result)` export: 0 for a draw, 1 when its team won and 2
+    /// when it lost. This is when a bot can update its storage.
+    pub fn end(&mut self, winner: u8) {
+        for (index, bot) in self.bots.iter_mut().enumerate() {
+            let result = match winner {
+                0 => 0,
+                team if team == bot.team + 1 => 1,
+                _ => 2,
+            };
+            bot.driver.begin_tick();
+            bot.driver.set_remaining_points(MATCH_END_GAS);
+            if let Err(fault) = bot.driver.match_end(result) {
+                bot.faults.record(fault, self.tick);
+            }
+            collect_logs(&mut self.logs, index, self.tick, &mut bot.driver);
+        }
+    }
+
+    fn teams_in_play(&self) -> usize {
+        let mut present = [false; MAX_TEAMS];
+        for bot in self.bots.iter() {
+            present[bot.team as usize] = true;
+        }
+        present.iter().filter(|present| **present).count()
+    }
+
+    /// The team credited when `conceding_team` lets `ball` in: the last bot to touch it if it
+    /// plays for another team, otherwise the opposing team when there is only one.
+    fn scoring_team(&self, ball: usize, conceding_team: u8) -> Option<u8> {
+        if let Some(last_touch) = self.last_touch[ball] {
+            let team = self.bots[last_touch].team;
+            if team != conceding_team {
+                return Some(team);
+            }
+        }
+        if self.teams_in_play() == 2 {
+            return self
+                .bots
+                .iter()
+                .map(|bot| bot.team)
+                .find(|team| *team != conceding_team);
+        }
+        None
+    }
+
+    /// Put `ball` back still above its spot, up to `drop_spread` away from it.
+    fn drop_ball(&mut self, ball: usize) {
+        let body = self.rules.ball;
+        let mut spot = ball_spot(&body, ball, self.balls.len());
+        if body.drop_spread > 0.0 {
+            let limit = ARENA_HALF_SIZE - body.radius;
+            for value in spot.iter_mut() {
+                let offset = rng::signed_unit(&mut self.drop_rng) * body.drop_spread;
+                *value = (*value + offset).clamp(-limit, limit);
+            }
+        }
+        let rigid_body = &mut self.rigid_body_set[self.balls[ball]];
+        rigid_body.set_translation(vector![spot[0], body.drop_height, spot[1]], true);
+        rigid_body.set_linvel(vector![0.0, 0.0, 0.0], true);
+        rigid_body.set_angvel(vector![0.0, 0.0, 0.0], true);
+        self.last_touch[ball] = None;
+    }
+}
+
+impl Battle {
+    /// Share compiled bots with other battles: bots added afterwards are only compiled if
+    /// they are not in the cache yet. Not used in the browser.
+    pub fn set_module_cache(&mut self, cache: Arc<ModuleCache>) {
+        self.module_cache = Some(cache);
+    }
+
+    /// Messages logged by the bots and not drained yet.
+    pub fn logs(&self) -> &[BotLog] {
+        &self.logs
+    }
+
+    /// Take the messages logged by the bots since the last call.
+    pub fn take_logs(&mut self) -> Vec<BotLog> {
+        std::mem::take(&mut self.logs)
+    }
+
+    /// What the bots get to see and how fast their actions apply, to record with a match.
+    pub fn perception(&self) -> Perception {
+        self.rules.perception
+    }
+
+    /// Faults of the bot so far, by kind.
+    pub fn bot_faults(&self, index: usize) -> &FaultCounts {
+        &self.bots[index].faults
+    }
+
+    /// [`Battle::execute`], calling `on_update` after `init` and after every update, for
+    /// example to record or draw the match as it goes. The match is abandoned, without
+    /// calling `end`, on the first error of `init` or `on_update`.
+    pub fn execute_with<E: From<String>>(
+        &mut self,
+        mut on_update: impl FnMut(&Battle) -> Result<(), E>,
+    ) -> Result<u8, E> {
+        self.init()?;
+        verbose!("initialised.");
+        on_update(self)?;
+        let mut counter = 0;
+        let mut winner;
+        loop {
+            winner = self.update();
+            on_update(self)?;
+            if winner != 0 {
+                // TODO winner
+                verbose!("WINNER: {winner}");
+                break;
+            } else {
+                counter += 1;
+                if counter > self.rules.max_ticks {
+                    // TODO draw
+                    verbose!("DRAW: {counter} updates executed.");
+                    break;
+                }
+            }
+        }
+        self.end(winner);
+        Ok(winner)
+    }
+
+    /// Add a built-in opponent, its team picked like [`Battle::add_bot`]. Returns the bot
+    /// index.
+    pub fn add_reference_bot(&mut self, bot: ReferenceBot) -> Result<usize, String> {
+        self.add_reference_bot_to_team(bot, self.next_team())
+    }
+
+    /// Add a built-in opponent to the given team (0-based). Its hash is the SHA-256 of
+    /// `reference:<name>`. Returns the bot index.
+    pub fn add_reference_bot_to_team(
+        &mut self,
+        bot: ReferenceBot,
+        team: u8,
+    ) -> Result<usize, String> {
+        let hash = Sha256::digest(format!("reference:{}", bot.name())).into();
+        self.add_driver_to_team(team, hash, |rules, _| {
+            create_reference_module(bot, rules).map(Driver::Module)
+        })
+    }
+
+    /// Add a bot played by Rust code, its team picked like [`Battle::add_bot`]. Returns the
+    /// bot index.
+    pub fn add_controller(
+        &mut self,
+        controller: impl Controller + 'static,
+    ) -> Result<usize, String> {
+        self.add_controller_to_team(controller, self.next_team())
+    }
+
+    /// Add a bot played by Rust code to the given team (0-based). Its hash is the SHA-256 of
+    /// `controller:<name>`. Returns the bot index.
+    pub fn add_controller_to_team(
+        &mut self,
+        controller: impl Controller + 'static,
+        team: u8,
+    ) -> Result<usize, String> {
+        let hash = Sha256::digest(format!("controller:{}", controller.name())).into();
+        self.add_driver_to_team(team, hash, |_, _| Ok(Driver::controller(controller)))
+    }
+
+    /// What bot `index` would be handed if the next tick started now, noise included,
+    /// flattened like the extended ABI lays it out: `self.xyz, ball.xyz, team, T,
+    /// T * teammate.xyz, O, O * opponent.xyz, energy, gas, B, B * ball.xyz`.
+    pub fn observation(&self, index: usize) -> Vec<f32> {
+        let balls = self.ball_positions();
+        let positions = self.bot_positions();
+        let teams: Vec<u8> = self.bots.iter().map(|bot| bot.team).collect();
+        let bot = &self.bots[index];
+        // the same draws as the next tick, without using them up
+        let mut noise = bot.noise;
+        let mut sight = Sight::default();
+        sight.look(
+            &self.rules.perception,
+            index,
+            &positions,
+            &teams,
+            &balls,
+            &mut noise,
+        );
+        let gas = match self.rules.gas_bank {
+            Some(bank) => (bot.gas_reserve + bank.allowance_per_tick).min(bank.capacity),
+            None => COMPUTE_ACTIONS_GAS,
+        };
+        let mut observation = Vec::with_capacity(Observation::len(
+            sight.teammates.len(),
+            sight.opponents.len(),
+            sight.balls.len(),
+        ));
+        Observation {
+            self_position: sight.self_position,
+            ball: sight.ball,
+            team: bot.team,
+            teammates: &sight.teammates,
+            opponents: &sight.opponents,
+            energy: bot.energy,
+            gas: gas as f32,
+            balls: &sight.balls,
+        }
+        .write_to(&mut observation);
+        observation
+    }
+
+    fn bot_positions(&self) -> Vec<[f32; 3]> {
+        self.bots
+            .iter()
+            .map(|bot| {
+                let position = self.body_position(bot.handle);
+                [position.x, position.y, position.z]
+            })
+            .collect()
+    }
+
+    fn ball_positions(&self) -> Vec<[f32; 3]> {
+        self.balls
+            .iter()
+            .map(|handle| {
+                let position = self.body_position(*handle);
+                [position.x, position.y, position.z]
+            })
+            .collect()
+    }
+
+    /// Gas the bot used so far.
+    pub fn bot_gas(&self, index: usize) -> &GasUsage {
+        &self.bots[index].gas
+    }
+
+    /// Check that no bot starts overlapping the arena, a ball or another bot.
+    pub fn validate_spawns(&self) -> Result<(), SpawnError> {
+        spawn::validate(&self.collider_set)
+    }
+
+    pub fn new_with_rules(mode: MatchMode, rules: Rules) -> Battle {
         let mut rigid_body_set = RigidBodySet::new();
         let mut collider_set = ColliderSet::new();
 
@@ -491,17 +1245,9 @@ impl Battle {
         //     .build();
         // collider_set.insert(collider);
 
-        let collider_for_bot1_goal = ColliderBuilder::cuboid(0.5, 2.5, 2.5)
-            .translation(vector![-10.4, 0.0, 0.0])
-            .user_data(ObjectType::Bot1Goal as u128)
-            .build();
-        collider_set.insert(collider_for_bot1_goal);
-
-        let collider_for_bot2_goal = ColliderBuilder::cuboid(0.5, 2.5, 2.5)
-            .translation(vector![10.4, 0.0, 0.0])
-            .user_data(ObjectType::Bot2Goal as u128)
-            .build();
-        collider_set.insert(collider_for_bot2_goal);
+        // the goals of the other free-for-all teams are added with their bot
+        collider_set.insert(goal_collider(0));
+        collider_set.insert(goal_collider(1));
 
         // // Define dome parameters
         // let dome_radius = 5.0;
@@ -523,7 +1269,10 @@ impl Battle {
 
         /* Create other structures necessary for the simulation. */
         let gravity = vector![0.0, -9.81, 0.0];
-        let integration_parameters = IntegrationParameters::default();
+        let integration_parameters = IntegrationParameters {
+            dt: rules.timing.step_dt(),
+            ..IntegrationParameters::default()
+        };
         let physics_pipeline = PhysicsPipeline::new();
         let island_manager = IslandManager::new();
         let broad_phase = DefaultBroadPhase::new();
@@ -533,23 +1282,42 @@ impl Battle {
         let ccd_solver = CCDSolver::new();
         let query_pipeline = QueryPipeline::new();
         let physics_hooks = ();
-        let event_handler = ();
 
-        /* Create the bouncing ball. */
-        let rigid_body = RigidBodyBuilder::dynamic()
-            .translation(vector![0.0, 10.5, 0.0])
-            .build();
-        let collider = ColliderBuilder::ball(0.5)
-            .active_events(ActiveEvents::COLLISION_EVENTS)
-            .restitution(0.7)
-            .user_data(ObjectType::Ball as u128)
-            .build();
-        let ball = rigid_body_set.insert(rigid_body);
-        collider_set.insert_with_parent(collider, ball, &mut rigid_body_set);
+        /* Create the bouncing balls. */
+        let body = rules.ball;
+        let count = body.count.max(1) as usize;
+        let mut balls = Vec::with_capacity(count);
+        for index in 0..count {
+            let [x, z] = ball_spot(&body, index, count);
+            let rigid_body = RigidBodyBuilder::dynamic()
+                .translation(vector![x, body.drop_height, z])
+                .linear_damping(body.linear_damping)
+                .angular_damping(body.angular_damping)
+                .build();
+            let mut collider = ColliderBuilder::ball(body.radius)
+                .active_events(ActiveEvents::COLLISION_EVENTS)
+                .restitution(body.restitution)
+                .friction(body.friction)
+                .user_data(ObjectType::Ball.user_data(index));
+            if let Some(mass) = body.mass {
+                collider = collider.mass(mass);
+            }
+            let ball = rigid_body_set.insert(rigid_body);
+            collider_set.insert_with_parent(collider.build(), ball, &mut rigid_body_set);
+            balls.push(ball);
+        }
 
         Battle {
-            bot1: None,
-            bot2: None,
+            mode,
+            rules,
+            bots: Vec::new(),
+            tick: 0,
+            last_touch: vec![None; count],
+            scores: [0; MAX_TEAMS],
+            drop_rng: 0,
+            module_cache: None,
+            match_id: [0; 32],
+            logs: Vec::new(),
             collider_set,
             physics_pipeline,
             rigid_body_set,
@@ -563,204 +1331,41 @@ impl Battle {
             ccd_solver,
             query_pipeline,
             physics_hooks,
-            event_handler,
-            ball,
-        }
-    }
-
-    fn create_bot_handle(
-        &mut self,
-        position: Position,
-        object_type: ObjectType,
-    ) -> RigidBodyHandle {
-        /* Create the bouncing ball. */
-        let rigid_body = RigidBodyBuilder::dynamic()
-            .translation(vector![position.x, position.y, position.y])
-            .build();
-        let collider = ColliderBuilder::ball(0.5)
-            .active_events(ActiveEvents::COLLISION_EVENTS)
-            .restitution(0.7)
-            .user_data(object_type as u128)
-            .build();
-        let handle = self.rigid_body_set.insert(rigid_body);
-        self.collider_set
-            .insert_with_parent(collider, handle, &mut self.rigid_body_set);
-        handle
-    }
-
-    pub fn get_bot1(&self) -> Position {
-        let bot = self.bot1.as_ref().unwrap();
-        let body = &self.rigid_body_set[bot.handle];
-        Position {
-            x: body.translation().x,
-            y: body.translation().y,
-            z: body.translation().z,
-        }
-    }
-
-    pub fn get_bot2(&self) -> Position {
-        let bot = self.bot2.as_ref().unwrap();
-        let body = &self.rigid_body_set[bot.handle];
-        Position {
-            x: body.translation().x,
-            y: body.translation().y,
-            z: body.translation().z,
-        }
-    }
-
-    pub fn get_ball(&self) -> Position {
-        let body = &self.rigid_body_set[self.ball];
-        Position {
-            x: body.translation().x,
-            y: body.translation().y,
-            z: body.translation().z,
-        }
-    }
-
-    pub fn add_bot(&mut self, wasm_bytes: &mut [u8]) {
-        if let Some(_existing_bot) = &self.bot1 {
-            if let Some(_existing_bot) = &self.bot2 {
-                panic!("already both bots added");
-            } else {
-                let handle = self.create_bot_handle(
-                    Position {
-                        x: 9.0,
-                        y: 0.5,
-                        z: 0.0,
-                    },
-                    ObjectType::Bot2,
-                );
-                self.bot2 = Some(create_bot_module(wasm_bytes, handle));
-            }
-        } else {
-            let handle = self.create_bot_handle(
-                Position {
-                    x: -9.0,
-                    y: 0.5,
-                    z: 0.0,
-                },
-                ObjectType::Bot1,
-            );
-            self.bot1 = Some(create_bot_module(wasm_bytes, handle));
+            balls,
         }
     }
+}
 
-    pub fn init(&mut self) {
-        let bot1 = self.bot1.as_mut().unwrap();
-        let bot2 = self.bot2.as_mut().unwrap();
-
-        bot1.set_remaining_points(INIT_GAS);
-        bot2.set_remaining_points(INIT_GAS);
-
-        println!("Calling `init` ...");
-        bot1.init(0);
-        bot2.init(0);
-
-        self.rigid_body_set[bot1.handle].apply_impulse(vector![2.0, 0.0, 0.0], true);
-        // self.rigid_body_set[bot2.handle].apply_impulse(vector![-1.0, 1.0, 0.0], true);
-        self.rigid_body_set[bot2.handle].apply_impulse(vector![-2.0, 0.0, 1.0], true);
+/// Attribute the messages the bot just logged to it and to the tick.
+fn collect_logs(logs: &mut Vec<BotLog>, bot: usize, tick: u64, driver: &mut Driver) {
+    for (level, message) in driver.take_logs() {
+        logs.push(BotLog {
+            bot,
+            tick,
+            level,
+            message,
+        });
     }
+}
 
-    pub fn update(&mut self) -> u8 {
-        let ball_pos = self.get_ball().clone();
-        let bot1_pos = self.get_bot1().clone();
-        let bot2_pos = self.get_bot2().clone();
-
-        // // Initialize the event collector.
-        // let (collision_send, collision_recv) = crossbeam::channel::unbounded();
-        // let (contact_force_send, contact_force_recv) = crossbeam::channel::unbounded();
-        // let event_handler = ChannelEventCollector::new(collision_send, contact_force_send);
-
-        let bot1 = self.bot1.as_mut().unwrap();
-        let bot2 = self.bot2.as_mut().unwrap();
-
-        bot1.set_remaining_points(COMPUTE_ACTIONS_GAS);
-        bot2.set_remaining_points(COMPUTE_ACTIONS_GAS);
-
-        // println!("Calling `compute_actions` ...");
-        // TODO
-
-        let bot1_vector = Vector3::from(reconstruct_action(bot1.compute_actions(
-            bot1_pos.x, bot1_pos.y, bot1_pos.z, ball_pos.x, ball_pos.y, ball_pos.z, bot2_pos.x,
-            bot2_pos.y, bot2_pos.z,
-        )));
-        let bot2_vector = Vector3::from(reconstruct_action(bot2.compute_actions(
-            bot2_pos.x, bot2_pos.y, bot2_pos.z, ball_pos.x, ball_pos.y, ball_pos.z, bot1_pos.x,
-            bot1_pos.y, bot1_pos.z,
-        )));
-
-        // let remain = bot1.get_remaining_points();
-        // println!("remaining points: {:?}", remain);
-
-        self.rigid_body_set[bot1.handle].apply_impulse(bot1_vector, true);
-        // self.rigid_body_set[bot2.handle].apply_impulse(vector![-1.0, 1.0, 0.0], true);
-        self.rigid_body_set[bot2.handle].apply_impulse(bot2_vector, true);
-
-        let event_handler = CustomEventHandler {
-            winner: RwLock::new(0),
-        };
-
-        // // Make sure to set up the event handler with the physics pipeline
-        // // let mut physics_hooks = ChannelEventCollector::new();
-        // self.physics_hooks.handle_collision_event(
-        //     &self.rigid_body_set,
-        //     &self.collider_set,
-        //     event,
-        //     contact_pair,
-        // );
-        // self.physics_hooks
-        //     .set_collision_event_handler(Box::new(event_handler));
-
-        /* Run the game loop, stepping the simulation once per frame. */
-        self.physics_pipeline.step(
-            &self.gravity,
-            &self.integration_parameters,
-            &mut self.island_manager,
-            &mut self.broad_phase,
-            &mut self.narrow_phase,
-            &mut self.rigid_body_set,
-            &mut self.collider_set,
-            &mut self.impulse_joint_set,
-            &mut self.multibody_joint_set,
-            &mut self.ccd_solver,
-            Some(&mut self.query_pipeline),
-            &self.physics_hooks,
-            &event_handler,
-        );
-
-        let winner = {
-            match event_handler.winner.read() {
-                Ok(guard) => *guard, // Dereference to get the actual value
-                Err(_) => {
-                    // Handle potential poisoned lock
-                    println!("Warning: RwLock was poisoned. Using default value.");
-                    0 // Or any other default value
-                }
-            }
-        };
-        winner
-    }
+/// Where ball `index` of `count` is dropped when there is no spread, on the ground plane:
+/// side by side across the middle of the arena.
+fn ball_spot(body: &BallBody, index: usize, count: usize) -> [f32; 2] {
+    let offset = index as f32 - (count - 1) as f32 / 2.0;
+    let limit = ARENA_HALF_SIZE - body.radius;
+    [0.0, (offset * body.radius * 3.0).clamp(-limit, limit)]
+}
 
-    pub fn execute(&mut self) -> u8 {
-        self.init();
-        println!("initialised.");
-        let mut counter = 0;
-        let mut winner;
-        loop {
-            winner = self.update();
-            if winner != 0 {
-                // TODO winner
-                println!("WINNER: {winner}");
-                break;
-            } else {
-                counter = counter + 1;
-                if counter > MAX_NUM_UPDATES {
-                    // TODO draw
-                    println!("DRAW: {counter} updates executed.");
-                    break;
-                }
-            }
-        }
-        winner
-    }
+/// Goal defended by `team`: teams 0 and 1 on the x walls, teams 2 and 3 on the z walls.
+fn goal_collider(team: u8) -> Collider {
+    let (half_extents, translation) = match team {
+        0 => (vector![0.5, 2.5, 2.5], vector![-10.4, 0.0, 0.0]),
+        1 => (vector![0.5, 2.5, 2.5], vector![10.4, 0.0, 0.0]),
+        2 => (vector![2.5, 2.5, 0.5], vector![0.0, 0.0, -10.4]),
+        _ => (vector![2.5, 2.5, 0.5], vector![0.0, 0.0, 10.4]),
+    };
+    ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z)
+        .translation(translation)
+        .user_data(ObjectType::Goal.user_data(team as usize))
+        .build()
 }
@@ -0,0 +1,471 @@
+//! Resource limits of the native runtime: the tunables cap the linear memory and the tables
+//! of every instance, the [`CallDepth`] middleware bounds recursion and the [`MemoryGrowth`]
+//! middleware flags the `memory.grow` that failed. The browser runtime relies on the JS
+//! engine for the limits.
+
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+use wasmer::sys::{
+    BaseTunables, FunctionMiddleware, MiddlewareReaderState, ModuleMiddleware, Tunables,
+};
+use wasmer::vm::{
+    MemoryError, MemoryStyle, TableStyle, VMMemory, VMMemoryDefinition, VMTable, VMTableDefinition,
+};
+use wasmer::wasmparser::{BlockType, Operator, RefType, ValType};
+use wasmer::{
+    ExportIndex, GlobalInit, GlobalType, LocalFunctionIndex, MemoryType, MiddlewareError,
+    Mutability, Pages, TableType, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo};
+
+use crate::rules::BotLimits;
+
+/// Exported global holding the current call depth, reset by the host before every call.
+pub(crate) const CALL_DEPTH_GLOBAL: &str = "machine_call_depth";
+/// Exported global set to 1 when the call depth limit made the bot trap.
+pub(crate) const CALL_DEPTH_EXCEEDED_GLOBAL: &str = "machine_call_depth_exceeded";
+/// Exported global set to 1 when a `memory.grow` failed, reset by the host before every call.
+pub(crate) const MEMORY_GROW_FAILED_GLOBAL: &str = "machine_memory_grow_failed";
+
+/// Tunables giving every memory and table a maximum no larger than the limits, so that
+/// `memory.grow` and `table.grow` fail instead of taking the host down.
+pub(crate) struct LimitingTunables {
+    base: BaseTunables,
+    max_memory: Pages,
+    max_table_elements: u32,
+}
+
+impl LimitingTunables {
+    pub fn new(base: BaseTunables, limits: &BotLimits) -> Self {
+        LimitingTunables {
+            base,
+            max_memory: Pages(limits.max_memory_pages),
+            max_table_elements: limits.max_table_elements,
+        }
+    }
+
+    fn adjust_memory(&self, requested: &MemoryType) -> MemoryType {
+        let mut adjusted = *requested;
+        adjusted.maximum = Some(match requested.maximum {
+            Some(maximum) if maximum < self.max_memory => maximum,
+            _ => self.max_memory,
+        });
+        adjusted
+    }
+
+    fn validate_memory(&self, ty: &MemoryType) -> Result<(), MemoryError> {
+        if ty.minimum > self.max_memory {
+            return Err(MemoryError::Generic(format!(
+                "the memory needs {} pages, the limit is {}",
+                ty.minimum.0, self.max_memory.0
+            )));
+        }
+        Ok(())
+    }
+
+    fn adjust_table(&self, requested: &TableType) -> TableType {
+        let mut adjusted = *requested;
+        adjusted.maximum = Some(match requested.maximum {
+            Some(maximum) if maximum < self.max_table_elements => maximum,
+            _ => self.max_table_elements,
+        });
+        adjusted
+    }
+
+    fn validate_table(&self, ty: &TableType) -> Result<(), String> {
+        if ty.minimum > self.max_table_elements {
+            return Err(format!(
+                "the table needs {} elements, the limit is {}",
+                ty.minimum, self.max_table_elements
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Tunables for LimitingTunables {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(&self.adjust_memory(memory))
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(&self.adjust_table(table))
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        self.base.create_host_memory(&adjusted, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        self.base
+            .create_vm_memory(&adjusted, style, vm_definition_location)
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        let adjusted = self.adjust_table(ty);
+        self.validate_table(&adjusted)?;
+        self.base.create_host_table(&adjusted, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        let adjusted = self.adjust_table(ty);
+        self.validate_table(&adjusted)?;
+        self.base
+            .create_vm_table(&adjusted, style, vm_definition_location)
+    }
+}
+
+/// Middleware counting nested calls in an exported global and trapping past the limit.
+///
+/// Every function increments the counter on entry and checks it. Its body is wrapped in a
+/// block so that the counter is decremented on the way out whether the function falls
+/// through its end or branches to its outermost label, `return` and the tail calls
+/// (`return_call*`, which replace the frame) decrement it in place. A trap leaves the
+/// counter wherever it was, which is why the host resets it before every call.
+///
+/// Modules using exceptions are refused: a thrown exception leaves functions without
+/// running their decrement.
+pub(crate) struct CallDepth {
+    limit: u32,
+    state: Mutex<Option<CallDepthState>>,
+}
+
+#[derive(Clone)]
+struct CallDepthState {
+    depth: GlobalIndex,
+    exceeded: GlobalIndex,
+    /// Block type wrapping the body of each local function, by local function index.
+    wrappers: Vec<Result<BlockType, String>>,
+}
+
+impl CallDepth {
+    pub fn new(limit: u32) -> Self {
+        CallDepth {
+            limit,
+            state: Mutex::new(None),
+        }
+    }
+}
+
+impl fmt::Debug for CallDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallDepth")
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+/// Block type producing `results`, or an error for multi-value results without a matching
+/// signature in the module.
+fn wrapper_block_type(module_info: &ModuleInfo, results: &[Type]) -> Result<BlockType, String> {
+    match results {
+        [] => Ok(BlockType::Empty),
+        [result] => match result {
+            Type::I32 => Ok(BlockType::Type(ValType::I32)),
+            Type::I64 => Ok(BlockType::Type(ValType::I64)),
+            Type::F32 => Ok(BlockType::Type(ValType::F32)),
+            Type::F64 => Ok(BlockType::Type(ValType::F64)),
+            Type::V128 => Ok(BlockType::Type(ValType::V128)),
+            Type::FuncRef => Ok(BlockType::Type(ValType::Ref(RefType::FUNCREF))),
+            Type::ExternRef => Ok(BlockType::Type(ValType::Ref(RefType::EXTERNREF))),
+        },
+        _ => module_info
+            .signatures
+            .iter()
+            .find(|(_, signature)| signature.params().is_empty() && signature.results() == results)
+            .map(|(index, _)| BlockType::FuncType(index.as_u32()))
+            .ok_or_else(|| "functions returning several values are not supported".to_string()),
+    }
+}
+
+impl ModuleMiddleware for CallDepth {
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let state = self
+            .state
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("module info was not transformed");
+        Box::new(FunctionCallDepth {
+            limit: self.limit,
+            depth: state.depth,
+            exceeded: state.exceeded,
+            wrapper: state.wrappers[local_function_index.as_u32() as usize].clone(),
+            entered: false,
+            block_depth: 0,
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        let mut state = self.state.lock().unwrap();
+        if state.is_some() {
+            panic!("CallDepth::transform_module_info: attempting to use a `CallDepth` middleware from multiple modules.");
+        }
+
+        let wrappers = module_info
+            .functions
+            .iter()
+            .skip(module_info.num_imported_functions)
+            .map(|(_, signature)| {
+                let results = module_info.signatures[*signature].results().to_vec();
+                wrapper_block_type(module_info, &results)
+            })
+            .collect();
+
+        let depth = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info
+            .exports
+            .insert(CALL_DEPTH_GLOBAL.to_string(), ExportIndex::Global(depth));
+
+        let exceeded = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info.exports.insert(
+            CALL_DEPTH_EXCEEDED_GLOBAL.to_string(),
+            ExportIndex::Global(exceeded),
+        );
+
+        *state = Some(CallDepthState {
+            depth,
+            exceeded,
+            wrappers,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct FunctionCallDepth {
+    limit: u32,
+    depth: GlobalIndex,
+    exceeded: GlobalIndex,
+    wrapper: Result<BlockType, String>,
+    entered: bool,
+    /// Nesting of the blocks of the original body, 0 at the function level.
+    block_depth: u32,
+}
+
+impl FunctionCallDepth {
+    fn decrement<'a>(&self, state: &mut MiddlewareReaderState<'a>) {
+        let depth = self.depth.as_u32();
+        state.extend(&[
+            Operator::GlobalGet {
+                global_index: depth,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::I32Sub,
+            Operator::GlobalSet {
+                global_index: depth,
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionCallDepth {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            let wrapper = self
+                .wrapper
+                .clone()
+                .map_err(|err| MiddlewareError::new("call_depth", err))?;
+            let depth = self.depth.as_u32();
+            state.extend(&[
+                Operator::GlobalGet {
+                    global_index: depth,
+                },
+                Operator::I32Const { value: 1 },
+                Operator::I32Add,
+                Operator::GlobalSet {
+                    global_index: depth,
+                },
+                Operator::GlobalGet {
+                    global_index: depth,
+                },
+                Operator::I32Const {
+                    value: self.limit as i32,
+                },
+                Operator::I32GtU,
+                Operator::If {
+                    blockty: BlockType::Empty,
+                },
+                Operator::I32Const { value: 1 },
+                Operator::GlobalSet {
+                    global_index: self.exceeded.as_u32(),
+                },
+                Operator::Unreachable,
+                Operator::End,
+                Operator::Block { blockty: wrapper },
+            ]);
+        }
+
+        match operator {
+            Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                self.block_depth += 1;
+                state.push_operator(operator);
+            }
+            Operator::Try { .. }
+            | Operator::TryTable { .. }
+            | Operator::Delegate { .. }
+            | Operator::Throw { .. }
+            | Operator::ThrowRef
+            | Operator::Rethrow { .. } => {
+                return Err(MiddlewareError::new(
+                    "call_depth",
+                    "exceptions are not supported",
+                ));
+            }
+            Operator::End if self.block_depth == 0 => {
+                // closes the wrapper, then the function
+                state.push_operator(Operator::End);
+                self.decrement(state);
+                state.push_operator(operator);
+            }
+            Operator::End => {
+                self.block_depth -= 1;
+                state.push_operator(operator);
+            }
+            Operator::Return
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. }
+            | Operator::ReturnCallRef { .. } => {
+                self.decrement(state);
+                state.push_operator(operator);
+            }
+            _ => state.push_operator(operator),
+        }
+        Ok(())
+    }
+}
+
+/// Middleware setting an exported global when a `memory.grow` returns -1, so that a trap
+/// following a failed allocation is told apart from any other trap.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryGrowth {
+    state: Mutex<Option<MemoryGrowthState>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct MemoryGrowthState {
+    /// Holds the result of `memory.grow` while it is compared.
+    scratch: GlobalIndex,
+    failed: GlobalIndex,
+}
+
+impl ModuleMiddleware for MemoryGrowth {
+    fn generate_function_middleware(
+        &self,
+        _local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let state = self
+            .state
+            .lock()
+            .unwrap()
+            .expect("module info was not transformed");
+        Box::new(FunctionMemoryGrowth { state })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        let mut state = self.state.lock().unwrap();
+        if state.is_some() {
+            panic!("MemoryGrowth::transform_module_info: attempting to use a `MemoryGrowth` middleware from multiple modules.");
+        }
+
+        let scratch = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        let failed = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info.exports.insert(
+            MEMORY_GROW_FAILED_GLOBAL.to_string(),
+            ExportIndex::Global(failed),
+        );
+
+        *state = Some(MemoryGrowthState { scratch, failed });
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct FunctionMemoryGrowth {
+    state: MemoryGrowthState,
+}
+
+impl FunctionMiddleware for FunctionMemoryGrowth {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let is_grow = matches!(operator, Operator::MemoryGrow { .. });
+        state.push_operator(operator);
+        if is_grow {
+            let scratch = self.state.scratch.as_u32();
+            state.extend(&[
+                Operator::GlobalSet {
+                    global_index: scratch,
+                },
+                Operator::GlobalGet {
+                    global_index: scratch,
+                },
+                Operator::GlobalGet {
+                    global_index: scratch,
+                },
+                Operator::I32Const { value: -1 },
+                Operator::I32Eq,
+                Operator::If {
+                    blockty: BlockType::Empty,
+                },
+                Operator::I32Const { value: 1 },
+                Operator::GlobalSet {
+                    global_index: self.state.failed.as_u32(),
+                },
+                Operator::End,
+            ]);
+        }
+        Ok(())
+    }
+}
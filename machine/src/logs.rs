@@ -0,0 +1,60 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Level given by a bot to `log_level`, `log`, `log_f32` and `print_u32` log at `Info`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// Level from the value passed by a bot, unknown values are treated as `Debug`.
+    pub(crate) fn from_u32(level: u32) -> LogLevel {
+        match level {
+            0 => LogLevel::Error,
+            1 => LogLevel::Warn,
+            2 => LogLevel::Info,
+            _ => LogLevel::Debug,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A message logged by a bot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BotLog {
+    /// Index of the bot in the battle.
+    pub bot: usize,
+    /// Updates completed when the message was logged, 0 for `init` and the first update.
+    pub tick: u64,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl fmt::Display for BotLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[tick {}] bot {} {}: {}",
+            self.tick,
+            self.bot + 1,
+            self.level,
+            self.message
+        )
+    }
+}
@@ -0,0 +1,200 @@
+//! The interpreter computes floats on the host CPU, whose NaN bit patterns differ from one
+//! architecture to the next. Like Cranelift does natively, every float instruction that can
+//! produce a NaN is followed by code replacing any NaN with the canonical one, so that a bot
+//! computes the same bits everywhere.
+
+use wasmparser::{FunctionBody, Operator, Parser, Payload};
+
+use crate::globals::{read_leb, write_leb};
+
+const CODE_SECTION: u8 = 10;
+
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const F32_CONST: u8 = 0x43;
+const F64_CONST: u8 = 0x44;
+const F32_NE: u8 = 0x5C;
+const F64_NE: u8 = 0x62;
+const SELECT: u8 = 0x1B;
+const F32: u8 = 0x7D;
+const F64: u8 = 0x7C;
+
+#[derive(Clone, Copy)]
+enum Float {
+    F32,
+    F64,
+}
+
+/// Type of the result of `operator` when it may be a NaN with any bit pattern. Sign
+/// manipulations (`abs`, `neg`, `copysign`), loads and reinterpretations only move bits and
+/// are left alone.
+fn nan_result(operator: &Operator) -> Option<Float> {
+    match operator {
+        Operator::F32Ceil
+        | Operator::F32Floor
+        | Operator::F32Trunc
+        | Operator::F32Nearest
+        | Operator::F32Sqrt
+        | Operator::F32Add
+        | Operator::F32Sub
+        | Operator::F32Mul
+        | Operator::F32Div
+        | Operator::F32Min
+        | Operator::F32Max
+        | Operator::F32DemoteF64 => Some(Float::F32),
+        Operator::F64Ceil
+        | Operator::F64Floor
+        | Operator::F64Trunc
+        | Operator::F64Nearest
+        | Operator::F64Sqrt
+        | Operator::F64Add
+        | Operator::F64Sub
+        | Operator::F64Mul
+        | Operator::F64Div
+        | Operator::F64Min
+        | Operator::F64Max
+        | Operator::F64PromoteF32 => Some(Float::F64),
+        _ => None,
+    }
+}
+
+/// `wasm` with the result of every float instruction that can produce a NaN canonicalized.
+/// Each function gets two scratch locals, an `f32` and an `f64`, to do it.
+pub(crate) fn canonicalize_nans(wasm: &[u8]) -> Result<Vec<u8>, String> {
+    let code = rewrite_code(wasm).map_err(|err| err.to_string())?;
+    let Some(code) = code else {
+        return Ok(wasm.to_vec());
+    };
+
+    let header = wasm.get(..8).ok_or("truncated module")?;
+    let mut out = header.to_vec();
+    let mut offset = header.len();
+    while offset < wasm.len() {
+        let id = wasm[offset];
+        let (size, size_len) = read_leb(&wasm[offset + 1..])?;
+        let start = offset + 1 + size_len;
+        let end = start + size as usize;
+        if end > wasm.len() {
+            return Err("truncated section".to_string());
+        }
+        if id == CODE_SECTION {
+            out.push(CODE_SECTION);
+            write_leb(&mut out, code.len() as u32);
+            out.extend_from_slice(&code);
+        } else {
+            out.extend_from_slice(&wasm[offset..end]);
+        }
+        offset = end;
+    }
+    Ok(out)
+}
+
+/// Content of the rewritten code section, `None` without one.
+fn rewrite_code(wasm: &[u8]) -> wasmparser::Result<Option<Vec<u8>>> {
+    let mut params = Vec::new();
+    // parameters of each function defined by the module, in the order of the code section
+    let mut functions = Vec::new();
+    let mut defined = 0;
+    let mut code = None;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::TypeSection(reader) => {
+                for func_type in reader.into_iter_err_on_gc_types() {
+                    params.push(func_type?.params().len() as u32);
+                }
+            }
+            Payload::FunctionSection(reader) => {
+                for type_index in reader {
+                    functions.push(
+                        params
+                            .get(type_index? as usize)
+                            .copied()
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+            Payload::CodeSectionStart { count, .. } => {
+                let mut content = Vec::new();
+                write_leb(&mut content, count);
+                code = Some(content);
+            }
+            Payload::CodeSectionEntry(body) => {
+                let params = functions.get(defined).copied().unwrap_or_default();
+                let rewritten = rewrite_body(&body, params)?;
+                defined += 1;
+                if let Some(content) = code.as_mut() {
+                    write_leb(content, rewritten.len() as u32);
+                    content.extend_from_slice(&rewritten);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(code)
+}
+
+/// The body of a function taking `params` parameters, with its two scratch locals declared
+/// last and a canonicalization after each instruction that may produce a NaN.
+fn rewrite_body(body: &FunctionBody, params: u32) -> wasmparser::Result<Vec<u8>> {
+    let mut locals = body.get_locals_reader()?;
+    let declarations = locals.original_position();
+    let mut declared = params;
+    for _ in 0..locals.get_count() {
+        declared += locals.read()?.0;
+    }
+    let (f32_scratch, f64_scratch) = (declared, declared + 1);
+
+    let range = body.range();
+    let bytes = body.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len() + bytes.len() / 4);
+    write_leb(&mut out, locals.get_count() + 2);
+    out.extend_from_slice(
+        &bytes[declarations - range.start..locals.original_position() - range.start],
+    );
+    out.extend_from_slice(&[1, F32, 1, F64]);
+
+    let mut operators = body.get_operators_reader()?;
+    let mut copied = locals.original_position();
+    let mut pending = None;
+    while !operators.eof() {
+        let (operator, offset) = operators.read_with_offset()?;
+        out.extend_from_slice(&bytes[copied - range.start..offset - range.start]);
+        copied = offset;
+        if let Some(float) = pending.take() {
+            canonicalize(&mut out, float, f32_scratch, f64_scratch);
+        }
+        pending = nan_result(&operator);
+    }
+    out.extend_from_slice(&bytes[copied - range.start..]);
+    Ok(out)
+}
+
+/// Replace the float on top of the stack with the canonical NaN if it is a NaN:
+/// `select(canonical, x, x != x)`.
+fn canonicalize(out: &mut Vec<u8>, float: Float, f32_scratch: u32, f64_scratch: u32) {
+    let scratch = match float {
+        Float::F32 => f32_scratch,
+        Float::F64 => f64_scratch,
+    };
+    out.push(LOCAL_SET);
+    write_leb(out, scratch);
+    match float {
+        Float::F32 => {
+            out.push(F32_CONST);
+            out.extend_from_slice(&f32::NAN.to_bits().to_le_bytes());
+        }
+        Float::F64 => {
+            out.push(F64_CONST);
+            out.extend_from_slice(&f64::NAN.to_bits().to_le_bytes());
+        }
+    }
+    for _ in 0..3 {
+        out.push(LOCAL_GET);
+        write_leb(out, scratch);
+    }
+    out.push(match float {
+        Float::F32 => F32_NE,
+        Float::F64 => F64_NE,
+    });
+    out.push(SELECT);
+}
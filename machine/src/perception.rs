@@ -0,0 +1,88 @@
+//! What a bot gets to see of the arena, see [`Perception`].
+
+use crate::rng::signed_unit;
+use crate::rules::Perception;
+
+/// Positions seen by a bot on a tick. Kept from one bot to the next to reuse the lists.
+#[derive(Default)]
+pub(crate) struct Sight {
+    pub self_position: [f32; 3],
+    /// The closest of `balls`.
+    pub ball: [f32; 3],
+    /// In ball order.
+    pub balls: Vec<[f32; 3]>,
+    /// In bot order.
+    pub teammates: Vec<[f32; 3]>,
+    /// In bot order, only those in view.
+    pub opponents: Vec<[f32; 3]>,
+}
+
+impl Sight {
+    /// Look at the arena as bot `index`, blurring the positions with noise drawn from `rng`,
+    /// which is left untouched without noise.
+    pub fn look(
+        &mut self,
+        perception: &Perception,
+        index: usize,
+        positions: &[[f32; 3]],
+        teams: &[u8],
+        balls: &[[f32; 3]],
+        rng: &mut u64,
+    ) {
+        self.self_position = positions[index];
+        self.balls.clear();
+        self.balls.extend_from_slice(balls);
+        self.teammates.clear();
+        self.opponents.clear();
+        for (other, position) in positions.iter().enumerate() {
+            if other == index {
+                continue;
+            }
+            if teams[other] == teams[index] {
+                self.teammates.push(*position);
+            } else if perception
+                .view_radius
+                .is_none_or(|radius| ground_distance(positions[index], *position) <= radius)
+            {
+                self.opponents.push(*position);
+            }
+        }
+
+        if perception.position_noise > 0.0 {
+            let mut blur = |position: &mut [f32; 3]| {
+                for value in position.iter_mut() {
+                    *value += signed_unit(rng) * perception.position_noise;
+                }
+            };
+            blur(&mut self.self_position);
+            self.balls.iter_mut().for_each(&mut blur);
+            self.teammates.iter_mut().for_each(&mut blur);
+            self.opponents.iter_mut().for_each(&mut blur);
+        }
+
+        self.ball = closest(self.self_position, &self.balls);
+    }
+}
+
+/// The position in `positions` closest to `from`, `from` itself when there is none.
+pub(crate) fn closest(from: [f32; 3], positions: &[[f32; 3]]) -> [f32; 3] {
+    let mut closest = from;
+    let mut closest_distance = f32::MAX;
+    for position in positions {
+        let dx = position[0] - from[0];
+        let dy = position[1] - from[1];
+        let dz = position[2] - from[2];
+        let distance = dx * dx + dy * dy + dz * dz;
+        if distance < closest_distance {
+            closest_distance = distance;
+            closest = *position;
+        }
+    }
+    closest
+}
+
+/// Distance on the ground.
+pub(crate) fn ground_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let (dx, dz) = (b[0] - a[0], b[2] - a[2]);
+    (dx * dx + dz * dz).sqrt()
+}
@@ -0,0 +1,188 @@
+//! Opponents written in Rust, to try a bot against without building a second one. They run
+//! behind the same [`crate::engine::BotInstance`] interface as compiled bots (see
+//! `engine/native.rs`), so they see the same observations and go through the same rules.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::perception::ground_distance;
+use crate::rng::splitmix;
+use crate::ARENA_HALF_SIZE;
+
+/// Ticks between two changes of direction of [`ReferenceBot::Random`], about a second.
+const RANDOM_WALK_PERIOD: u64 = 60;
+
+/// How far in front of its goal [`ReferenceBot::Goalkeeper`] waits.
+const KEEPER_DISTANCE: f32 = 2.5;
+/// Distance at which [`ReferenceBot::Goalkeeper`] leaves its post to clear the ball.
+const KEEPER_REACH: f32 = 4.0;
+
+/// A built-in opponent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReferenceBot {
+    /// Never moves.
+    Idle,
+    /// Runs straight at the ball.
+    Chaser,
+    /// Stays between the ball and its goal, and clears the ball when it comes close.
+    Goalkeeper,
+    /// Walks in a random direction, a new one every second, seeded by the match.
+    Random,
+    /// Plays its moves one after the other, over and over.
+    Scripted(Vec<ScriptedMove>),
+}
+
+/// A step of [`ReferenceBot::Scripted`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScriptedMove {
+    /// How long the move lasts.
+    pub ticks: u64,
+    /// Same as the float action of a bot, clamped to unit length.
+    pub impulse: [f32; 3],
+    #[serde(default)]
+    pub boost: bool,
+}
+
+impl ReferenceBot {
+    pub const NAMES: [&'static str; 5] = ["idle", "chaser", "goalkeeper", "random", "scripted"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReferenceBot::Idle => "idle",
+            ReferenceBot::Chaser => "chaser",
+            ReferenceBot::Goalkeeper => "goalkeeper",
+            ReferenceBot::Random => "random",
+            ReferenceBot::Scripted(_) => "scripted",
+        }
+    }
+
+    /// Going back and forth across the arena, the script of `scripted` when none is given.
+    pub fn default_script() -> Vec<ScriptedMove> {
+        [
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.0],
+            [-1.0, 0.0, 0.0],
+        ]
+        .into_iter()
+        .map(|impulse| ScriptedMove {
+            ticks: 90,
+            impulse,
+            boost: false,
+        })
+        .collect()
+    }
+
+    /// Impulse and boost for the tick, `observation` being laid out as for
+    /// `compute_actions_ext`.
+    pub(crate) fn act(&self, state: &mut ReferenceState, observation: &[f32]) -> ([f32; 3], bool) {
+        let get = |index: usize| observation.get(index).copied().unwrap_or(0.0);
+        let position = [get(0), get(1), get(2)];
+        let ball = [get(3), get(4), get(5)];
+        let team = get(6) as u8;
+        let tick = state.tick;
+        state.tick += 1;
+
+        match self {
+            ReferenceBot::Idle => ([0.0; 3], false),
+            ReferenceBot::Chaser => (towards(position, ball), false),
+            ReferenceBot::Goalkeeper => {
+                let goal = goal_of(team);
+                if ground_distance(position, ball) < KEEPER_REACH {
+                    return (towards(position, ball), false);
+                }
+                let to_ball = towards(goal, ball);
+                let post = [
+                    goal[0] + to_ball[0] * KEEPER_DISTANCE,
+                    position[1],
+                    goal[2] + to_ball[2] * KEEPER_DISTANCE,
+                ];
+                // slow down on the way in, not to overshoot the post
+                let speed = ground_distance(position, post).min(1.0);
+                (towards(position, post).map(|value| value * speed), false)
+            }
+            ReferenceBot::Random => {
+                if tick == 0 {
+                    // bots of both teams get the same seed, they should not walk alike
+                    state.rng ^= team as u64;
+                }
+                if tick.is_multiple_of(RANDOM_WALK_PERIOD) {
+                    state.rng = splitmix(state.rng);
+                }
+                let angle = (state.rng >> 40) as f32 / (1u64 << 24) as f32 * std::f32::consts::TAU;
+                ([angle.cos(), 0.0, angle.sin()], false)
+            }
+            ReferenceBot::Scripted(moves) => {
+                let length: u64 = moves.iter().map(|step| step.ticks).sum();
+                if length == 0 {
+                    return ([0.0; 3], false);
+                }
+                let mut at = tick % length;
+                for step in moves {
+                    if at < step.ticks {
+                        return (step.impulse, step.boost);
+                    }
+                    at -= step.ticks;
+                }
+                ([0.0; 3], false)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ReferenceBot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ReferenceBot {
+    type Err = String;
+
+    /// One of [`ReferenceBot::NAMES`], `scripted` playing [`ReferenceBot::default_script`].
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "idle" => Ok(ReferenceBot::Idle),
+            "chaser" => Ok(ReferenceBot::Chaser),
+            "goalkeeper" => Ok(ReferenceBot::Goalkeeper),
+            "random" => Ok(ReferenceBot::Random),
+            "scripted" => Ok(ReferenceBot::Scripted(ReferenceBot::default_script())),
+            _ => Err(format!(
+                "unknown reference bot `{}`, expected one of {}",
+                name,
+                ReferenceBot::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// What a reference bot remembers between ticks, saved with snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ReferenceState {
+    /// Calls to `compute_actions` so far.
+    pub tick: u64,
+    pub rng: u64,
+}
+
+/// Centre of the goal defended by `team`.
+fn goal_of(team: u8) -> [f32; 3] {
+    match team {
+        0 => [-ARENA_HALF_SIZE, 0.0, 0.0],
+        1 => [ARENA_HALF_SIZE, 0.0, 0.0],
+        2 => [0.0, 0.0, -ARENA_HALF_SIZE],
+        _ => [0.0, 0.0, ARENA_HALF_SIZE],
+    }
+}
+
+/// Unit direction from `from` to `to` on the ground, 0 when they are on top of each other.
+fn towards(from: [f32; 3], to: [f32; 3]) -> [f32; 3] {
+    let (dx, dz) = (to[0] - from[0], to[2] - from[2]);
+    let length = (dx * dx + dz * dz).sqrt();
+    if length < f32::EPSILON {
+        [0.0; 3]
+    } else {
+        [dx / length, 0.0, dz / length]
+    }
+}
@@ -0,0 +1,15 @@
+//! Randomness of the machine: seeded from the match, so that a replay draws the same values.
+
+/// SplitMix64, enough to pick directions, drop balls and blur observations.
+pub(crate) fn splitmix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Advance `rng` and draw a value uniform in [-1, 1) from it.
+pub(crate) fn signed_unit(rng: &mut u64) -> f32 {
+    *rng = splitmix(*rng);
+    (*rng >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
@@ -0,0 +1,333 @@
+/// Physical properties of the bots and the limits the machine enforces on their actions.
+/// The default is the body of rule set version 1, see [`BotBody::bounded`] for a tuned one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BotBody {
+    /// `None` for a density of 1, about 0.52 with the default radius.
+    pub mass: Option<f32>,
+    pub radius: f32,
+    pub friction: f32,
+    pub restitution: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    /// Speed the body is clamped to after every step, `None` for no limit.
+    pub max_speed: Option<f32>,
+    /// Magnitude of the impulse applied for a full action, the action being clamped to unit
+    /// length. `None` applies the action as it is, up to 1 along each axis.
+    pub max_impulse: Option<f32>,
+    pub energy: Option<Energy>,
+}
+
+/// Boost meter: while the boost bit of the action is set and enough energy is left, the
+/// impulse is multiplied and the meter drains, otherwise it regenerates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Energy {
+    pub capacity: f32,
+    pub regeneration_per_tick: f32,
+    pub boost_cost_per_tick: f32,
+    pub boost_multiplier: f32,
+}
+
+impl Default for BotBody {
+    fn default() -> Self {
+        BotBody {
+            mass: None,
+            radius: 0.5,
+            friction: 0.5,
+            restitution: 0.7,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            max_speed: None,
+            max_impulse: None,
+            energy: None,
+        }
+    }
+}
+
+impl BotBody {
+    /// Body of rule set version 2: damped, slowed down to 8 m/s and pushed by impulses of
+    /// at most 0.1, so bots tuned for version 1 move about ten times slower.
+    pub fn bounded() -> Self {
+        BotBody {
+            mass: Some(0.5),
+            linear_damping: 0.5,
+            angular_damping: 0.5,
+            max_speed: Some(8.0),
+            max_impulse: Some(0.1),
+            ..BotBody::default()
+        }
+    }
+}
+
+impl Default for Energy {
+    fn default() -> Self {
+        Energy {
+            capacity: 100.0,
+            regeneration_per_tick: 0.5,
+            boost_cost_per_tick: 2.0,
+            boost_multiplier: 3.0,
+        }
+    }
+}
+
+/// The ball, or the balls of a multi-ball match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BallBody {
+    /// `None` for a density of 1, about 0.52 with the default radius.
+    pub mass: Option<f32>,
+    pub radius: f32,
+    pub friction: f32,
+    /// Bounciness, from 0 to 1.
+    pub restitution: f32,
+    /// Drag, slowing the ball down as it flies and rolls.
+    pub linear_damping: f32,
+    /// Slows its spin down.
+    pub angular_damping: f32,
+    /// Magnus effect, curving the path of a spinning ball: the force is `magnus` times the
+    /// cross product of its angular velocity and its velocity. 0 turns it off.
+    pub magnus: f32,
+    /// Balls in play at once, each scoring on its own.
+    pub count: u32,
+    /// Height balls are dropped from, at the start and after a goal.
+    pub drop_height: f32,
+    /// Balls are dropped up to this far from their spot along both ground axes, at random
+    /// but seeded by the match. 0 drops them right on it.
+    pub drop_spread: f32,
+}
+
+impl Default for BallBody {
+    fn default() -> Self {
+        BallBody {
+            mass: None,
+            radius: 0.5,
+            friction: 0.5,
+            restitution: 0.7,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            magnus: 0.0,
+            count: 1,
+            drop_height: 10.5,
+            drop_spread: 0.0,
+        }
+    }
+}
+
+/// How simulated time goes by: every tick moves the physics `dt` seconds forward in
+/// `substeps` equal steps, and bots only decide every `decision_interval` ticks, their last
+/// action being held (applied again, boost included) in between.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    /// Seconds simulated per tick.
+    pub dt: f32,
+    /// Physics steps per tick, more of them make fast collisions more accurate.
+    pub substeps: u32,
+    /// Ticks from one `compute_actions` call of a bot to the next, 1 to decide every tick.
+    pub decision_interval: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            dt: 1.0 / 60.0,
+            substeps: 1,
+            decision_interval: 1,
+        }
+    }
+}
+
+impl Timing {
+    /// Seconds simulated by each physics step.
+    pub fn step_dt(&self) -> f32 {
+        self.dt / self.substeps.max(1) as f32
+    }
+
+    /// Whether the bots decide at the start of `tick` (counted from 0) or hold their action.
+    pub fn decides(&self, tick: u64) -> bool {
+        tick.is_multiple_of(self.decision_interval.max(1) as u64)
+    }
+}
+
+/// How well bots know the arena and how fast their decisions apply, for competitions that
+/// reward strategies coping with imperfect information. Everything is off by default and
+/// the noise is drawn from the match seed, so a match plays out the same every time.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Perception {
+    /// Ticks from a decision to the tick it is applied, the previous action (none at the
+    /// start) going on meanwhile.
+    pub action_delay: u32,
+    /// Every coordinate a bot observes, its own included, is off by up to this much.
+    pub position_noise: f32,
+    /// Opponents farther than this on the ground are left out of the observation.
+    pub view_radius: Option<f32>,
+}
+
+/// Optional gas model where the unused part of the per-tick allowance is kept, up to a cap,
+/// so that a bot can save compute on quiet ticks and spend it on a heavy one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GasBank {
+    /// Gas added to the reserve before every `compute_actions` call.
+    pub allowance_per_tick: u64,
+    /// The reserve never holds more than this.
+    pub capacity: u64,
+    /// Reserve at the start of the battle.
+    pub initial_reserve: u64,
+}
+
+impl Default for GasBank {
+    fn default() -> Self {
+        GasBank {
+            allowance_per_tick: 1_000,
+            capacity: 50_000,
+            initial_reserve: 0,
+        }
+    }
+}
+
+/// Resources a bot instance may use. Enforced by the native runtime, hitting one of them
+/// faults the bot for the tick instead of stopping the battle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BotLimits {
+    /// Linear memory, in 64 KiB pages, a bot can never grow past.
+    pub max_memory_pages: u32,
+    pub max_table_elements: u32,
+    /// Calls a bot can nest inside a single call from the host.
+    pub max_call_depth: u32,
+}
+
+impl Default for BotLimits {
+    fn default() -> Self {
+        // 10 bots at the memory limit take 80 MiB, which leaves room for the host within
+        // the 128Mi of the cartesi machine.
+        BotLimits {
+            max_memory_pages: 128,
+            max_table_elements: 10_000,
+            max_call_depth: 1_000,
+        }
+    }
+}
+
+/// Blob a bot can keep from one match to the next, see `Battle::set_bot_storage`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StorageLimits {
+    pub max_bytes: usize,
+}
+
+impl Default for StorageLimits {
+    fn default() -> Self {
+        StorageLimits { max_bytes: 4096 }
+    }
+}
+
+/// How much a bot can log, messages over the budget are truncated then dropped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LogLimits {
+    /// Bytes a bot may log during an update, however many calls it gets. `init` and
+    /// `on_match_end` each count as an update of their own.
+    pub max_bytes_per_tick: usize,
+    pub max_bytes_per_match: usize,
+}
+
+impl Default for LogLimits {
+    fn default() -> Self {
+        LogLimits {
+            max_bytes_per_tick: 256,
+            max_bytes_per_match: 16 * 1024,
+        }
+    }
+}
+
+/// Where the bots of a team start, given for team 0 (defending the -x goal). The other teams
+/// get the same layout rotated around the center of the arena, half a turn for team 1 and a
+/// quarter turn for the free-for-all teams on the z walls, so that no side is favoured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnLayout {
+    /// (x, z) of each slot, filled in order as bots join the team.
+    pub slots: Vec<[f32; 2]>,
+}
+
+impl Default for SpawnLayout {
+    fn default() -> Self {
+        SpawnLayout {
+            slots: vec![
+                [-9.0, 0.0],
+                [-9.0, -2.0],
+                [-9.0, 2.0],
+                [-9.0, -4.0],
+                [-9.0, 4.0],
+            ],
+        }
+    }
+}
+
+impl SpawnLayout {
+    /// (x, z) of the given slot for `team`, `None` when the team is full.
+    pub fn position(&self, team: u8, slot: usize) -> Option<[f32; 2]> {
+        self.slots
+            .get(slot)
+            .map(|slot| rotate_for_team(team, *slot))
+    }
+}
+
+/// Rotate a horizontal (x, z) vector given for team 0 to the side of `team`.
+fn rotate_for_team(team: u8, [x, z]: [f32; 2]) -> [f32; 2] {
+    match team {
+        0 => [x, z],
+        1 => [-x, -z],
+        2 => [-z, x],
+        _ => [z, -x],
+    }
+}
+
+/// The rule set of a battle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    /// Told to the bots so that they can adapt to a rule set, bump it when changing one.
+    /// The default rule set is version 1, version 2 plays with [`BotBody::bounded`].
+    pub version: u32,
+    /// Updates after which the battle is a draw.
+    pub max_ticks: u64,
+    pub timing: Timing,
+    pub perception: Perception,
+    pub bot: BotBody,
+    pub ball: BallBody,
+    /// Goals a team needs to win, the ball being dropped again after the others.
+    pub goals_to_win: u32,
+    pub spawn: SpawnLayout,
+    /// Impulse given to the bots of team 0 at the start, rotated like the spawn layout for
+    /// the other teams.
+    pub initial_impulse: [f32; 3],
+    pub limits: BotLimits,
+    /// When set, replaces the flat gas budget of every `compute_actions` call.
+    pub gas_bank: Option<GasBank>,
+    /// `None` drops every bot log, the logging imports are still available.
+    pub logs: Option<LogLimits>,
+    /// `None` disables storage: bots read an empty blob and can not write.
+    pub storage: Option<StorageLimits>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            version: 1,
+            max_ticks: 1_000_000,
+            timing: Timing::default(),
+            perception: Perception::default(),
+            bot: BotBody::default(),
+            ball: BallBody::default(),
+            goals_to_win: 1,
+            spawn: SpawnLayout::default(),
+            initial_impulse: [2.0, 0.0, 0.0],
+            limits: BotLimits::default(),
+            gas_bank: None,
+            logs: Some(LogLimits::default()),
+            storage: Some(StorageLimits::default()),
+        }
+    }
+}
+
+impl Rules {
+    pub fn initial_impulse(&self, team: u8) -> [f32; 3] {
+        let [x, y, z] = self.initial_impulse;
+        let [x, z] = rotate_for_team(team, [x, z]);
+        [x, y, z]
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use crate::{Battle, MatchMode, ModuleCache, Rules};
+
+/// One match of a series.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Leg {
+    /// Whether side A played as team 1 (spawning on the +x side) in this leg.
+    pub swapped: bool,
+    /// 0 for a draw, 1 when side A won, 2 when side B won.
+    pub winner: u8,
+}
+
+/// Aggregate of a series where every side played from both ends of the arena.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesResult {
+    pub legs: Vec<Leg>,
+    pub wins_a: u32,
+    pub wins_b: u32,
+    pub draws: u32,
+}
+
+impl SeriesResult {
+    /// 0 when the series is tied, 1 when side A won more legs, 2 when side B did.
+    pub fn winner(&self) -> u8 {
+        if self.wins_a > self.wins_b {
+            1
+        } else if self.wins_b > self.wins_a {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+/// Play side A against side B twice, swapping the ends of the arena for the second leg. As
+/// spawns and initial impulses are mirrored, both sides get exactly the same conditions.
+/// With a cache, each bot is compiled once for both legs.
+pub fn play_series(
+    side_a: &[Vec<u8>],
+    side_b: &[Vec<u8>],
+    rules: &Rules,
+    cache: Option<Arc<ModuleCache>>,
+) -> Result<SeriesResult, String> {
+    let mut result = SeriesResult::default();
+    for swapped in [false, true] {
+        let (team_a, team_b) = if swapped { (1, 0) } else { (0, 1) };
+
+        let mut battle = Battle::new_with_rules(MatchMode::Teams, rules.clone());
+        if let Some(cache) = &cache {
+            battle.set_module_cache(cache.clone());
+        }
+        for wasm in side_a {
+            battle.add_bot_to_team(&mut wasm.clone(), team_a)?;
+        }
+        for wasm in side_b {
+            battle.add_bot_to_team(&mut wasm.clone(), team_b)?;
+        }
+
+        verbose!(
+            "leg {} (sides swapped: {}) ...",
+            result.legs.len() + 1,
+            swapped
+        );
+        let winner = match battle.execute()? {
+            0 => 0,
+            team if team - 1 == team_a => 1,
+            _ => 2,
+        };
+        match winner {
+            1 => result.wins_a += 1,
+            2 => result.wins_b += 1,
+            _ => result.draws += 1,
+        }
+        result.legs.push(Leg { swapped, winner });
+    }
+    Ok(result)
+}
@@ -0,0 +1,84 @@
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::Action;
+use crate::fault::FaultCounts;
+use crate::gas::GasUsage;
+use crate::MAX_TEAMS;
+
+/// Bumped whenever the layout below changes, older snapshots are then rejected.
+pub(crate) const SNAPSHOT_VERSION: u32 = 10;
+
+/// Value of a mutable exported global of a bot instance.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) enum GlobalValue {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+}
+
+impl GlobalValue {
+    /// The same value, with any NaN replaced by the canonical one so that snapshots do not
+    /// depend on the engine that ran the bot.
+    pub fn canonical(self) -> GlobalValue {
+        match self {
+            GlobalValue::F32(bits) if f32::from_bits(bits).is_nan() => {
+                GlobalValue::F32(f32::NAN.to_bits())
+            }
+            GlobalValue::F64(bits) if f64::from_bits(bits).is_nan() => {
+                GlobalValue::F64(f64::NAN.to_bits())
+            }
+            value => value,
+        }
+    }
+}
+
+/// State of a bot instance between two calls.
+///
+/// Only exported globals are reachable from the host, so bots are compiled with all their
+/// mutable globals exported (see [`crate::globals`]): the linear memory and the exported
+/// globals, metering counters included, are then enough to resume the bot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ModuleState {
+    pub memory: Vec<u8>,
+    pub globals: Vec<(String, GlobalValue)>,
+    pub observation_pointer: Option<u32>,
+    pub action_pointer: Option<u32>,
+    /// Bytes the bot logged so far, counted against the per match cap.
+    pub log_bytes: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct BotSnapshot {
+    pub module: ModuleState,
+    pub team: u8,
+    pub energy: f32,
+    pub gas_reserve: u64,
+    pub faults: FaultCounts,
+    pub gas: GasUsage,
+    pub action: Action,
+    /// Decisions not applied yet, with the tick they take effect.
+    pub pending: Vec<(u64, Action)>,
+    pub noise: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    pub version: u32,
+    pub tick: u64,
+    /// Per ball.
+    pub last_touch: Vec<Option<usize>>,
+    pub scores: [u32; MAX_TEAMS],
+    pub drop_rng: u64,
+    pub bots: Vec<BotSnapshot>,
+    pub rigid_body_set: RigidBodySet,
+    pub collider_set: ColliderSet,
+    pub integration_parameters: IntegrationParameters,
+    pub island_manager: IslandManager,
+    pub broad_phase: BroadPhaseMultiSap,
+    pub narrow_phase: NarrowPhase,
+    pub impulse_joint_set: ImpulseJointSet,
+    pub multibody_joint_set: MultibodyJointSet,
+    pub ccd_solver: CCDSolver,
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+
+use rapier3d::parry::query;
+use rapier3d::prelude::*;
+
+use crate::ObjectType;
+
+/// Why the starting positions of a battle were rejected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpawnError {
+    /// The bot overlaps the ground, the roof, a wall or a goal.
+    Arena { bot: usize },
+    /// The bot overlaps the ball.
+    Ball { bot: usize },
+    /// The two bots overlap each other.
+    Bot { bot: usize, other: usize },
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::Arena { bot } => write!(f, "bot {} spawns inside the arena", bot),
+            SpawnError::Ball { bot } => write!(f, "bot {} spawns on the ball", bot),
+            SpawnError::Bot { bot, other } => {
+                write!(f, "bot {} spawns on bot {}", bot, other)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// Check that no bot collider intersects anything else.
+pub(crate) fn validate(colliders: &ColliderSet) -> Result<(), SpawnError> {
+    for (handle, collider) in colliders.iter() {
+        let (ObjectType::Bot, bot) = ObjectType::from_user_data(collider.user_data) else {
+            continue;
+        };
+        for (other_handle, other) in colliders.iter() {
+            if other_handle == handle {
+                continue;
+            }
+            let intersects = query::intersection_test(
+                collider.position(),
+                collider.shape(),
+                other.position(),
+                other.shape(),
+            )
+            .unwrap_or(false);
+            if !intersects {
+                continue;
+            }
+            return Err(match ObjectType::from_user_data(other.user_data) {
+                (ObjectType::Ball, _) => SpawnError::Ball { bot },
+                (ObjectType::Bot, other) => SpawnError::Bot { bot, other },
+                _ => SpawnError::Arena { bot },
+            });
+        }
+    }
+    Ok(())
+}
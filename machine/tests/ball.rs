@@ -0,0 +1,135 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use machine::{Action, BallBody, Battle, MatchMode, Observation, Position, Rules};
+
+fn battle(ball: BallBody, match_id: &[u8]) -> Battle {
+    let mut battle = Battle::new_with_rules(
+        MatchMode::Teams,
+        Rules {
+            ball,
+            ..Rules::default()
+        },
+    );
+    battle.set_match_id(match_id);
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+}
+
+fn balls(battle: &Battle) -> Vec<Position> {
+    (0..battle.num_balls())
+        .map(|index| battle.get_ball_at(index))
+        .collect()
+}
+
+#[test]
+fn balls_are_dropped_side_by_side() {
+    let mut battle = battle(
+        BallBody {
+            count: 3,
+            ..BallBody::default()
+        },
+        b"a",
+    );
+    battle.init().unwrap();
+
+    let balls = balls(&battle);
+    assert_eq!(balls.len(), 3);
+    for (ball, z) in balls.iter().zip([-1.5, 0.0, 1.5]) {
+        assert_eq!((ball.x, ball.y, ball.z), (0.0, 10.5, z));
+    }
+    assert_eq!(battle.get_ball(), balls[0]);
+}
+
+#[test]
+fn observations_list_every_ball() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut battle = Battle::new_with_rules(
+        MatchMode::Teams,
+        Rules {
+            ball: BallBody {
+                count: 2,
+                ..BallBody::default()
+            },
+            ..Rules::default()
+        },
+    );
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    let recorder = seen.clone();
+    battle
+        .add_controller(move |observation: &Observation| {
+            recorder
+                .lock()
+                .unwrap()
+                .push((observation.ball, observation.balls.to_vec()));
+            Action::NEUTRAL
+        })
+        .unwrap();
+    battle.init().unwrap();
+    let observation = battle.observation(1);
+    battle.update();
+
+    let (closest, balls) = seen.lock().unwrap().remove(0);
+    assert_eq!(balls.len(), 2);
+    assert!(balls.contains(&closest));
+    // ..., energy, gas, B, B * ball.xyz
+    assert_eq!(observation[observation.len() - 7], 2.0);
+    assert_eq!(&observation[observation.len() - 6..], balls.concat());
+}
+
+#[test]
+fn drop_spread_is_seeded_by_the_match() {
+    let spread = BallBody {
+        drop_spread: 3.0,
+        ..BallBody::default()
+    };
+    let dropped = |match_id: &[u8]| {
+        let mut battle = battle(spread, match_id);
+        battle.init().unwrap();
+        battle.get_ball()
+    };
+
+    assert_eq!(dropped(b"a"), dropped(b"a"));
+    assert_ne!(dropped(b"a"), dropped(b"b"));
+    let ball = dropped(b"a");
+    assert!(ball.x.abs() <= 3.0 && ball.z.abs() <= 3.0);
+    assert_eq!(ball.y, 10.5);
+}
+
+#[test]
+fn drag_slows_the_ball_down() {
+    let fall = |ball: BallBody| {
+        let mut battle = battle(ball, b"a");
+        battle.init().unwrap();
+        for _ in 0..30 {
+            battle.update();
+        }
+        battle.get_ball().y
+    };
+
+    let dragged = BallBody {
+        linear_damping: 5.0,
+        ..BallBody::default()
+    };
+    assert!(fall(dragged) > fall(BallBody::default()));
+}
+
+#[test]
+fn snapshots_need_as_many_balls() {
+    let mut two = battle(
+        BallBody {
+            count: 2,
+            ..BallBody::default()
+        },
+        b"a",
+    );
+    two.init().unwrap();
+    let snapshot = two.snapshot().unwrap();
+
+    let mut one = battle(BallBody::default(), b"a");
+    one.init().unwrap();
+    let err = one.restore(&snapshot).unwrap_err();
+    assert!(err.contains("2 balls"), "{}", err);
+}
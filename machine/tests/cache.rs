@@ -0,0 +1,159 @@
+//! The interpreter compiles nothing and ignores the cache.
+#![cfg(not(feature = "interpreter"))]
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use machine::{Battle, BotLimits, MatchMode, ModuleCache, Rules};
+
+/// Pushes towards +x every tick.
+const RUNNING_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 16744576))
+"#;
+
+/// An empty directory of its own for each test.
+fn directory(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("machine-cache-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    directory
+}
+
+fn files(directory: &PathBuf) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Play a few ticks with `bot` as the first bot, compiled through `cache`, and tell where
+/// it ended up.
+fn play(rules: &Rules, cache: &Arc<ModuleCache>, mut bot: Vec<u8>) -> f32 {
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules.clone());
+    battle.set_module_cache(cache.clone());
+    battle.add_bot(&mut bot).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    for _ in 0..20 {
+        battle.update();
+    }
+    battle.get_bot(0).x
+}
+
+#[test]
+fn a_bot_is_compiled_once() {
+    let rules = Rules::default();
+    let cache = Arc::new(ModuleCache::new());
+
+    play(&rules, &cache, common::idle_bot());
+    // the idle opponent is the same module
+    assert_eq!(cache.len(), 1);
+    play(&rules, &cache, common::idle_bot());
+    assert_eq!(cache.len(), 1);
+    play(&rules, &cache, wat::parse_str(RUNNING_BOT).unwrap());
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn changing_the_limits_compiles_again() {
+    let cache = Arc::new(ModuleCache::new());
+    play(&Rules::default(), &cache, common::idle_bot());
+
+    let rules = Rules {
+        limits: BotLimits {
+            max_call_depth: 500,
+            ..BotLimits::default()
+        },
+        ..Rules::default()
+    };
+    play(&rules, &cache, common::idle_bot());
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn artifacts_survive_on_disk() {
+    let rules = Rules::default();
+    let directory = directory("disk");
+    let running = wat::parse_str(RUNNING_BOT).unwrap();
+    let uncached = play(&rules, &Arc::new(ModuleCache::new()), running.clone());
+
+    play(
+        &rules,
+        &Arc::new(ModuleCache::with_directory(&directory)),
+        running.clone(),
+    );
+    let written = files(&directory);
+    assert_eq!(written.len(), 2);
+    assert!(written.iter().all(|file| !file.ends_with(".partial")));
+
+    let reloaded = Arc::new(ModuleCache::with_directory(&directory));
+    assert!(reloaded.is_empty());
+    assert_eq!(play(&rules, &reloaded, running), uncached);
+    assert_eq!(reloaded.len(), 2);
+    assert_eq!(files(&directory), written);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn an_artifact_moved_under_another_key_is_not_loaded() {
+    let rules = Rules::default();
+    let idle = directory("idle");
+    let running = directory("running");
+    play(
+        &rules,
+        &Arc::new(ModuleCache::with_directory(&idle)),
+        common::idle_bot(),
+    );
+    play(
+        &rules,
+        &Arc::new(ModuleCache::with_directory(&running)),
+        wat::parse_str(RUNNING_BOT).unwrap(),
+    );
+
+    // put the artifact of the running bot where the idle bot's is looked up
+    let idle_key = files(&idle).remove(0);
+    let running_key = files(&running)
+        .into_iter()
+        .find(|key| *key != idle_key)
+        .unwrap();
+    fs::copy(running.join(&running_key), idle.join(&idle_key)).unwrap();
+
+    let swapped = Arc::new(ModuleCache::with_directory(&idle));
+    let moved = play(&rules, &swapped, common::idle_bot());
+    let stayed = play(&rules, &Arc::new(ModuleCache::new()), common::idle_bot());
+    assert_eq!(moved, stayed);
+
+    fs::remove_dir_all(&idle).unwrap();
+    fs::remove_dir_all(&running).unwrap();
+}
+
+#[test]
+fn artifacts_are_only_loaded_with_the_secret_they_were_signed_with() {
+    let rules = Rules::default();
+    let directory = directory("secret");
+    let signed =
+        |secret: &str| Arc::new(ModuleCache::with_directory(&directory).with_secret(secret));
+    play(&rules, &signed("a"), common::idle_bot());
+    let key = files(&directory).remove(0);
+    let written = fs::read(directory.join(&key)).unwrap();
+
+    // loaded as it is by a run knowing the secret
+    play(&rules, &signed("a"), common::idle_bot());
+    assert_eq!(fs::read(directory.join(&key)).unwrap(), written);
+    // compiled and signed again by any other
+    play(&rules, &signed("b"), common::idle_bot());
+    assert_ne!(fs::read(directory.join(&key)).unwrap(), written);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,13 @@
+/// A bot that never moves, using the legacy `compute_actions` entry point.
+pub const IDLE_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 8421504))
+"#;
+
+pub fn idle_bot() -> Vec<u8> {
+    wat::parse_str(IDLE_BOT).unwrap()
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use machine::{Battle, GasBank, MatchMode, Rules};
+use sha2::{Digest, Sha256};
+
+/// Keeps the context it is given at 1024 and stores it once the match is over.
+const CONTEXT_BOT: &str = r#"
+(module
+  (import "env" "storage_write" (func $write (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (global $len (mut i32) (i32.const 0))
+  (func (export "context_buffer") (param i32) (result i32)
+    i32.const 1024)
+  (func (export "init_with_context") (param i32)
+    (global.set $len (local.get 0)))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 8421504)
+  (func (export "on_match_end") (param i32)
+    (drop (call $write (i32.const 1024) (global.get $len)))))
+"#;
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The context given to the first bot, in a battle against one bot per other team.
+fn context(mode: MatchMode, rules: Rules, opponents: usize) -> (Battle, Vec<u8>) {
+    let mut battle = Battle::new_with_rules(mode, rules);
+    battle
+        .add_bot(&mut wat::parse_str(CONTEXT_BOT).unwrap())
+        .unwrap();
+    for _ in 0..opponents {
+        battle.add_bot(&mut common::idle_bot()).unwrap();
+    }
+    battle.set_match_id(b"context");
+    battle.init().unwrap();
+    battle.end(0);
+    let context = battle.get_bot_storage(0);
+    (battle, context)
+}
+
+#[test]
+fn the_context_follows_the_documented_layout() {
+    let rules = Rules::default();
+    let (battle, context) = context(MatchMode::Teams, rules.clone(), 1);
+
+    assert_eq!(context.len(), 124 + 32);
+    assert_eq!(u32_at(&context, 0), 1);
+    let match_id = Sha256::digest(b"context");
+    assert_eq!(&context[8..40], match_id.as_slice());
+    assert_eq!(&context[4..8], &match_id[..4]);
+    assert_eq!(hex(&context[40..72]), battle.get_bot_hash(0));
+    assert_eq!(u32_at(&context, 72), 0);
+    assert_eq!(u32_at(&context, 76), rules.version);
+    assert_eq!(u64_at(&context, 80), rules.max_ticks);
+    assert!(u64_at(&context, 88) > 0);
+    assert!(u64_at(&context, 96) > 0);
+    assert_eq!(u64_at(&context, 104), 0);
+    assert!(u64_at(&context, 112) > 0);
+    assert_eq!(u32_at(&context, 120), 1);
+    assert_eq!(hex(&context[124..156]), battle.get_bot_hash(1));
+}
+
+#[test]
+fn the_context_tells_the_gas_bank() {
+    let bank = GasBank {
+        allowance_per_tick: 700,
+        capacity: 9_000,
+        initial_reserve: 0,
+    };
+    let rules = Rules {
+        gas_bank: Some(bank),
+        ..Rules::default()
+    };
+    let (_, context) = context(MatchMode::Teams, rules, 1);
+
+    assert_eq!(u64_at(&context, 96), 700);
+    assert_eq!(u64_at(&context, 104), 9_000);
+}
+
+#[test]
+fn the_context_lists_every_opponent() {
+    let (battle, context) = context(MatchMode::FreeForAll, Rules::default(), 3);
+
+    assert_eq!(context.len(), 124 + 3 * 32);
+    assert_eq!(u32_at(&context, 120), 3);
+    for opponent in 0..3 {
+        let start = 124 + opponent * 32;
+        assert_eq!(
+            hex(&context[start..start + 32]),
+            battle.get_bot_hash(opponent + 1)
+        );
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use machine::{Action, Battle, Controller, Fault, MatchContext, Observation};
+
+const TICKS: u64 = 10;
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn battles_can_move_to_another_thread() {
+    assert_send::<Battle>();
+}
+
+#[test]
+fn closure_plays_a_bot() {
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+        .add_controller(|_: &Observation| Action {
+            impulse: [0.0, 0.0, 1.0],
+            boost: false,
+        })
+        .unwrap();
+    battle.init().unwrap();
+    let start = battle.get_bot(1);
+    for _ in 0..TICKS {
+        battle.update();
+    }
+
+    assert!(battle.get_bot(1).z > start.z);
+    assert_eq!(battle.bot_faults(1).total(), 0);
+}
+
+/// Remembers what the battle told it.
+#[derive(Default)]
+struct Recorder {
+    team: Option<u8>,
+    ticks: u64,
+    result: Option<u32>,
+}
+
+struct RecordingController(Arc<Mutex<Recorder>>);
+
+impl Controller for RecordingController {
+    fn init(&mut self, context: &MatchContext) -> Result<(), Fault> {
+        self.0.lock().unwrap().team = Some(context.team);
+        Ok(())
+    }
+
+    fn compute_actions(&mut self, observation: &Observation) -> Result<Action, Fault> {
+        let mut recorder = self.0.lock().unwrap();
+        recorder.ticks += 1;
+        assert_eq!(observation.opponents.len(), 1);
+        Ok(Action::NEUTRAL)
+    }
+
+    fn match_end(&mut self, result: u32) -> Result<(), Fault> {
+        self.0.lock().unwrap().result = Some(result);
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "recorder"
+    }
+}
+
+#[test]
+fn controller_is_told_about_the_match() {
+    let recorder = Arc::new(Mutex::new(Recorder::default()));
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+        .add_controller(RecordingController(recorder.clone()))
+        .unwrap();
+    battle.init().unwrap();
+    for _ in 0..TICKS {
+        battle.update();
+    }
+    battle.end(0);
+
+    let recorder = recorder.lock().unwrap();
+    assert_eq!(recorder.team, Some(1));
+    assert_eq!(recorder.ticks, TICKS);
+    assert_eq!(recorder.result, Some(0));
+    assert_eq!(
+        battle.get_bot_hash(1),
+        machine::bot_hash(b"controller:recorder")
+    );
+}
+
+#[test]
+fn controller_faults_are_counted() {
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+        .add_controller(|_: &Observation| Action {
+            impulse: [f32::NAN, 0.0, 0.0],
+            boost: false,
+        })
+        .unwrap();
+    battle.init().unwrap();
+    for _ in 0..TICKS {
+        battle.update();
+    }
+
+    assert_eq!(battle.bot_faults(1).invalid_action, TICKS);
+}
+
+#[test]
+fn controllers_can_not_be_snapshotted() {
+    let mut battle = Battle::new();
+    for _ in 0..2 {
+        battle
+            .add_controller(|_: &Observation| Action::NEUTRAL)
+            .unwrap();
+    }
+    battle.init().unwrap();
+
+    let err = battle.snapshot().unwrap_err();
+    assert!(err.contains("can not be snapshotted"), "{}", err);
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use machine::{Battle, Energy, MatchMode, Rules};
+
+/// A bot writing `x y z` (wat float literals) as its float action every tick.
+fn float_action_bot(x: &str, y: &str, z: &str) -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "init") (param i32))
+  (func (export "observation_buffer") (param i32) (result i32)
+    i32.const 0)
+  (func (export "action_buffer") (param i32) (result i32)
+    i32.const 1024)
+  (func (export "compute_actions_ext") (param i32) (result i32)
+    (f32.store (i32.const 1024) (f32.const {x}))
+    (f32.store (i32.const 1028) (f32.const {y}))
+    (f32.store (i32.const 1032) (f32.const {z}))
+    i32.const 0))
+"#
+    ))
+    .unwrap()
+}
+
+/// A bot trapping as soon as its observation holds NaN or an infinity.
+const FINITE_OBSERVATION_BOT: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "init") (param i32))
+  (func (export "observation_buffer") (param i32) (result i32)
+    i32.const 0)
+  (func (export "compute_actions_ext") (param $len i32) (result i32)
+    (local $i i32)
+    (local $value f32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $value (f32.load (i32.shl (local.get $i) (i32.const 2))))
+        ;; value - value is 0 unless value is NaN or an infinity
+        (if (f32.ne (f32.sub (local.get $value) (local.get $value)) (f32.const 0))
+          (then unreachable))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    i32.const 8421504))
+"#;
+
+/// Computes a NaN from a signaling one and another from `sqrt(-1)` in `init`, and stores
+/// their bits once the match is over.
+const NAN_BOT: &str = r#"
+(module
+  (import "env" "storage_write" (func $write (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "init") (param i32)
+    (f32.store (i32.const 0)
+      (f32.add (f32.reinterpret_i32 (i32.const 0x7fa00001)) (f32.const 1)))
+    (f64.store (i32.const 4) (f64.sqrt (f64.const -1))))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 8421504)
+  (func (export "on_match_end") (param i32)
+    (drop (call $write (i32.const 0) (i32.const 12)))))
+"#;
+
+const TICKS: u64 = 10;
+
+/// Position of bot 0 after [`TICKS`] updates against an idle bot.
+fn play(mut bot: Vec<u8>) -> (Battle, machine::Position) {
+    let mut battle = Battle::new();
+    battle.add_bot(&mut bot).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    for _ in 0..TICKS {
+        battle.update();
+    }
+    let position = battle.get_bot(0);
+    (battle, position)
+}
+
+#[test]
+fn finite_float_action_moves_the_bot() {
+    let (_, idle) = play(common::idle_bot());
+    let (battle, moved) = play(float_action_bot("1", "0", "0"));
+
+    assert_eq!(battle.bot_faults(0).total(), 0);
+    assert!(
+        moved.x > idle.x,
+        "{:?} should be ahead of {:?}",
+        moved,
+        idle
+    );
+}
+
+#[test]
+fn nan_action_is_no_impulse_and_a_fault() {
+    let (_, idle) = play(common::idle_bot());
+    for (x, y, z) in [
+        ("nan", "0", "0"),
+        ("1", "nan:0x200000", "0"),
+        ("1", "0", "-nan"),
+    ] {
+        let (battle, position) = play(float_action_bot(x, y, z));
+
+        let faults = battle.bot_faults(0);
+        assert_eq!(faults.invalid_action, TICKS);
+        assert_eq!(faults.total(), TICKS);
+        assert_eq!(position, idle, "action ({}, {}, {})", x, y, z);
+    }
+}
+
+#[test]
+fn infinite_action_is_no_impulse_and_a_fault() {
+    let (_, idle) = play(common::idle_bot());
+    for (x, y, z) in [("inf", "0", "0"), ("0", "-inf", "0")] {
+        let (battle, position) = play(float_action_bot(x, y, z));
+
+        assert_eq!(battle.bot_faults(0).invalid_action, TICKS);
+        assert_eq!(position, idle, "action ({}, {}, {})", x, y, z);
+    }
+}
+
+#[test]
+fn observations_are_finite() {
+    let mut rules = Rules::default();
+    rules.bot.energy = Some(Energy {
+        capacity: f32::INFINITY,
+        ..Energy::default()
+    });
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle
+        .add_bot(&mut wat::parse_str(FINITE_OBSERVATION_BOT).unwrap())
+        .unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    for _ in 0..TICKS {
+        battle.update();
+    }
+
+    // the meter itself is infinite, the bot sees 0 instead
+    assert_eq!(battle.get_bot_energy(0), f32::INFINITY);
+    assert_eq!(battle.bot_faults(0).total(), 0);
+}
+
+#[test]
+fn computed_nans_are_canonical() {
+    let mut battle = Battle::new();
+    battle
+        .add_bot(&mut wat::parse_str(NAN_BOT).unwrap())
+        .unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    battle.end(0);
+
+    let stored = battle.get_bot_storage(0);
+    assert_eq!(&stored[..4], f32::NAN.to_bits().to_le_bytes());
+    assert_eq!(&stored[4..], f64::NAN.to_bits().to_le_bytes());
+}
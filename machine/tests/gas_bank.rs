@@ -0,0 +1,82 @@
+mod common;
+
+use machine::{Battle, GasBank, MatchMode, Rules};
+
+/// A bot looping until it runs out of gas.
+const SPINNING_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (loop $spin (br $spin))
+    i32.const 8421504))
+"#;
+
+const BANK: GasBank = GasBank {
+    allowance_per_tick: 1_000,
+    capacity: 3_500,
+    initial_reserve: 0,
+};
+
+fn battle(bank: GasBank, mut bot: Vec<u8>) -> Battle {
+    let rules = Rules {
+        gas_bank: Some(bank),
+        ..Rules::default()
+    };
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.add_bot(&mut bot).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    battle
+}
+
+#[test]
+fn unused_gas_accrues_in_the_reserve() {
+    let mut battle = battle(BANK, common::idle_bot());
+
+    for tick in 1..=3 {
+        battle.update();
+        let used = battle.bot_gas(0).total;
+        assert_eq!(battle.get_bot_gas_reserve(0), tick * 1_000 - used);
+    }
+}
+
+#[test]
+fn the_reserve_is_capped() {
+    let mut battle = battle(BANK, common::idle_bot());
+
+    for _ in 0..10 {
+        battle.update();
+    }
+
+    // topped up to the capacity before the call, minus what the call used
+    let per_call = battle.bot_gas(0).max;
+    assert_eq!(battle.get_bot_gas_reserve(0), 3_500 - per_call);
+}
+
+#[test]
+fn a_bot_spending_everything_lives_on_its_allowance() {
+    let mut battle = battle(BANK, wat::parse_str(SPINNING_BOT).unwrap());
+
+    for _ in 0..5 {
+        battle.update();
+    }
+
+    assert_eq!(battle.get_bot_gas_reserve(0), 0);
+    assert_eq!(battle.bot_faults(0).out_of_gas, 5);
+    assert_eq!(battle.bot_gas(0).total, 5 * 1_000);
+}
+
+#[test]
+fn the_initial_reserve_is_capped() {
+    let battle = battle(
+        GasBank {
+            initial_reserve: 10_000,
+            ..BANK
+        },
+        common::idle_bot(),
+    );
+
+    assert_eq!(battle.get_bot_gas_reserve(0), 3_500);
+}
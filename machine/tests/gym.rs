@@ -0,0 +1,148 @@
+use machine::{Action, Env, EnvConfig, Opponent, ReferenceBot, RewardShaping, VecEnv};
+
+const RIGHT: Action = Action {
+    impulse: [1.0, 0.0, 0.0],
+    boost: false,
+};
+
+fn idle_config() -> EnvConfig {
+    EnvConfig {
+        opponents: vec![Opponent::Reference(ReferenceBot::Idle)],
+        ..EnvConfig::default()
+    }
+}
+
+#[test]
+fn reset_starts_a_match() {
+    let mut env = Env::new(idle_config()).unwrap();
+    assert!(env.step(RIGHT).is_err());
+
+    let observation = env.reset(1).unwrap();
+    // self.xyz, ball.xyz, team, no teammate, 1 opponent, energy, gas, 1 ball
+    assert_eq!(observation.len(), 3 + 3 + 1 + 1 + 1 + 3 + 1 + 1 + 1 + 3);
+    assert_eq!(observation[6], 0.0);
+    assert_eq!(env.battle().unwrap().get_tick(), 0);
+}
+
+#[test]
+fn agent_moves_with_its_action() {
+    let mut env = Env::new(idle_config()).unwrap();
+    let start = env.reset(1).unwrap();
+    let mut step = env.step(RIGHT).unwrap();
+    for _ in 0..10 {
+        step = env.step(RIGHT).unwrap();
+    }
+
+    assert!(step.observation[0] > start[0]);
+    assert_eq!(step.info.tick, 11);
+    assert!(!step.done);
+}
+
+#[test]
+fn same_seed_same_match() {
+    let play = |seed: u64| {
+        let mut env = Env::new(EnvConfig {
+            opponents: vec![Opponent::Reference(ReferenceBot::Random)],
+            ..EnvConfig::default()
+        })
+        .unwrap();
+        env.reset(seed).unwrap();
+        let mut observation = Vec::new();
+        for _ in 0..30 {
+            observation = env.step(RIGHT).unwrap().observation;
+        }
+        observation
+    };
+
+    assert_eq!(play(7), play(7));
+    assert_ne!(play(7), play(8));
+}
+
+#[test]
+fn shaped_reward() {
+    let mut env = Env::new(EnvConfig {
+        reward: RewardShaping {
+            goal: 0.0,
+            time: -1.0,
+            ..RewardShaping::default()
+        },
+        ..idle_config()
+    })
+    .unwrap();
+    env.reset(1).unwrap();
+
+    assert_eq!(env.step(RIGHT).unwrap().reward, -1.0);
+}
+
+#[test]
+fn every_goal_is_rewarded() {
+    let mut config = EnvConfig {
+        opponents: vec![Opponent::Reference(ReferenceBot::Chaser)],
+        ..EnvConfig::default()
+    };
+    config.rules.goals_to_win = 2;
+    let mut env = Env::new(config).unwrap();
+    env.reset(1).unwrap();
+    let mut rewards = Vec::new();
+    loop {
+        let step = env.step(Action::NEUTRAL).unwrap();
+        if step.reward != 0.0 {
+            rewards.push(step.reward);
+        }
+        if step.done {
+            assert_ne!(step.info.winner, 0);
+            break;
+        }
+    }
+
+    // only goals count by default
+    let battle = env.battle().unwrap();
+    let (ours, theirs) = (battle.get_team_score(0), battle.get_team_score(1));
+    assert!(ours.max(theirs) == 2 && ours + theirs > 1);
+    assert_eq!(rewards.len() as u32, ours + theirs);
+    assert_eq!(rewards.iter().sum::<f32>(), ours as f32 - theirs as f32);
+}
+
+#[test]
+fn matches_end_after_max_ticks() {
+    let mut config = idle_config();
+    config.rules.max_ticks = 5;
+    let mut env = Env::new(config).unwrap();
+    env.reset(1).unwrap();
+    let mut steps = 0;
+    let last = loop {
+        let step = env.step(Action::NEUTRAL).unwrap();
+        steps += 1;
+        if step.done {
+            break step;
+        }
+    };
+
+    assert_eq!(steps, 6);
+    assert!(last.info.truncated);
+    assert_eq!(last.info.winner, 0);
+    assert!(env.step(Action::NEUTRAL).is_err());
+}
+
+#[test]
+fn vectorized_envs_reset_on_their_own() {
+    let mut config = idle_config();
+    config.rules.max_ticks = 3;
+    let mut envs = VecEnv::new(config, 2).unwrap();
+    let observations = envs.reset(1).unwrap();
+    assert_eq!(observations.len(), 2);
+    assert!(envs.step(&[RIGHT]).is_err());
+
+    let mut steps = Vec::new();
+    for _ in 0..4 {
+        steps = envs.step(&[RIGHT, RIGHT]).unwrap();
+    }
+
+    for step in steps {
+        assert!(step.done);
+        assert!(step.info.final_observation.is_some());
+    }
+    // a new match has started
+    assert_eq!(envs.envs()[0].battle().unwrap().get_tick(), 0);
+    assert!(envs.step(&[RIGHT, RIGHT]).is_ok());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use machine::{Battle, HumanController};
+
+#[test]
+fn human_steers_the_bot_between_updates() {
+    let human = HumanController::new();
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    let index = battle.add_human(&human).unwrap();
+    battle.init().unwrap();
+
+    human.set_action(0.0, 0.0, 1.0, false);
+    let start = battle.get_bot(index);
+    for _ in 0..10 {
+        battle.update();
+    }
+    let turn = battle.get_bot(index);
+    assert!(turn.z > start.z);
+
+    human.set_action(0.0, 0.0, -1.0, false);
+    for _ in 0..30 {
+        battle.update();
+    }
+    assert!(battle.get_bot(index).z < turn.z);
+    assert_eq!(battle.bot_faults(index).total(), 0);
+    assert_eq!(
+        battle.get_bot_hash(index),
+        machine::bot_hash(b"controller:human")
+    );
+}
+
+#[test]
+fn human_joins_the_given_team() {
+    let human = HumanController::new();
+    let mut battle = Battle::new();
+    battle.add_human_to_team(&human, 1).unwrap();
+    battle.add_bot_to_team(&mut common::idle_bot(), 0).unwrap();
+
+    assert_eq!(battle.get_bot_team(0), 1);
+}
+
+#[test]
+fn human_can_be_steered_from_another_thread() {
+    let human = HumanController::new();
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    let index = battle.add_human(&human).unwrap();
+    battle.init().unwrap();
+
+    let remote = human.clone();
+    std::thread::spawn(move || remote.set_action(0.0, 0.0, 1.0, false))
+        .join()
+        .unwrap();
+    let start = battle.get_bot(index);
+    for _ in 0..10 {
+        battle.update();
+    }
+    assert!(battle.get_bot(index).z > start.z);
+}
@@ -0,0 +1,69 @@
+//! Battles under the `interpreter` backend, run with
+//! `cargo test --no-default-features --features interpreter`.
+#![cfg(feature = "interpreter")]
+
+mod common;
+
+use machine::{Battle, MatchMode, Rules};
+
+/// Pushes towards +x every tick.
+const RUNNING_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    i32.const 16744576))
+"#;
+
+/// A bot looping until it runs out of fuel.
+const SPINNING_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (loop $spin (br $spin))
+    i32.const 8421504))
+"#;
+
+fn battle(bot: &str) -> Battle {
+    let mut battle = Battle::new_with_rules(
+        MatchMode::Teams,
+        Rules {
+            max_ticks: 300,
+            ..Rules::default()
+        },
+    );
+    battle.set_match_id(b"interpreter");
+    battle.add_bot(&mut wat::parse_str(bot).unwrap()).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+}
+
+#[test]
+fn a_battle_plays_to_the_end() {
+    let mut first = battle(RUNNING_BOT);
+    let mut second = battle(RUNNING_BOT);
+
+    let mut start = battle(RUNNING_BOT);
+    start.init().unwrap();
+
+    let winner = first.execute().unwrap();
+    assert_eq!(second.execute().unwrap(), winner);
+    assert_eq!(first.get_state_hash(), second.get_state_hash());
+    assert_ne!(first.get_bot(0), start.get_bot(0));
+    assert_eq!(first.bot_faults(0).total(), 0);
+}
+
+#[test]
+fn calls_are_metered() {
+    let mut battle = battle(SPINNING_BOT);
+    battle.init().unwrap();
+    for _ in 0..5 {
+        battle.update();
+    }
+
+    assert_eq!(battle.bot_faults(0).out_of_gas, 5);
+    assert_eq!(battle.bot_faults(1).total(), 0);
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use machine::{Battle, BotLimits, MatchMode, Rules};
+
+/// Asks for more memory than the limit every tick and traps when it does not get it.
+const MEMORY_BOT: &str = r#"
+(module
+  (memory 1)
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (if (i32.eq (memory.grow (i32.const 200)) (i32.const -1))
+      (then unreachable))
+    i32.const 8421504))
+"#;
+
+/// Starts with all the memory it may have and traps without asking for more.
+const FULL_MEMORY_BOT: &str = r#"
+(module
+  (memory 128)
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    unreachable))
+"#;
+
+/// Asks for more table elements than the limit every tick and traps if it gets them.
+const TABLE_BOT: &str = r#"
+(module
+  (table 1 funcref)
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (if (i32.ne (table.grow (ref.null func) (i32.const 20000)) (i32.const -1))
+      (then unreachable))
+    i32.const 8421504))
+"#;
+
+/// Recurses forever.
+const DEEP_BOT: &str = r#"
+(module
+  (func $deep (call $deep))
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    call $deep
+    i32.const 8421504))
+"#;
+
+const TICKS: u64 = 5;
+
+fn play(bot: &str) -> Battle {
+    play_with_rules(bot, Rules::default())
+}
+
+fn play_with_rules(bot: &str, rules: Rules) -> Battle {
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.add_bot(&mut wat::parse_str(bot).unwrap()).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    for _ in 0..TICKS {
+        battle.update();
+    }
+    battle
+}
+
+#[test]
+fn failing_to_grow_the_memory_is_a_memory_limit_fault() {
+    let battle = play(MEMORY_BOT);
+
+    let faults = battle.bot_faults(0);
+    assert_eq!(faults.memory_limit, TICKS);
+    assert_eq!(faults.total(), TICKS);
+}
+
+#[test]
+fn a_trap_with_all_the_memory_is_not_a_memory_limit_fault() {
+    let battle = play(FULL_MEMORY_BOT);
+
+    let faults = battle.bot_faults(0);
+    assert_eq!(faults.trap, TICKS);
+    assert_eq!(faults.total(), TICKS);
+}
+
+#[test]
+fn the_table_can_not_grow_past_the_limit() {
+    let battle = play(TABLE_BOT);
+
+    assert_eq!(battle.bot_faults(0).total(), 0);
+}
+
+#[test]
+fn a_table_larger_than_the_limit_is_refused() {
+    let mut battle = Battle::new();
+    let mut bot =
+        wat::parse_str(TABLE_BOT.replace("(table 1 funcref)", "(table 20000 funcref)")).unwrap();
+
+    assert!(battle.add_bot(&mut bot).is_err());
+}
+
+#[test]
+fn recursing_too_deep_is_a_call_depth_fault() {
+    // shallow enough to be reached before running out of gas on either engine
+    let rules = Rules {
+        limits: BotLimits {
+            max_call_depth: 50,
+            ..BotLimits::default()
+        },
+        ..Rules::default()
+    };
+    let battle = play_with_rules(DEEP_BOT, rules);
+
+    let faults = battle.bot_faults(0);
+    assert_eq!(faults.call_depth_exceeded, TICKS);
+    assert_eq!(faults.total(), TICKS);
+}
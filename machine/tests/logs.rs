@@ -0,0 +1,102 @@
+mod common;
+
+use machine::{Battle, BotLog, LogLimits, MatchMode, Rules};
+
+/// Logs `len` bytes three times in `init` and in every update.
+fn chatty_bot(len: usize) -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"
+(module
+  (import "env" "log" (func $log (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{text}")
+  (func $chat
+    (call $log (i32.const 0) (i32.const {len}))
+    (call $log (i32.const 0) (i32.const {len}))
+    (call $log (i32.const 0) (i32.const {len})))
+  (func (export "init") (param i32) call $chat)
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    call $chat
+    i32.const 8421504))
+"#,
+        text = "x".repeat(len),
+        len = len
+    ))
+    .unwrap()
+}
+
+/// Logs 200 bytes in `observation_buffer` and again in `init`, both called before the first
+/// update.
+const SETUP_BOT: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32 i32)))
+  (memory (export "memory") 1)
+  (func (export "observation_buffer") (param i32) (result i32)
+    (call $log (i32.const 0) (i32.const 200))
+    i32.const 1024)
+  (func (export "init") (param i32)
+    (call $log (i32.const 0) (i32.const 200)))
+  (func (export "compute_actions_ext") (param i32) (result i32)
+    i32.const 8421504))
+"#;
+
+fn play(rules: Rules, mut bot: Vec<u8>, ticks: u64) -> Vec<BotLog> {
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.add_bot(&mut bot).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    for _ in 0..ticks {
+        battle.update();
+    }
+    battle.take_logs()
+}
+
+#[test]
+fn messages_over_the_tick_budget_are_cut_then_dropped() {
+    let logs = play(Rules::default(), chatty_bot(100), 1);
+
+    let lengths: Vec<usize> = logs
+        .iter()
+        .filter(|log| log.bot == 0)
+        .map(|log| log.message.len())
+        .collect();
+    // 256 bytes for init, 256 more for the update
+    assert_eq!(lengths, [100, 100, 56, 100, 100, 56]);
+    assert!(logs.iter().all(|log| log.bot == 0 && log.tick == 0));
+}
+
+#[test]
+fn the_match_budget_is_shared_by_every_tick() {
+    let rules = Rules {
+        logs: Some(LogLimits {
+            max_bytes_per_tick: 256,
+            max_bytes_per_match: 600,
+        }),
+        ..Rules::default()
+    };
+    let logs = play(rules, chatty_bot(100), 4);
+
+    let total: usize = logs.iter().map(|log| log.message.len()).sum();
+    assert_eq!(total, 600);
+    assert!(logs.iter().all(|log| log.tick < 2), "{:?}", logs);
+}
+
+#[test]
+fn the_calls_before_the_first_update_share_a_budget() {
+    let logs = play(Rules::default(), wat::parse_str(SETUP_BOT).unwrap(), 0);
+
+    let lengths: Vec<usize> = logs.iter().map(|log| log.message.len()).collect();
+    assert_eq!(lengths, [200, 56]);
+}
+
+#[test]
+fn nothing_is_logged_without_limits() {
+    let rules = Rules {
+        logs: None,
+        ..Rules::default()
+    };
+
+    assert!(play(rules, chatty_bot(100), 3).is_empty());
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use machine::{
+    Action, Battle, MatchMode, Observation, Perception, ReferenceBot, Rules, ENEMY_OUT_OF_VIEW,
+};
+
+/// Legacy bot keeping the enemy position it was given last, stored once the match is over.
+const ENEMY_BOT: &str = r#"
+(module
+  (import "env" "storage_write" (func $write (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (f32.store (i32.const 0) (local.get 6))
+    (f32.store (i32.const 4) (local.get 7))
+    (f32.store (i32.const 8) (local.get 8))
+    i32.const 8421504)
+  (func (export "on_match_end") (param i32)
+    (drop (call $write (i32.const 0) (i32.const 12)))))
+"#;
+
+fn battle(perception: Perception) -> Battle {
+    Battle::new_with_rules(
+        MatchMode::Teams,
+        Rules {
+            perception,
+            ..Rules::default()
+        },
+    )
+}
+
+/// Plays a few ticks against an idle bot, returns what the controller saw each tick: its
+/// position and the opponents in view.
+fn observe(perception: Perception, match_id: &[u8]) -> Vec<([f32; 3], Vec<[f32; 3]>)> {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut battle = battle(perception);
+    battle.set_match_id(match_id);
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    let recorder = seen.clone();
+    battle
+        .add_controller(move |observation: &Observation| {
+            recorder
+                .lock()
+                .unwrap()
+                .push((observation.self_position, observation.opponents.to_vec()));
+            Action::NEUTRAL
+        })
+        .unwrap();
+    battle.init().unwrap();
+    for _ in 0..5 {
+        battle.update();
+    }
+    drop(battle);
+    Arc::into_inner(seen).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn actions_take_effect_after_the_delay() {
+    let mut battle = battle(Perception {
+        action_delay: 3,
+        ..Perception::default()
+    });
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+        .add_controller(|_: &Observation| Action {
+            impulse: [0.0, 0.0, 1.0],
+            boost: false,
+        })
+        .unwrap();
+    battle.init().unwrap();
+    let start = battle.get_bot(1);
+    for _ in 0..3 {
+        battle.update();
+    }
+    assert_eq!(battle.get_bot(1).z, start.z);
+
+    for _ in 0..3 {
+        battle.update();
+    }
+    assert!(battle.get_bot(1).z > start.z);
+}
+
+#[test]
+fn noise_is_seeded_by_the_match() {
+    let noisy = Perception {
+        position_noise: 0.5,
+        ..Perception::default()
+    };
+    let exact = observe(Perception::default(), b"a");
+
+    assert_eq!(observe(noisy, b"a"), observe(noisy, b"a"));
+    assert_ne!(observe(noisy, b"a"), observe(noisy, b"b"));
+    for ((position, _), (noisy_position, _)) in exact.iter().zip(observe(noisy, b"a")) {
+        assert_ne!(*position, noisy_position);
+        for axis in 0..3 {
+            assert!((position[axis] - noisy_position[axis]).abs() <= 0.5);
+        }
+    }
+}
+
+#[test]
+fn opponents_out_of_view_are_hidden() {
+    let short_sighted = Perception {
+        view_radius: Some(1.0),
+        ..Perception::default()
+    };
+
+    assert!(observe(Perception::default(), b"a")
+        .iter()
+        .all(|(_, opponents)| opponents.len() == 1));
+    assert!(observe(short_sighted, b"a")
+        .iter()
+        .all(|(_, opponents)| opponents.is_empty()));
+}
+
+/// Enemy position seen last by [`ENEMY_BOT`] playing against an idle bot.
+fn legacy_enemy(perception: Perception) -> [f32; 3] {
+    let mut battle = battle(perception);
+    battle
+        .add_bot(&mut wat::parse_str(ENEMY_BOT).unwrap())
+        .unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    for _ in 0..3 {
+        battle.update();
+    }
+    battle.end(0);
+    let stored = battle.get_bot_storage(0);
+    [0, 1, 2].map(|axis| f32::from_le_bytes(stored[4 * axis..4 * axis + 4].try_into().unwrap()))
+}
+
+#[test]
+fn legacy_bots_are_told_when_no_enemy_is_in_view() {
+    let short_sighted = Perception {
+        view_radius: Some(1.0),
+        ..Perception::default()
+    };
+
+    assert_eq!(legacy_enemy(short_sighted), ENEMY_OUT_OF_VIEW);
+    let enemy = legacy_enemy(Perception::default());
+    assert_ne!(enemy, ENEMY_OUT_OF_VIEW);
+    assert!(enemy.iter().all(|value| value.abs() < 100.0), "{:?}", enemy);
+}
+
+#[test]
+fn snapshots_keep_the_noise_and_pending_actions() {
+    let mut battle = battle(Perception {
+        action_delay: 4,
+        position_noise: 0.3,
+        view_radius: None,
+    });
+    battle.set_match_id(b"a");
+    battle.add_reference_bot(ReferenceBot::Chaser).unwrap();
+    battle.add_reference_bot(ReferenceBot::Chaser).unwrap();
+    battle.init().unwrap();
+    for _ in 0..10 {
+        battle.update();
+    }
+    let snapshot = battle.snapshot().unwrap();
+    for _ in 0..20 {
+        battle.update();
+    }
+    let expected = (battle.get_bot(0), battle.get_bot(1), battle.observation(0));
+
+    battle.restore(&snapshot).unwrap();
+    for _ in 0..20 {
+        battle.update();
+    }
+    assert_eq!(
+        (battle.get_bot(0), battle.get_bot(1), battle.observation(0)),
+        expected
+    );
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use machine::{Action, Battle, BotBody, Energy, MatchMode, Observation, Rules};
+
+const TICKS: u64 = 30;
+
+/// Bot 0 plays `action` every tick against an idle bot from a standstill, returns how far it
+/// went along x.
+fn travel(body: BotBody, action: Action, ticks: u64) -> (f32, Battle) {
+    let mut battle = Battle::new_with_rules(
+        MatchMode::Teams,
+        Rules {
+            bot: body,
+            initial_impulse: [0.0; 3],
+            ..Rules::default()
+        },
+    );
+    battle
+        .add_controller(move |_: &Observation| action)
+        .unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    let start = battle.get_bot(0).x;
+    for _ in 0..ticks {
+        battle.update();
+    }
+    (battle.get_bot(0).x - start, battle)
+}
+
+fn forward(x: f32, boost: bool) -> Action {
+    Action {
+        impulse: [x, 0.0, 0.0],
+        boost,
+    }
+}
+
+fn diagonal() -> Action {
+    Action {
+        impulse: [1.0, 1.0, 0.0],
+        boost: false,
+    }
+}
+
+#[test]
+fn default_rule_set_is_version_1() {
+    assert_eq!(Rules::default().version, 1);
+    assert_eq!(Rules::default().bot, BotBody::default());
+}
+
+#[test]
+fn legacy_impulse_is_clamped_per_axis() {
+    let (unit, _) = travel(BotBody::default(), forward(1.0, false), 5);
+    let (huge, _) = travel(BotBody::default(), forward(10.0, false), 5);
+    let (diagonal, _) = travel(BotBody::default(), diagonal(), 5);
+
+    assert_eq!(unit, huge);
+    assert_eq!(unit, diagonal);
+}
+
+#[test]
+fn bounded_impulse_is_clamped_to_unit_length() {
+    let (unit, _) = travel(BotBody::bounded(), forward(1.0, false), TICKS);
+    let (huge, _) = travel(BotBody::bounded(), forward(10.0, false), TICKS);
+    let (diagonal, _) = travel(BotBody::bounded(), diagonal(), TICKS);
+
+    assert_eq!(unit, huge);
+    assert!(unit > diagonal, "{} <= {}", unit, diagonal);
+}
+
+#[test]
+fn max_impulse_scales_the_action() {
+    let weak = BotBody {
+        max_impulse: Some(0.05),
+        ..BotBody::bounded()
+    };
+    let (strong, _) = travel(BotBody::bounded(), forward(1.0, false), TICKS);
+    let (weak, _) = travel(weak, forward(1.0, false), TICKS);
+
+    assert!(strong > weak, "{} <= {}", strong, weak);
+}
+
+#[test]
+fn speed_is_capped() {
+    let slow = BotBody {
+        max_speed: Some(1.0),
+        ..BotBody::bounded()
+    };
+    let (capped, _) = travel(slow, forward(1.0, false), TICKS);
+    let (free, _) = travel(BotBody::bounded(), forward(1.0, false), TICKS);
+
+    // at most max_speed, plus what a single impulse adds on top of it, for each tick
+    let per_tick = (1.0 + slow.max_impulse.unwrap() / slow.mass.unwrap()) / 60.0;
+    assert!(capped <= per_tick * TICKS as f32, "{}", capped);
+    assert!(free > capped);
+}
+
+#[test]
+fn boost_drains_the_energy_meter() {
+    let energy = Energy::default();
+    let body = BotBody {
+        energy: Some(energy),
+        ..BotBody::bounded()
+    };
+    let (boosted, battle) = travel(body, forward(1.0, true), 10);
+    assert_eq!(
+        battle.get_bot_energy(0),
+        energy.capacity - 10.0 * energy.boost_cost_per_tick
+    );
+
+    let (plain, battle) = travel(body, forward(1.0, false), 10);
+    assert_eq!(battle.get_bot_energy(0), energy.capacity);
+    assert!(boosted > plain, "{} <= {}", boosted, plain);
+}
+
+#[test]
+fn energy_regenerates_once_empty() {
+    let energy = Energy {
+        capacity: 4.0,
+        regeneration_per_tick: 1.0,
+        boost_cost_per_tick: 2.0,
+        boost_multiplier: 3.0,
+    };
+    let body = BotBody {
+        energy: Some(energy),
+        ..BotBody::default()
+    };
+    // two boosted ticks empty the meter, the third can not boost and regenerates
+    let (_, battle) = travel(body, forward(1.0, true), 3);
+    assert_eq!(battle.get_bot_energy(0), 1.0);
+}
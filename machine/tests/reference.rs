@@ -0,0 +1,83 @@
+mod common;
+
+use machine::{Battle, ReferenceBot, ScriptedMove};
+
+fn distance_to_ball(battle: &Battle, index: usize) -> f32 {
+    let (bot, ball) = (battle.get_bot(index), battle.get_ball());
+    ((bot.x - ball.x).powi(2) + (bot.z - ball.z).powi(2)).sqrt()
+}
+
+#[test]
+fn every_reference_bot_plays() {
+    for name in ReferenceBot::NAMES {
+        let mut battle = Battle::new();
+        battle.add_bot(&mut common::idle_bot()).unwrap();
+        battle.add_reference_bot(name.parse().unwrap()).unwrap();
+        battle.init().unwrap();
+        for _ in 0..100 {
+            battle.update();
+        }
+
+        assert_eq!(battle.bot_faults(1).total(), 0, "{}", name);
+        assert_eq!(battle.get_bot_team(1), 1);
+    }
+}
+
+#[test]
+fn unknown_reference_bot() {
+    let err = "striker".parse::<ReferenceBot>().unwrap_err();
+    assert!(err.contains("chaser"), "{}", err);
+}
+
+#[test]
+fn chaser_goes_for_the_ball() {
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.add_reference_bot(ReferenceBot::Chaser).unwrap();
+    battle.init().unwrap();
+    let start = distance_to_ball(&battle, 1);
+    for _ in 0..30 {
+        battle.update();
+    }
+
+    assert!(distance_to_ball(&battle, 1) < start - 1.0);
+}
+
+#[test]
+fn scripted_bot_follows_its_script() {
+    let script = vec![ScriptedMove {
+        ticks: 1000,
+        impulse: [0.0, 0.0, 1.0],
+        boost: false,
+    }];
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+        .add_reference_bot(ReferenceBot::Scripted(script))
+        .unwrap();
+    battle.init().unwrap();
+    let start = battle.get_bot(1);
+    for _ in 0..30 {
+        battle.update();
+    }
+
+    assert!(battle.get_bot(1).z > start.z + 1.0);
+}
+
+#[test]
+fn random_walk_is_seeded_by_the_match() {
+    let play = |match_id: &[u8]| {
+        let mut battle = Battle::new();
+        battle.set_match_id(match_id);
+        battle.add_bot(&mut common::idle_bot()).unwrap();
+        battle.add_reference_bot(ReferenceBot::Random).unwrap();
+        battle.init().unwrap();
+        for _ in 0..30 {
+            battle.update();
+        }
+        battle.get_bot(1)
+    };
+
+    assert_eq!(play(b"a"), play(b"a"));
+    assert_ne!(play(b"a"), play(b"b"));
+}
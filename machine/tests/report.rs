@@ -0,0 +1,59 @@
+mod common;
+
+use machine::Battle;
+
+/// A bot looping until it runs out of gas.
+const SPINNING_BOT: &str = r#"
+(module
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (loop $spin (br $spin))
+    i32.const 8421504))
+"#;
+
+const TICKS: u64 = 10;
+
+fn play(mut bot: Vec<u8>, ticks: u64) -> Battle {
+    let mut battle = Battle::new();
+    battle.add_bot(&mut bot).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+    for _ in 0..ticks {
+        battle.update();
+    }
+    battle
+}
+
+#[test]
+fn gas_usage_counts_every_call() {
+    let battle = play(common::idle_bot(), TICKS);
+
+    let gas = battle.bot_gas(0);
+    assert_eq!(gas.calls, TICKS);
+    assert!(gas.total > 0);
+    assert!(gas.max <= gas.total);
+    assert_eq!(battle.bot_gas(1), gas);
+}
+
+#[test]
+fn running_out_of_gas_uses_all_of_it() {
+    let battle = play(wat::parse_str(SPINNING_BOT).unwrap(), TICKS);
+
+    let gas = battle.bot_gas(0);
+    assert_eq!(battle.bot_faults(0).out_of_gas, TICKS);
+    assert_eq!(gas.calls, TICKS);
+    assert_eq!(gas.total, TICKS * gas.max);
+    assert!(gas.max > battle.bot_gas(1).max);
+}
+
+#[test]
+fn state_hash_is_reproducible() {
+    let first = play(common::idle_bot(), TICKS);
+    let second = play(common::idle_bot(), TICKS);
+    let longer = play(common::idle_bot(), TICKS + 1);
+
+    assert_eq!(first.get_state_hash(), second.get_state_hash());
+    assert_ne!(first.get_state_hash(), longer.get_state_hash());
+}
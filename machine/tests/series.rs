@@ -0,0 +1,54 @@
+mod common;
+
+use machine::{play_series, Leg, Rules, SeriesResult};
+
+fn rules() -> Rules {
+    Rules {
+        max_ticks: 20,
+        ..Rules::default()
+    }
+}
+
+fn result(wins_a: u32, wins_b: u32, draws: u32) -> SeriesResult {
+    SeriesResult {
+        legs: Vec::new(),
+        wins_a,
+        wins_b,
+        draws,
+    }
+}
+
+#[test]
+fn series_winner_counts_legs() {
+    assert_eq!(result(2, 0, 0).winner(), 1);
+    assert_eq!(result(0, 1, 1).winner(), 2);
+    assert_eq!(result(1, 1, 0).winner(), 0);
+    assert_eq!(result(0, 0, 2).winner(), 0);
+}
+
+#[test]
+fn series_plays_both_ends() {
+    let series = play_series(&[common::idle_bot()], &[common::idle_bot()], &rules(), None).unwrap();
+
+    assert_eq!(
+        series.legs,
+        vec![
+            Leg {
+                swapped: false,
+                winner: 0
+            },
+            Leg {
+                swapped: true,
+                winner: 0
+            },
+        ]
+    );
+    assert_eq!((series.wins_a, series.wins_b, series.draws), (0, 0, 2));
+    assert_eq!(series.winner(), 0);
+}
+
+#[test]
+fn series_needs_both_sides() {
+    let err = play_series(&[common::idle_bot()], &[], &rules(), None).unwrap_err();
+    assert!(err.contains("at least 2 teams"), "{}", err);
+}
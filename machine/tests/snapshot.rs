@@ -0,0 +1,81 @@
+use machine::Battle;
+
+/// Pushes along z a little harder every tick, counting the ticks in a global it does not
+/// export, then starts over every 64 ticks.
+const COUNTER_BOT: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $ticks (mut i32) (i32.const 0))
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))
+    (i32.add (i32.const 8421504) (i32.and (global.get $ticks) (i32.const 63)))))
+"#;
+
+/// The same with a second private global, so its state does not fit [`COUNTER_BOT`].
+const WIDER_COUNTER_BOT: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $spare (mut i32) (i32.const 0))
+  (global $ticks (mut i32) (i32.const 0))
+  (func (export "init") (param i32))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))
+    (i32.add (i32.const 8421504) (i32.and (global.get $ticks) (i32.const 63)))))
+"#;
+
+fn battle(bots: [&str; 2]) -> Battle {
+    let mut battle = Battle::new();
+    battle.set_match_id(b"snapshot");
+    for bot in bots {
+        battle.add_bot(&mut wat::parse_str(bot).unwrap()).unwrap();
+    }
+    battle.init().unwrap();
+    battle
+}
+
+fn play(battle: &mut Battle, ticks: u64) {
+    for _ in 0..ticks {
+        battle.update();
+    }
+}
+
+#[test]
+fn restored_battle_matches_an_uninterrupted_run() {
+    let mut uninterrupted = battle([COUNTER_BOT, COUNTER_BOT]);
+    play(&mut uninterrupted, 100);
+
+    let mut original = battle([COUNTER_BOT, COUNTER_BOT]);
+    play(&mut original, 40);
+    let snapshot = original.snapshot().unwrap();
+
+    let mut resumed = battle([COUNTER_BOT, COUNTER_BOT]);
+    resumed.restore(&snapshot).unwrap();
+    play(&mut resumed, 60);
+
+    assert_eq!(resumed.get_tick(), uninterrupted.get_tick());
+    assert_eq!(resumed.get_state_hash(), uninterrupted.get_state_hash());
+}
+
+#[test]
+fn failed_restore_changes_nothing() {
+    let mut original = battle([WIDER_COUNTER_BOT, WIDER_COUNTER_BOT]);
+    play(&mut original, 40);
+    let snapshot = original.snapshot().unwrap();
+
+    // bot 1 can not take the state of the wider bot, bot 0 could
+    let mut untouched = battle([WIDER_COUNTER_BOT, COUNTER_BOT]);
+    let mut target = battle([WIDER_COUNTER_BOT, COUNTER_BOT]);
+    play(&mut untouched, 10);
+    play(&mut target, 10);
+    let err = target.restore(&snapshot).unwrap_err();
+    assert!(err.starts_with("bot 2"), "{}", err);
+
+    play(&mut untouched, 50);
+    play(&mut target, 50);
+    assert_eq!(target.get_state_hash(), untouched.get_state_hash());
+}
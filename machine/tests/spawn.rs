@@ -0,0 +1,130 @@
+mod common;
+
+use machine::{Battle, MatchMode, Position, Rules, SpawnError, SpawnLayout};
+
+const EPSILON: f32 = 1e-5;
+
+fn assert_position(actual: Position, x: f32, y: f32, z: f32) {
+    assert!(
+        (actual.x - x).abs() < EPSILON
+            && (actual.y - y).abs() < EPSILON
+            && (actual.z - z).abs() < EPSILON,
+        "expected ({}, {}, {}), got {:?}",
+        x,
+        y,
+        z,
+        actual
+    );
+}
+
+/// Ground level + default radius + spawn clearance.
+const SPAWN_HEIGHT: f32 = 0.1 + 0.5 + 0.05;
+
+#[test]
+fn duel_spawns_where_requested() {
+    let mut battle = Battle::new();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.init().unwrap();
+
+    assert_position(battle.get_bot(0), -9.0, SPAWN_HEIGHT, 0.0);
+    assert_position(battle.get_bot(1), 9.0, SPAWN_HEIGHT, 0.0);
+    assert_position(battle.get_ball(), 0.0, 10.5, 0.0);
+}
+
+#[test]
+fn team_spawns_are_mirrored() {
+    let mut battle = Battle::new();
+    for team in [0, 0, 0, 1, 1, 1] {
+        battle
+            .add_bot_to_team(&mut common::idle_bot(), team)
+            .unwrap();
+    }
+    battle.init().unwrap();
+
+    assert_position(battle.get_bot(0), -9.0, SPAWN_HEIGHT, 0.0);
+    assert_position(battle.get_bot(1), -9.0, SPAWN_HEIGHT, -2.0);
+    assert_position(battle.get_bot(2), -9.0, SPAWN_HEIGHT, 2.0);
+    assert_position(battle.get_bot(3), 9.0, SPAWN_HEIGHT, 0.0);
+    assert_position(battle.get_bot(4), 9.0, SPAWN_HEIGHT, 2.0);
+    assert_position(battle.get_bot(5), 9.0, SPAWN_HEIGHT, -2.0);
+    assert_position(battle.get_ball(), 0.0, 10.5, 0.0);
+}
+
+#[test]
+fn free_for_all_spawns_one_bot_per_wall() {
+    let mut battle = Battle::new_with_mode(MatchMode::FreeForAll);
+    for _ in 0..4 {
+        battle.add_bot(&mut common::idle_bot()).unwrap();
+    }
+    battle.init().unwrap();
+
+    assert_position(battle.get_bot(0), -9.0, SPAWN_HEIGHT, 0.0);
+    assert_position(battle.get_bot(1), 9.0, SPAWN_HEIGHT, 0.0);
+    assert_position(battle.get_bot(2), 0.0, SPAWN_HEIGHT, -9.0);
+    assert_position(battle.get_bot(3), 0.0, SPAWN_HEIGHT, 9.0);
+    assert_position(battle.get_ball(), 0.0, 10.5, 0.0);
+}
+
+#[test]
+fn spawn_inside_a_wall_is_rejected() {
+    let rules = Rules {
+        spawn: SpawnLayout {
+            slots: vec![[-10.5, 0.0]],
+        },
+        ..Rules::default()
+    };
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+
+    assert!(matches!(
+        battle.validate_spawns(),
+        Err(SpawnError::Arena { .. })
+    ));
+    let err = battle.init().unwrap_err();
+    assert!(err.contains("invalid spawn layout"), "{}", err);
+}
+
+#[test]
+fn a_single_team_can_not_play() {
+    let mut battle = Battle::new();
+    battle.add_bot_to_team(&mut common::idle_bot(), 0).unwrap();
+    battle.add_bot_to_team(&mut common::idle_bot(), 0).unwrap();
+
+    let err = battle.execute().unwrap_err();
+    assert!(err.contains("at least 2 teams"), "{}", err);
+}
+
+#[test]
+fn a_full_team_is_rejected() {
+    let mut battle = Battle::new();
+    let slots = Rules::default().spawn.slots.len();
+    for _ in 0..slots {
+        battle.add_bot_to_team(&mut common::idle_bot(), 0).unwrap();
+    }
+
+    let err = battle
+        .add_bot_to_team(&mut common::idle_bot(), 0)
+        .unwrap_err();
+    assert!(err.contains("is full"), "{}", err);
+}
+
+#[test]
+fn overlapping_bots_are_rejected() {
+    let rules = Rules {
+        spawn: SpawnLayout {
+            slots: vec![[-5.0, 0.0], [-5.0, 0.5]],
+        },
+        ..Rules::default()
+    };
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.add_bot_to_team(&mut common::idle_bot(), 0).unwrap();
+    battle.add_bot_to_team(&mut common::idle_bot(), 0).unwrap();
+    battle.add_bot_to_team(&mut common::idle_bot(), 1).unwrap();
+
+    assert_eq!(
+        battle.validate_spawns(),
+        Err(SpawnError::Bot { bot: 0, other: 1 })
+    );
+}
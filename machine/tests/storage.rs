@@ -0,0 +1,106 @@
+mod common;
+
+use machine::{Battle, MatchMode, Rules, StorageLimits};
+
+/// Counts its matches in the first 4 bytes of its storage, writing `len` bytes at the end
+/// of each. It also tries to write during every update, which is not allowed.
+fn counting_bot(len: u32) -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"
+(module
+  (import "env" "storage_read" (func $read (param i32 i32) (result i32)))
+  (import "env" "storage_write" (func $write (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "init") (param i32)
+    (drop (call $read (i32.const 0) (i32.const 4))))
+  (func (export "compute_actions")
+    (param f32 f32 f32 f32 f32 f32 f32 f32 f32)
+    (result i32)
+    (i32.store (i32.const 16) (i32.const 1000))
+    (drop (call $write (i32.const 16) (i32.const 4)))
+    i32.const 8421504)
+  (func (export "on_match_end") (param i32)
+    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+    (drop (call $write (i32.const 0) (i32.const {len})))))
+"#,
+        len = len
+    ))
+    .unwrap()
+}
+
+/// Play a short match with `storage` given to the first bot, return what it stored.
+fn play(rules: &Rules, bot: &[u8], storage: Vec<u8>) -> Vec<u8> {
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules.clone());
+    battle.add_bot(&mut bot.to_vec()).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.set_bot_storage(0, storage).unwrap();
+    battle.init().unwrap();
+    for _ in 0..3 {
+        battle.update();
+    }
+    battle.end(0);
+    battle.get_bot_storage(0)
+}
+
+#[test]
+fn storage_persists_across_matches() {
+    let rules = Rules::default();
+    let bot = counting_bot(4);
+
+    let mut storage = Vec::new();
+    for _ in 0..3 {
+        storage = play(&rules, &bot, storage);
+    }
+
+    assert_eq!(storage, 3u32.to_le_bytes());
+}
+
+#[test]
+fn storage_is_read_only_during_the_match() {
+    let mut battle = Battle::new();
+    battle.add_bot(&mut counting_bot(4)).unwrap();
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle.set_bot_storage(0, vec![1, 0, 0, 0]).unwrap();
+    battle.init().unwrap();
+    for _ in 0..3 {
+        battle.update();
+    }
+
+    assert_eq!(battle.get_bot_storage(0), [1, 0, 0, 0]);
+    battle.end(0);
+    assert_eq!(battle.get_bot_storage(0), [2, 0, 0, 0]);
+}
+
+#[test]
+fn a_write_over_the_limit_is_refused() {
+    let rules = Rules {
+        storage: Some(StorageLimits { max_bytes: 8 }),
+        ..Rules::default()
+    };
+
+    assert_eq!(play(&rules, &counting_bot(8), Vec::new()).len(), 8);
+    assert_eq!(play(&rules, &counting_bot(9), vec![7]), [7]);
+}
+
+#[test]
+fn storage_given_over_the_limit_is_refused() {
+    let rules = Rules {
+        storage: Some(StorageLimits { max_bytes: 8 }),
+        ..Rules::default()
+    };
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules);
+    battle.add_bot(&mut counting_bot(4)).unwrap();
+
+    assert!(battle.set_bot_storage(0, vec![0; 9]).is_err());
+    assert!(battle.set_bot_storage(0, vec![0; 8]).is_ok());
+}
+
+#[test]
+fn nothing_is_stored_without_storage() {
+    let rules = Rules {
+        storage: None,
+        ..Rules::default()
+    };
+
+    assert!(play(&rules, &counting_bot(4), Vec::new()).is_empty());
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use machine::{Action, Battle, MatchMode, Observation, ReferenceBot, Rules, ScriptedMove, Timing};
+
+const FORWARD: Action = Action {
+    impulse: [0.0, 0.0, 1.0],
+    boost: false,
+};
+
+fn rules(timing: Timing) -> Rules {
+    Rules {
+        timing,
+        ..Rules::default()
+    }
+}
+
+/// Plays `ticks` with a bot pushing forward, returns how many times it was asked and where
+/// it ended up.
+fn push_forward(timing: Timing, ticks: u64) -> (u64, machine::Position) {
+    let calls = Arc::new(AtomicU64::new(0));
+    let mut battle = Battle::new_with_rules(MatchMode::Teams, rules(timing));
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    let counter = calls.clone();
+    battle
+        .add_controller(move |_: &Observation| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            FORWARD
+        })
+        .unwrap();
+    battle.init().unwrap();
+    for _ in 0..ticks {
+        battle.update();
+    }
+    (calls.load(Ordering::Relaxed), battle.get_bot(1))
+}
+
+#[test]
+fn actions_are_held_between_decisions() {
+    let every_tick = push_forward(Timing::default(), 12);
+    let every_4_ticks = push_forward(
+        Timing {
+            decision_interval: 4,
+            ..Timing::default()
+        },
+        12,
+    );
+
+    assert_eq!(every_tick.0, 12);
+    assert_eq!(every_4_ticks.0, 3);
+    assert_eq!(every_tick.1, every_4_ticks.1);
+}
+
+#[test]
+fn dt_sets_the_pace_of_a_tick() {
+    let fall = |timing: Timing| {
+        let mut battle = Battle::new_with_rules(MatchMode::Teams, rules(timing));
+        battle.add_bot(&mut common::idle_bot()).unwrap();
+        battle.add_bot(&mut common::idle_bot()).unwrap();
+        battle.init().unwrap();
+        let start = battle.get_ball().y;
+        for _ in 0..20 {
+            battle.update();
+        }
+        start - battle.get_ball().y
+    };
+    let normal = fall(Timing::default());
+    let slow = fall(Timing {
+        dt: 1.0 / 120.0,
+        ..Timing::default()
+    });
+    let substepped = fall(Timing {
+        substeps: 4,
+        ..Timing::default()
+    });
+
+    assert!(slow < normal / 2.0, "{} {}", slow, normal);
+    assert!(
+        (substepped - normal).abs() < normal * 0.1,
+        "{} {}",
+        substepped,
+        normal
+    );
+}
+
+#[test]
+fn held_actions_are_snapshotted() {
+    let script = vec![
+        ScriptedMove {
+            ticks: 1,
+            impulse: [0.0, 0.0, 1.0],
+            boost: false,
+        },
+        ScriptedMove {
+            ticks: 1,
+            impulse: [1.0, 0.0, 0.0],
+            boost: false,
+        },
+        ScriptedMove {
+            ticks: 1,
+            impulse: [0.0, 0.0, -1.0],
+            boost: false,
+        },
+    ];
+    let mut battle = Battle::new_with_rules(
+        MatchMode::Teams,
+        rules(Timing {
+            decision_interval: 5,
+            ..Timing::default()
+        }),
+    );
+    battle.add_bot(&mut common::idle_bot()).unwrap();
+    battle
+        .add_reference_bot(ReferenceBot::Scripted(script))
+        .unwrap();
+    battle.init().unwrap();
+    // holding the first move, the last one is held when restoring
+    for _ in 0..2 {
+        battle.update();
+    }
+    let snapshot = battle.snapshot().unwrap();
+    for _ in 0..10 {
+        battle.update();
+    }
+    let expected = battle.get_bot(1);
+
+    battle.restore(&snapshot).unwrap();
+    for _ in 0..10 {
+        battle.update();
+    }
+    assert_eq!(battle.get_bot(1), expected);
+}
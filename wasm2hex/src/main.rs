@@ -1,3 +1,4 @@
+use bot_validator::{validate, Limits};
 use clap::Parser;
 use lz4_flex::compress;
 use serde::{Deserialize, Serialize};
@@ -33,6 +34,12 @@ fn main() {
         .read_to_end(&mut input_data)
         .expect("failed to read file");
 
+    let report = validate(&input_data, &Limits::default());
+    if !report.is_valid() {
+        eprintln!("{}", report);
+        std::process::exit(1);
+    }
+
     let uncompressed_hex = {
         // Convert compressed data to hex string
         let mut hex_string = hex::encode(&input_data);
@@ -1,26 +1,41 @@
-import { Battle } from 'machine';
+import { Battle, HumanController, MatchMode } from 'machine';
 import { writable } from 'svelte/store';
+import { listen, steer } from './input';
 
-const MAX_NUM_UPDATES = 1_000_000; // TODO share with machine
+// a snapshot of the battle is kept every SNAPSHOT_INTERVAL steps so the replay can be scrubbed
+const SNAPSHOT_INTERVAL = 30;
+const MAX_SNAPSHOTS = 200;
 
 export type Position = { x: number; y: number; z: number };
 
+// a bot, or a person playing with the keyboard or a gamepad
+export type RosterEntry = { wasm: Uint8Array; team?: number } | { human: true; team?: number };
+
+export type BotState = Position & { team: number };
+
 export type BattleState = {
 	step: number;
 	battle?: Battle;
-	bot1: Position;
-	bot2: Position;
-	ball: Position;
+	bots: BotState[];
+	// more than one when the rule set plays several balls at once
+	balls: Position[];
 	initialized: boolean;
 	winner: number;
+	paused: boolean;
+	// furthest step played so far, the range available for scrubbing
+	lastStep: number;
+	// a person plays: their moves can not be replayed, so there are no snapshots to scrub
+	human: boolean;
 };
 const $battle: BattleState = {
 	step: 0,
-	bot1: { x: 0, y: 0, z: 0 },
-	bot2: { x: 0, y: 0, z: 0 },
-	ball: { x: 0, y: 0, z: 0 },
+	lastStep: 0,
+	paused: false,
+	bots: [],
+	balls: [],
 	winner: 0,
-	initialized: false
+	initialized: false,
+	human: false
 };
 const _battle = writable($battle);
 
@@ -32,61 +47,182 @@ function updateState() {
 	_battle.set($battle);
 }
 
+let snapshots: { step: number; data: Uint8Array }[] = [];
+
+let humans: { controller: HumanController; team: number }[] = [];
+let stopListening: (() => void) | undefined;
+
+function step(battle: Battle): number {
+	const result = battle.update();
+	// bot logs are shown the first time a step is played, not again when scrubbing over it
+	const logs = battle.drain_logs();
+	if ($battle.step >= $battle.lastStep) {
+		for (const log of logs) {
+			console.log(log);
+		}
+	}
+	if (result == 0) {
+		$battle.step++;
+		if ($battle.step > $battle.lastStep) {
+			$battle.lastStep = $battle.step;
+			if (!$battle.human && $battle.step % SNAPSHOT_INTERVAL == 0) {
+				snapshots.push({ step: $battle.step, data: battle.snapshot() });
+				if (snapshots.length > MAX_SNAPSHOTS) {
+					snapshots.shift();
+				}
+			}
+		}
+	}
+	return result;
+}
+
+// the battle runs on its own clock, whatever the refresh rate of the display: every frame plays
+// the ticks that fit in the time elapsed since the previous one, and shows the positions
+// interpolated between the last two ticks
+const MAX_TICKS_PER_FRAME = 10;
+let accumulator = 0;
+let lastFrame: number | undefined;
+let previous: { bots: Position[]; balls: Position[] } = { bots: [], balls: [] };
+
 let currentPendingAnimationFrame: number;
-function update() {
-	if ($battle.battle) {
-		const result = $battle.battle.update();
-		if (result > 0) {
-			$battle.winner = result;
-			updateState();
-			console.log(`Winner: ${result}`);
-			return;
-		} else {
-			$battle.step++;
-			if ($battle.step > MAX_NUM_UPDATES) {
+function update(now: number = performance.now()) {
+	if ($battle.battle && !$battle.paused) {
+		const battle = $battle.battle;
+		const tickDuration = battle.get_tick_duration() * 1000;
+		accumulator += lastFrame === undefined ? tickDuration : now - lastFrame;
+		lastFrame = now;
+		// after a hiccup (or a hidden tab), catch up a little then drop the rest
+		accumulator = Math.min(accumulator, tickDuration * MAX_TICKS_PER_FRAME);
+
+		for (const human of humans) {
+			steer(human.controller, human.team);
+		}
+		while (accumulator >= tickDuration) {
+			accumulator -= tickDuration;
+			previous = currentPositions(battle);
+			const result = step(battle);
+			if (result > 0) {
+				$battle.winner = result;
+				readPositions(battle);
+				updateState();
+				console.log(`Winner: ${result}`);
+				return;
+			} else if ($battle.step > Number(battle.get_max_ticks())) {
+				readPositions(battle);
 				updateState();
 				console.log(`DRAW: ${$battle.step} updates executed.`);
 				return;
 			}
 		}
 
-		const bot1Postion = $battle.battle.get_bot1();
-		$battle.bot1.x = bot1Postion.x;
-		$battle.bot1.y = bot1Postion.y;
-		$battle.bot1.z = bot1Postion.z;
-
-		const bot2Postion = $battle.battle.get_bot2();
-		$battle.bot2.x = bot2Postion.x;
-		$battle.bot2.y = bot2Postion.y;
-		$battle.bot2.z = bot2Postion.z;
-
-		const ballPostion = $battle.battle.get_ball();
-		$battle.ball.x = ballPostion.x;
-		$battle.ball.y = ballPostion.y;
-		$battle.ball.z = ballPostion.z;
+		readPositions(battle, accumulator / tickDuration);
 
 		updateState();
 		currentPendingAnimationFrame = requestAnimationFrame(update);
 	}
 }
 
-let last_wasm_bytes_1: Uint8Array | undefined;
-let last_wasm_bytes_2: Uint8Array | undefined;
-export function play(wasm_bytes_1: Uint8Array, wasm_bytes_2: Uint8Array) {
+function currentPositions(battle: Battle): { bots: Position[]; balls: Position[] } {
+	const bots: Position[] = [];
+	for (let i = 0; i < battle.num_bots(); i++) {
+		const { x, y, z } = battle.get_bot(i);
+		bots.push({ x, y, z });
+	}
+	const balls: Position[] = [];
+	for (let i = 0; i < battle.num_balls(); i++) {
+		const { x, y, z } = battle.get_ball_at(i);
+		balls.push({ x, y, z });
+	}
+	return { bots, balls };
+}
+
+function lerp(from: Position | undefined, to: Position, alpha: number): Position {
+	if (!from || alpha >= 1) {
+		return to;
+	}
+	return {
+		x: from.x + (to.x - from.x) * alpha,
+		y: from.y + (to.y - from.y) * alpha,
+		z: from.z + (to.z - from.z) * alpha
+	};
+}
+
+// show the battle `alpha` of the way from the previous tick to the current one, 1 for the current
+// positions
+function readPositions(battle: Battle, alpha: number = 1) {
+	readBots(battle, alpha);
+
+	const numBalls = battle.num_balls();
+	for (let i = 0; i < numBalls; i++) {
+		$battle.balls[i] = lerp(previous.balls[i], battle.get_ball_at(i), alpha);
+	}
+}
+
+function readBots(battle: Battle, alpha: number = 1) {
+	const numBots = battle.num_bots();
+	for (let i = 0; i < numBots; i++) {
+		const position = lerp(previous.bots[i], battle.get_bot(i), alpha);
+		$battle.bots[i] = {
+			x: position.x,
+			y: position.y,
+			z: position.z,
+			team: battle.get_bot_team(i)
+		};
+	}
+}
+
+let last_roster: RosterEntry[] | undefined;
+let last_mode: MatchMode = MatchMode.Teams;
+export function play(roster: RosterEntry[], mode: MatchMode = MatchMode.Teams) {
 	$battle.winner = 0;
+	$battle.bots = [];
+	$battle.balls = [];
+	$battle.step = 0;
+	$battle.lastStep = 0;
+	$battle.paused = false;
 
-	last_wasm_bytes_1 = wasm_bytes_1;
-	last_wasm_bytes_2 = wasm_bytes_2;
-	const battle = new Battle();
+	last_roster = roster;
+	last_mode = mode;
+	const battle = Battle.new_with_mode(mode);
 	$battle.battle = battle;
 
-	battle.add_bot(wasm_bytes_1);
-	battle.add_bot(wasm_bytes_2);
+	stopListening?.();
+	stopListening = undefined;
+	for (const human of humans) {
+		human.controller.free();
+	}
+	humans = [];
+	for (const entry of roster) {
+		if ('human' in entry) {
+			const controller = new HumanController();
+			const index =
+				entry.team === undefined
+					? battle.add_human(controller)
+					: battle.add_human_to_team(controller, entry.team);
+			humans.push({ controller, team: battle.get_bot_team(index) });
+		} else if (entry.team === undefined) {
+			battle.add_bot(entry.wasm);
+		} else {
+			battle.add_bot_to_team(entry.wasm, entry.team);
+		}
+	}
+	$battle.human = humans.length > 0;
+	if ($battle.human) {
+		stopListening = listen();
+	}
 	battle.init();
+	for (const log of battle.drain_logs()) {
+		console.log(log);
+	}
+	previous = currentPositions(battle);
+	accumulator = 0;
+	lastFrame = undefined;
+	readPositions(battle);
+	snapshots = $battle.human ? [] : [{ step: 0, data: battle.snapshot() }];
 
 	updateState();
 
-	setTimeout(update, 1100);
+	setTimeout(() => update(), 1100);
 }
 
 export function replay() {
@@ -94,7 +230,51 @@ export function replay() {
 		cancelAnimationFrame(currentPendingAnimationFrame);
 		currentPendingAnimationFrame = 0;
 	}
-	if (last_wasm_bytes_1 && last_wasm_bytes_2) {
-		play(last_wasm_bytes_1, last_wasm_bytes_2);
+	if (last_roster) {
+		play(last_roster, last_mode);
+	}
+}
+
+export function pause() {
+	if (currentPendingAnimationFrame) {
+		cancelAnimationFrame(currentPendingAnimationFrame);
+		currentPendingAnimationFrame = 0;
 	}
+	$battle.paused = true;
+	updateState();
+}
+
+export function resume() {
+	if ($battle.battle && $battle.paused) {
+		$battle.paused = false;
+		$battle.winner = 0;
+		lastFrame = undefined;
+		updateState();
+		currentPendingAnimationFrame = requestAnimationFrame(update);
+	}
+}
+
+// go back (or forward) to the given step: restore the closest snapshot before it and replay from there
+export function scrubTo(target: number) {
+	const battle = $battle.battle;
+	if (!battle) {
+		return;
+	}
+	pause();
+	const snapshot = snapshots.filter((s) => s.step <= target).pop();
+	if (!snapshot) {
+		return;
+	}
+	battle.restore(snapshot.data);
+	$battle.step = snapshot.step;
+	$battle.winner = 0;
+	while ($battle.step < target) {
+		const result = step(battle);
+		if (result > 0) {
+			$battle.winner = result;
+			break;
+		}
+	}
+	readPositions(battle);
+	updateState();
 }
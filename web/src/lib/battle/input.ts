@@ -0,0 +1,90 @@
+import type { HumanController } from 'machine';
+
+// keys steering the human player, by `KeyboardEvent.code`
+const FORWARD = ['KeyW', 'ArrowUp'];
+const BACKWARD = ['KeyS', 'ArrowDown'];
+const LEFT = ['KeyA', 'ArrowLeft'];
+const RIGHT = ['KeyD', 'ArrowRight'];
+const BOOST = ['ShiftLeft', 'ShiftRight', 'Space'];
+const KEYS = new Set([...FORWARD, ...BACKWARD, ...LEFT, ...RIGHT, ...BOOST]);
+
+// gamepad sticks report small values at rest
+const DEAD_ZONE = 0.15;
+
+const pressed = new Set<string>();
+
+function onKeyDown(event: KeyboardEvent) {
+	if (KEYS.has(event.code)) {
+		pressed.add(event.code);
+		event.preventDefault();
+	}
+}
+
+function onKeyUp(event: KeyboardEvent) {
+	pressed.delete(event.code);
+}
+
+function onBlur() {
+	pressed.clear();
+}
+
+// start following the keyboard, returns a function to stop
+export function listen(): () => void {
+	window.addEventListener('keydown', onKeyDown);
+	window.addEventListener('keyup', onKeyUp);
+	window.addEventListener('blur', onBlur);
+	return () => {
+		window.removeEventListener('keydown', onKeyDown);
+		window.removeEventListener('keyup', onKeyUp);
+		window.removeEventListener('blur', onBlur);
+		pressed.clear();
+	};
+}
+
+function axis(positive: string[], negative: string[]): number {
+	const held = (keys: string[]) => keys.some((key) => pressed.has(key));
+	return (held(positive) ? 1 : 0) - (held(negative) ? 1 : 0);
+}
+
+// forward, right and boost from the keyboard, or from the first gamepad when its left stick is used
+function read(): { forward: number; right: number; boost: boolean } {
+	let forward = axis(FORWARD, BACKWARD);
+	let right = axis(RIGHT, LEFT);
+	let boost = BOOST.some((key) => pressed.has(key));
+
+	const gamepad = navigator.getGamepads?.().find((pad) => pad);
+	if (gamepad) {
+		const [x = 0, y = 0] = gamepad.axes;
+		if (Math.hypot(x, y) > DEAD_ZONE) {
+			forward = -y;
+			right = x;
+		}
+		// A or the right trigger
+		boost ||= gamepad.buttons[0]?.pressed || gamepad.buttons[7]?.pressed || false;
+	}
+	return { forward, right, boost };
+}
+
+// direction away from the goal defended by `team`, on the ground
+function forwardOf(team: number): [number, number] {
+	switch (team) {
+		case 0:
+			return [1, 0];
+		case 1:
+			return [-1, 0];
+		case 2:
+			return [0, 1];
+		default:
+			return [0, -1];
+	}
+}
+
+// set the action of the human for the next update: forward is towards the opposite side of the
+// arena, whatever the team, the machine clamps the impulse to unit length
+export function steer(human: HumanController, team: number) {
+	const { forward, right, boost } = read();
+	const [fx, fz] = forwardOf(team);
+	// right of the forward direction, with y up
+	const [rx, rz] = [-fz, fx];
+	human.set_action(forward * fx + right * rx, 0, forward * fz + right * rz, boost);
+}